@@ -0,0 +1,191 @@
+//! Input files read by the CLI
+
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use nft_vault_client::instruction::CollectionPercentages;
+use nft_vault_client::ArtistPercentage;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+/**
+ * Split file used by `init`, percentages are in basis points
+ *
+ * {
+ *   "artist_mint_percentage": 8000, "label_mint_percentage": 2000,
+ *   "artist_secondary_percentage": 5000, "label_secondary_percentage": 5000,
+ *   "artist_licensing_percentage": 6000, "label_licensing_percentage": 4000,
+ *   "artists": [{ "address": "<pubkey>", "percentage": 10000 }]
+ * }
+ */
+#[derive(Debug, Deserialize)]
+pub struct SplitFile {
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+    pub artists: Vec<ArtistSplit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtistSplit {
+    pub address: String,
+    pub percentage: u64,
+}
+
+impl SplitFile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading split file {}", path.display()))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let split_file: SplitFile = serde_json::from_str(contents).context("parsing split file")?;
+
+        if split_file.artists.is_empty() {
+            bail!("split file needs at least one artist");
+        }
+
+        let total: u64 = split_file
+            .artists
+            .iter()
+            .map(|artist| artist.percentage)
+            .sum();
+        if total != 10000 {
+            bail!("artist percentages add up to {}, expected 10000", total);
+        }
+
+        Ok(split_file)
+    }
+
+    pub fn percentages(&self) -> CollectionPercentages {
+        CollectionPercentages {
+            artist_mint_percentage: self.artist_mint_percentage,
+            label_mint_percentage: self.label_mint_percentage,
+            artist_secondary_percentage: self.artist_secondary_percentage,
+            label_secondary_percentage: self.label_secondary_percentage,
+            artist_licensing_percentage: self.artist_licensing_percentage,
+            label_licensing_percentage: self.label_licensing_percentage,
+        }
+    }
+
+    pub fn artist_splits(&self) -> Result<Vec<ArtistPercentage>> {
+        self.artists
+            .iter()
+            .map(|artist| {
+                Ok(ArtistPercentage {
+                    artist_address: parse_pubkey(&artist.address)?,
+                    allocated_percentage: artist.percentage,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MintEntry {
+    pub mint: Pubkey,
+    pub amount_paid: u64,
+}
+
+/**
+ * Mint list used by `add-nfts`, one `<mint> <amount paid in lamports>` per line.
+ * Blank lines and lines starting with # are skipped.
+ */
+pub fn read_mint_list(path: &Path) -> Result<Vec<MintEntry>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("reading mint list {}", path.display()))?;
+    parse_mint_list(&contents)
+}
+
+pub fn parse_mint_list(contents: &str) -> Result<Vec<MintEntry>> {
+    let mut entries = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (mint, amount_paid) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(mint), Some(amount_paid), None) => (mint, amount_paid),
+            _ => bail!("line {}: expected `<mint> <amount paid>`", number + 1),
+        };
+
+        entries.push(MintEntry {
+            mint: parse_pubkey(mint).with_context(|| format!("line {}", number + 1))?,
+            amount_paid: amount_paid
+                .parse()
+                .with_context(|| format!("line {}: invalid amount", number + 1))?,
+        });
+    }
+
+    Ok(entries)
+}
+
+pub fn parse_pubkey(value: &str) -> Result<Pubkey> {
+    Pubkey::from_str(value).map_err(|_| anyhow!("invalid pubkey `{}`", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTIST: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    #[test]
+    fn parses_split_file() {
+        let split_file = SplitFile::parse(&format!(
+            r#"{{
+                "artist_mint_percentage": 8000, "label_mint_percentage": 2000,
+                "artist_secondary_percentage": 5000, "label_secondary_percentage": 5000,
+                "artist_licensing_percentage": 6000, "label_licensing_percentage": 4000,
+                "artists": [{{ "address": "{}", "percentage": 10000 }}]
+            }}"#,
+            ARTIST
+        ))
+        .unwrap();
+
+        assert_eq!(split_file.percentages().artist_mint_percentage, 8000);
+        assert_eq!(
+            split_file.artist_splits().unwrap()[0]
+                .artist_address
+                .to_string(),
+            ARTIST
+        );
+    }
+
+    #[test]
+    fn rejects_artists_not_adding_to_100_percent() {
+        let result = SplitFile::parse(&format!(
+            r#"{{
+                "artist_mint_percentage": 8000, "label_mint_percentage": 2000,
+                "artist_secondary_percentage": 5000, "label_secondary_percentage": 5000,
+                "artist_licensing_percentage": 6000, "label_licensing_percentage": 4000,
+                "artists": [{{ "address": "{}", "percentage": 9000 }}]
+            }}"#,
+            ARTIST
+        ));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parses_mint_list() {
+        let entries = parse_mint_list(&format!(
+            "# mint amount\n{} 1000000000\n\n{} 2000000000\n",
+            ARTIST, ARTIST
+        ))
+        .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].amount_paid, 2_000_000_000);
+        assert!(parse_mint_list("not-a-pubkey 1").is_err());
+        assert!(parse_mint_list(ARTIST).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! `nft-vault` admin CLI
+//!
+//! Runs collection management tasks against any RPC URL, including a local
+//! `solana-test-validator`. The keypair signs and pays for every transaction
+//! and is used as the collection authority.
+
+mod files;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{ArgEnum, Parser, Subcommand};
+use nft_vault_client::export::{self, CsvRecord};
+use nft_vault_client::rpc::RpcClient;
+use nft_vault_client::{instruction, pda};
+use serde::Serialize;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+
+use files::{parse_pubkey, read_mint_list, SplitFile};
+
+#[derive(Parser)]
+#[clap(name = "nft-vault", version, about = "Manage an nft-vault collection")]
+struct Cli {
+    /// RPC URL of the cluster
+    #[clap(long, short, global = true, default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Keypair paying for transactions and acting as collection authority
+    #[clap(long, short, global = true)]
+    keypair: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Initialize the collection from a JSON split file
+    Init {
+        #[clap(long)]
+        splits: PathBuf,
+    },
+    /// Add NFTs from a mint list, one `<mint> <amount paid>` per line
+    AddNfts {
+        #[clap(long)]
+        mints: PathBuf,
+    },
+    /// Pay a licensing fee into the collection, in lamports, as the licensee
+    PayLicense {
+        /// License account of the deal
+        #[clap(long)]
+        license: String,
+        #[clap(long)]
+        amount: u64,
+    },
+    /// Let a marketplace or relayer key record secondary sales
+    ApproveReporter {
+        #[clap(long)]
+        reporter: String,
+    },
+    /// Withdraw a reporter's approval to record secondary sales
+    RevokeReporter {
+        #[clap(long)]
+        reporter: String,
+    },
+    /// Record a resale and pay its royalty, in lamports, into the secondary pool
+    RecordSale {
+        #[clap(long)]
+        nft: String,
+        #[clap(long)]
+        price: u64,
+        #[clap(long)]
+        buyer: String,
+        #[clap(long)]
+        seller: String,
+        #[clap(long)]
+        royalty: u64,
+    },
+    /// Split the secondary sale pool between artists and holders
+    DistributeSecondary,
+    /// Hand the collection authority over, co-signed by an artist
+    TransferAuthority {
+        #[clap(long)]
+        artist_keypair: PathBuf,
+        #[clap(long)]
+        new_authority: String,
+    },
+    /// Print the collection config and both ledgers
+    Show,
+    /// Write claimable balances per NFT and per artist for accounting
+    Export {
+        #[clap(long, arg_enum, default_value = "csv")]
+        format: ExportFormat,
+        /// Directory the statement files are written to
+        #[clap(long, default_value = ".")]
+        out_dir: PathBuf,
+        /// Also replay distribution and withdrawal events into earnings history
+        #[clap(long)]
+        historical: bool,
+    },
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let client = RpcClient::new(cli.url.clone());
+
+    match cli.command {
+        Command::Show => show(&client),
+        Command::Export {
+            format,
+            out_dir,
+            historical,
+        } => export(&client, format, &out_dir, historical),
+        command => {
+            let payer = load_keypair(cli.keypair)?;
+            run(&client, &payer, command)
+        }
+    }
+}
+
+fn load_keypair(path: Option<PathBuf>) -> Result<Keypair> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let home = std::env::var("HOME").context("HOME is not set, pass --keypair")?;
+            PathBuf::from(home).join(".config/solana/id.json")
+        }
+    };
+
+    read_keypair_file(&path)
+        .map_err(|err| anyhow::anyhow!("reading keypair {}: {}", path.display(), err))
+}
+
+fn run(client: &RpcClient, payer: &Keypair, command: Command) -> Result<()> {
+    match command {
+        Command::Init { splits } => init(client, payer, &SplitFile::read(&splits)?),
+        Command::AddNfts { mints } => {
+            let entries = read_mint_list(&mints)?;
+            for entry in &entries {
+                let signature = client.send_instructions(
+                    &[instruction::add_nft(
+                        payer.pubkey(),
+                        payer.pubkey(),
+                        entry.mint,
+                        entry.amount_paid,
+                    )],
+                    payer,
+                    &[],
+                )?;
+                println!("added {} ({})", entry.mint, signature);
+            }
+            println!("added {} NFTs", entries.len());
+            Ok(())
+        }
+        Command::PayLicense { license, amount } => {
+            let license = parse_pubkey(&license)?;
+            let signature = client.send_instructions(
+                &[instruction::pay_licensing_fee(
+                    payer.pubkey(),
+                    license,
+                    amount,
+                )],
+                payer,
+                &[],
+            )?;
+            println!("paid {} lamports ({})", amount, signature);
+            Ok(())
+        }
+        Command::ApproveReporter { reporter } => {
+            let reporter = parse_pubkey(&reporter)?;
+            let signature = client.send_instructions(
+                &[instruction::approve_sale_reporter(
+                    payer.pubkey(),
+                    payer.pubkey(),
+                    reporter,
+                )],
+                payer,
+                &[],
+            )?;
+            println!("approved {} to record sales ({})", reporter, signature);
+            Ok(())
+        }
+        Command::RevokeReporter { reporter } => {
+            let reporter = parse_pubkey(&reporter)?;
+            let signature = client.send_instructions(
+                &[instruction::revoke_sale_reporter(payer.pubkey(), reporter)],
+                payer,
+                &[],
+            )?;
+            println!("revoked {} ({})", reporter, signature);
+            Ok(())
+        }
+        Command::RecordSale {
+            nft,
+            price,
+            buyer,
+            seller,
+            royalty,
+        } => {
+            let nft = parse_pubkey(&nft)?;
+            let mut instructions = Vec::new();
+            // The first recorded sale of an NFT opens its sale totals
+            let sale_index = match client.nft_sales(&nft)? {
+                Some(nft_sales) => nft_sales.sale_count,
+                None => {
+                    instructions.push(instruction::initialize_nft_sales(payer.pubkey(), nft));
+                    0
+                }
+            };
+            instructions.push(instruction::record_secondary_sale(
+                payer.pubkey(),
+                nft,
+                sale_index,
+                price,
+                parse_pubkey(&buyer)?,
+                parse_pubkey(&seller)?,
+                royalty,
+            ));
+            let signature = client.send_instructions(&instructions, payer, &[])?;
+            println!("recorded sale {} of {} ({})", sale_index, nft, signature);
+            Ok(())
+        }
+        Command::DistributeSecondary => {
+            let signature = client.send_instructions(
+                &[instruction::distribute_secondary_pool()],
+                payer,
+                &[],
+            )?;
+            println!("distributed secondary pool ({})", signature);
+            Ok(())
+        }
+        Command::TransferAuthority {
+            artist_keypair,
+            new_authority,
+        } => {
+            let artist = load_keypair(Some(artist_keypair))?;
+            let new_authority = parse_pubkey(&new_authority)?;
+            let signature = client.send_instructions(
+                &[instruction::transfer_collection_authority(
+                    payer.pubkey(),
+                    artist.pubkey(),
+                    new_authority,
+                )],
+                payer,
+                &[&artist],
+            )?;
+            println!(
+                "collection authority is now {} ({})",
+                new_authority, signature
+            );
+            Ok(())
+        }
+        Command::Show => show(client),
+        Command::Export {
+            format,
+            out_dir,
+            historical,
+        } => export(client, format, &out_dir, historical),
+    }
+}
+
+/**
+ * The program initializes with a single artist, so extra artists from the
+ * split file go through a config update proposal
+ */
+fn init(client: &RpcClient, payer: &Keypair, split_file: &SplitFile) -> Result<()> {
+    let artist_splits = split_file.artist_splits()?;
+    let percentages = split_file.percentages();
+
+    let signature = client.send_instructions(
+        &[instruction::initialize_collection(
+            payer.pubkey(),
+            percentages,
+            artist_splits[0].artist_address,
+        )],
+        payer,
+        &[],
+    )?;
+    println!("initialized collection ({})", signature);
+
+    if artist_splits.len() == 1 {
+        return Ok(());
+    }
+
+    if client.get_account_data(&pda::config_update().0)?.is_some() {
+        bail!("a config update is already pending, cancel it before proposing the artist splits");
+    }
+
+    let signature = client.send_instructions(
+        &[instruction::propose_config_update(
+            payer.pubkey(),
+            payer.pubkey(),
+            percentages,
+            artist_splits,
+        )],
+        payer,
+        &[],
+    )?;
+    println!("proposed artist splits ({})", signature);
+    println!("each artist must approve the update before it can be executed after the timelock");
+    Ok(())
+}
+
+fn show(client: &RpcClient) -> Result<()> {
+    let config = client.collection_configuration()?;
+    println!("collection authority: {}", config.collection_authority);
+    println!(
+        "mint:       artist {:>5} label {:>5}",
+        config.artist_mint_percentage, config.label_mint_percentage
+    );
+    println!(
+        "secondary:  artist {:>5} label {:>5}",
+        config.artist_secondary_percentage, config.label_secondary_percentage
+    );
+    println!(
+        "licensing:  artist {:>5} label {:>5}",
+        config.artist_licensing_percentage, config.label_licensing_percentage
+    );
+    println!("artist reserve: {}", config.artist_reserve_percentage);
+    for split in &config.artist_splits {
+        println!(
+            "  {} {:>5}",
+            split.artist_address, split.allocated_percentage
+        );
+    }
+
+    println!();
+    println!("vault balance: {}", client.get_balance(&pda::vault().0)?);
+    println!(
+        "secondary pool balance: {}",
+        client.get_balance(&pda::secondary_pool().0)?
+    );
+
+    println!();
+    println!("artist ledger:");
+    for artist in &client.artist_balance_ledger()?.artist_balances {
+        println!("  {} {}", artist.artist_address, artist.royalties_balance);
+    }
+
+    println!();
+    let nft_ledger = client.nft_balance_ledger()?;
+    println!("nft ledger ({} NFTs):", nft_ledger.nft_balances.len());
+    for nft in &nft_ledger.nft_balances {
+        println!("  {} {}", nft.nft_address, nft.royalties_balance);
+    }
+
+    Ok(())
+}
+
+fn write_records<T: CsvRecord + Serialize>(
+    out_dir: &Path,
+    name: &str,
+    format: ExportFormat,
+    records: &[T],
+) -> Result<()> {
+    let (path, contents) = match format {
+        ExportFormat::Csv => (
+            out_dir.join(format!("{}.csv", name)),
+            export::to_csv(records),
+        ),
+        ExportFormat::Json => (
+            out_dir.join(format!("{}.json", name)),
+            export::to_json(records)?,
+        ),
+    };
+
+    std::fs::write(&path, contents).with_context(|| format!("writing {}", path.display()))?;
+    println!("wrote {} rows to {}", records.len(), path.display());
+    Ok(())
+}
+
+fn export(
+    client: &RpcClient,
+    format: ExportFormat,
+    out_dir: &Path,
+    historical: bool,
+) -> Result<()> {
+    std::fs::create_dir_all(out_dir).with_context(|| format!("creating {}", out_dir.display()))?;
+
+    let statements = export::fetch_statements(client)?;
+    write_records(out_dir, "nft-balances", format, &statements.nfts)?;
+    write_records(out_dir, "artist-balances", format, &statements.artists)?;
+
+    if historical {
+        let history = export::fetch_history(client)?;
+        write_records(out_dir, "history", format, &history.entries)?;
+        write_records(out_dir, "nft-earnings", format, &history.nfts)?;
+        write_records(out_dir, "artist-earnings", format, &history.artists)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Decoding of the events the program emits into transaction logs
+//!
+//! Anchor logs each event as `Program log: <base64>`, where the payload is
+//! the 8 byte event discriminator followed by the borsh encoded event.
+
+use anchor_lang::{AnchorDeserialize, Discriminator};
+
+use crate::ID;
+
+pub use nft_vault_prototype::events::{
+    ArtistWithdrawn, CollectionAuthorityTransferred, CollectionConfigUpdated,
+    CollectionInitialized, FeeScheduleUpdated, LicenseCreated, LicenseFeeConverted, LicenseFeePaid,
+    LicenseLapsed, LicensePaymentHeld, LicensePaymentRefunded, LicensePaymentReleased,
+    LicenseRenewed, LicensedTracksCredited, MemberWithdrawn, NftAdded, RoyaltiesDistributed,
+    RoyaltyStream, SecondarySaleRecorded, UsageReported,
+};
+
+#[derive(Clone, Debug)]
+pub enum ProgramEvent {
+    CollectionInitialized(CollectionInitialized),
+    NftAdded(NftAdded),
+    RoyaltiesDistributed(RoyaltiesDistributed),
+    MemberWithdrawn(MemberWithdrawn),
+    ArtistWithdrawn(ArtistWithdrawn),
+    CollectionAuthorityTransferred(CollectionAuthorityTransferred),
+    CollectionConfigUpdated(CollectionConfigUpdated),
+    LicenseCreated(LicenseCreated),
+    LicenseFeePaid(LicenseFeePaid),
+    LicenseRenewed(LicenseRenewed),
+    LicenseLapsed(LicenseLapsed),
+    FeeScheduleUpdated(FeeScheduleUpdated),
+    LicenseFeeConverted(LicenseFeeConverted),
+    LicensedTracksCredited(LicensedTracksCredited),
+    LicensePaymentHeld(LicensePaymentHeld),
+    LicensePaymentReleased(LicensePaymentReleased),
+    LicensePaymentRefunded(LicensePaymentRefunded),
+    UsageReported(UsageReported),
+    SecondarySaleRecorded(SecondarySaleRecorded),
+}
+
+impl ProgramEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProgramEvent::CollectionInitialized(_) => "CollectionInitialized",
+            ProgramEvent::NftAdded(_) => "NftAdded",
+            ProgramEvent::RoyaltiesDistributed(_) => "RoyaltiesDistributed",
+            ProgramEvent::MemberWithdrawn(_) => "MemberWithdrawn",
+            ProgramEvent::ArtistWithdrawn(_) => "ArtistWithdrawn",
+            ProgramEvent::CollectionAuthorityTransferred(_) => "CollectionAuthorityTransferred",
+            ProgramEvent::CollectionConfigUpdated(_) => "CollectionConfigUpdated",
+            ProgramEvent::LicenseCreated(_) => "LicenseCreated",
+            ProgramEvent::LicenseFeePaid(_) => "LicenseFeePaid",
+            ProgramEvent::LicenseRenewed(_) => "LicenseRenewed",
+            ProgramEvent::LicenseLapsed(_) => "LicenseLapsed",
+            ProgramEvent::FeeScheduleUpdated(_) => "FeeScheduleUpdated",
+            ProgramEvent::LicenseFeeConverted(_) => "LicenseFeeConverted",
+            ProgramEvent::LicensedTracksCredited(_) => "LicensedTracksCredited",
+            ProgramEvent::LicensePaymentHeld(_) => "LicensePaymentHeld",
+            ProgramEvent::LicensePaymentReleased(_) => "LicensePaymentReleased",
+            ProgramEvent::LicensePaymentRefunded(_) => "LicensePaymentRefunded",
+            ProgramEvent::UsageReported(_) => "UsageReported",
+            ProgramEvent::SecondarySaleRecorded(_) => "SecondarySaleRecorded",
+        }
+    }
+}
+
+pub fn stream_name(stream: RoyaltyStream) -> &'static str {
+    match stream {
+        RoyaltyStream::Mint => "mint",
+        RoyaltyStream::Secondary => "secondary",
+        RoyaltyStream::Licensing => "licensing",
+    }
+}
+
+fn decode_as<T: AnchorDeserialize + Discriminator>(data: &[u8]) -> Option<T> {
+    if data.len() < 8 || data[..8] != T::discriminator() {
+        return None;
+    }
+    T::try_from_slice(&data[8..]).ok()
+}
+
+/**
+ * Decodes an event payload, discriminator included
+ *
+ * Returns None for data that is not one of the program's events
+ */
+pub fn decode_event(data: &[u8]) -> Option<ProgramEvent> {
+    decode_as(data)
+        .map(ProgramEvent::CollectionInitialized)
+        .or_else(|| decode_as(data).map(ProgramEvent::NftAdded))
+        .or_else(|| decode_as(data).map(ProgramEvent::RoyaltiesDistributed))
+        .or_else(|| decode_as(data).map(ProgramEvent::MemberWithdrawn))
+        .or_else(|| decode_as(data).map(ProgramEvent::ArtistWithdrawn))
+        .or_else(|| decode_as(data).map(ProgramEvent::CollectionAuthorityTransferred))
+        .or_else(|| decode_as(data).map(ProgramEvent::CollectionConfigUpdated))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicenseCreated))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicenseFeePaid))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicenseRenewed))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicenseLapsed))
+        .or_else(|| decode_as(data).map(ProgramEvent::FeeScheduleUpdated))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicenseFeeConverted))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicensedTracksCredited))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicensePaymentHeld))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicensePaymentReleased))
+        .or_else(|| decode_as(data).map(ProgramEvent::LicensePaymentRefunded))
+        .or_else(|| decode_as(data).map(ProgramEvent::UsageReported))
+        .or_else(|| decode_as(data).map(ProgramEvent::SecondarySaleRecorded))
+}
+
+/**
+ * Extracts the program's events from a transaction's log messages
+ *
+ * Tracks the invocation stack so logs from other programs, including ones
+ * this program invokes, are ignored.
+ */
+pub fn parse_logs<S: AsRef<str>>(logs: &[S]) -> Vec<ProgramEvent> {
+    let program_id = ID.to_string();
+    let mut invocations: Vec<String> = Vec::new();
+    let mut events = Vec::new();
+
+    for log in logs {
+        let log = log.as_ref();
+
+        if let Some(payload) = log.strip_prefix("Program log: ") {
+            if invocations.last() == Some(&program_id) {
+                if let Some(event) = base64::decode(payload)
+                    .ok()
+                    .and_then(|data| decode_event(&data))
+                {
+                    events.push(event);
+                }
+            }
+            continue;
+        }
+
+        let mut words = log.split_whitespace();
+        if let (Some("Program"), Some(program), Some(status)) =
+            (words.next(), words.next(), words.next())
+        {
+            match status {
+                "invoke" => invocations.push(program.to_string()),
+                "success" | "failed:" => {
+                    invocations.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::Event;
+
+    fn log_event(event: &impl Event) -> String {
+        format!("Program log: {}", base64::encode(event.data()))
+    }
+
+    #[test]
+    fn parses_events_from_program_logs() {
+        let withdrawn = MemberWithdrawn {
+            nft_address: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+        };
+        let other_program = Pubkey::new_unique();
+        let logs = vec![
+            format!("Program {} invoke [1]", ID),
+            "Program log: Instruction: MemberWithdraw".to_string(),
+            format!("Program {} invoke [2]", other_program),
+            log_event(&withdrawn),
+            format!("Program {} success", other_program),
+            log_event(&withdrawn),
+            format!("Program {} consumed 5000 of 200000 compute units", ID),
+            format!("Program {} success", ID),
+        ];
+
+        let events = parse_logs(&logs);
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            ProgramEvent::MemberWithdrawn(event) => {
+                assert_eq!(event.nft_address, withdrawn.nft_address);
+                assert_eq!(event.amount, 42);
+            }
+            event => panic!("unexpected event {}", event.name()),
+        }
+    }
+
+    #[test]
+    fn ignores_unknown_payloads() {
+        assert!(decode_event(&[0; 16]).is_none());
+        assert!(decode_event(&[1, 2, 3]).is_none());
+    }
+}
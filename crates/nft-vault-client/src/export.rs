@@ -0,0 +1,517 @@
+//! Accounting statements built from the ledgers and emitted events
+//!
+//! Statements list what each NFT and artist can currently claim. The
+//! historical mode replays distribution and withdrawal events to show what
+//! was earned and withdrawn over the life of the collection.
+
+use std::collections::HashMap;
+
+use anchor_lang::prelude::Pubkey;
+use serde::Serialize;
+
+use crate::events::{stream_name, ProgramEvent, RoyaltyStream};
+use crate::rpc::{RpcClient, RpcError, TransactionEvents};
+use crate::state::{ArtistBalanceLedger, CollectionConfiguration, NftBalanceLedger};
+use crate::ArtistPercentage;
+
+/// A row in a CSV export
+pub trait CsvRecord {
+    const HEADER: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NftStatement {
+    pub nft_address: String,
+    pub owner: Option<String>,
+    pub claimable: u64,
+}
+
+impl CsvRecord for NftStatement {
+    const HEADER: &'static [&'static str] = &["nft_address", "owner", "claimable"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.nft_address.clone(),
+            self.owner.clone().unwrap_or_default(),
+            self.claimable.to_string(),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArtistStatement {
+    pub artist_address: String,
+    pub allocated_percentage: u64,
+    pub claimable: u64,
+}
+
+impl CsvRecord for ArtistStatement {
+    const HEADER: &'static [&'static str] =
+        &["artist_address", "allocated_percentage", "claimable"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.artist_address.clone(),
+            self.allocated_percentage.to_string(),
+            self.claimable.to_string(),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Statements {
+    pub nfts: Vec<NftStatement>,
+    pub artists: Vec<ArtistStatement>,
+}
+
+pub fn nft_statements(
+    nft_ledger: &NftBalanceLedger,
+    owners: &HashMap<Pubkey, Pubkey>,
+) -> Vec<NftStatement> {
+    nft_ledger
+        .nft_balances
+        .iter()
+        .map(|nft| NftStatement {
+            nft_address: nft.nft_address.to_string(),
+            owner: owners.get(&nft.nft_address).map(Pubkey::to_string),
+            claimable: nft.royalties_balance,
+        })
+        .collect()
+}
+
+// Artists removed from the splits keep their ledger entry until they withdraw
+pub fn artist_statements(
+    artist_ledger: &ArtistBalanceLedger,
+    collection_config: &CollectionConfiguration,
+) -> Vec<ArtistStatement> {
+    artist_ledger
+        .artist_balances
+        .iter()
+        .map(|artist| ArtistStatement {
+            artist_address: artist.artist_address.to_string(),
+            allocated_percentage: collection_config
+                .artist_splits
+                .iter()
+                .find(|split| split.artist_address == artist.artist_address)
+                .map(|split| split.allocated_percentage)
+                .unwrap_or_default(),
+            claimable: artist.royalties_balance,
+        })
+        .collect()
+}
+
+/// Reads both ledgers and resolves the current owner of every NFT
+pub fn fetch_statements(client: &RpcClient) -> Result<Statements, RpcError> {
+    let collection_config = client.collection_configuration()?;
+    let nft_ledger = client.nft_balance_ledger()?;
+    let artist_ledger = client.artist_balance_ledger()?;
+
+    let mut owners = HashMap::new();
+    for nft in &nft_ledger.nft_balances {
+        if let Some(owner) = client.nft_owner(&nft.nft_address)? {
+            owners.insert(nft.nft_address, owner);
+        }
+    }
+
+    Ok(Statements {
+        nfts: nft_statements(&nft_ledger, &owners),
+        artists: artist_statements(&artist_ledger, &collection_config),
+    })
+}
+
+/// One distribution or withdrawal
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub event: String,
+    pub stream: Option<String>,
+    pub address: Option<String>,
+    pub amount: u64,
+    pub artist_total: u64,
+    pub holder_total: u64,
+    pub per_nft_share: u64,
+}
+
+impl CsvRecord for HistoryEntry {
+    const HEADER: &'static [&'static str] = &[
+        "signature",
+        "slot",
+        "block_time",
+        "event",
+        "stream",
+        "address",
+        "amount",
+        "artist_total",
+        "holder_total",
+        "per_nft_share",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.signature.clone(),
+            self.slot.to_string(),
+            self.block_time
+                .map(|block_time| block_time.to_string())
+                .unwrap_or_default(),
+            self.event.clone(),
+            self.stream.clone().unwrap_or_default(),
+            self.address.clone().unwrap_or_default(),
+            self.amount.to_string(),
+            self.artist_total.to_string(),
+            self.holder_total.to_string(),
+            self.per_nft_share.to_string(),
+        ]
+    }
+}
+
+/// Lifetime totals for an NFT or artist
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EarningsSummary {
+    pub address: String,
+    pub earned: u64,
+    pub withdrawn: u64,
+}
+
+impl CsvRecord for EarningsSummary {
+    const HEADER: &'static [&'static str] = &["address", "earned", "withdrawn"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.address.clone(),
+            self.earned.to_string(),
+            self.withdrawn.to_string(),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+    pub nfts: Vec<EarningsSummary>,
+    pub artists: Vec<EarningsSummary>,
+}
+
+#[derive(Default)]
+struct Totals {
+    order: Vec<Pubkey>,
+    totals: HashMap<Pubkey, (u64, u64)>,
+}
+
+impl Totals {
+    fn entry(&mut self, address: Pubkey) -> &mut (u64, u64) {
+        if !self.totals.contains_key(&address) {
+            self.order.push(address);
+        }
+        self.totals.entry(address).or_default()
+    }
+
+    fn summaries(&self) -> Vec<EarningsSummary> {
+        self.order
+            .iter()
+            .map(|address| {
+                let (earned, withdrawn) = self.totals[address];
+                EarningsSummary {
+                    address: address.to_string(),
+                    earned,
+                    withdrawn,
+                }
+            })
+            .collect()
+    }
+}
+
+/**
+ * Rebuilds earnings from events, oldest transaction first
+ *
+ * Follows the program's rounding: each NFT in the ledger at the time of a
+ * distribution gets per_nft_share, and artists get artist_total pro-rata to
+ * the splits in force, rounded down. Licensed tracks also get the
+ * per_nft_share of their LicensedTracksCredited.
+ */
+pub fn replay_history(transactions: &[TransactionEvents]) -> History {
+    let mut entries = Vec::new();
+    let mut ledger_nfts: Vec<Pubkey> = Vec::new();
+    let mut artist_splits: Vec<ArtistPercentage> = Vec::new();
+    let mut nfts = Totals::default();
+    let mut artists = Totals::default();
+
+    for transaction in transactions {
+        let entry = |event: &str| HistoryEntry {
+            signature: transaction.signature.to_string(),
+            slot: transaction.slot,
+            block_time: transaction.block_time,
+            event: event.to_string(),
+            stream: None,
+            address: None,
+            amount: 0,
+            artist_total: 0,
+            holder_total: 0,
+            per_nft_share: 0,
+        };
+
+        for event in &transaction.events {
+            match event {
+                ProgramEvent::CollectionInitialized(event) => {
+                    artist_splits = event.artist_splits.clone();
+                }
+                ProgramEvent::CollectionConfigUpdated(event) => {
+                    artist_splits = event.artist_splits.clone();
+                }
+                ProgramEvent::NftAdded(event) => {
+                    ledger_nfts.push(event.nft_address);
+                    nfts.entry(event.nft_address);
+                }
+                ProgramEvent::RoyaltiesDistributed(event) => {
+                    for nft in ledger_nfts.iter().take(event.nft_count as usize) {
+                        nfts.entry(*nft).0 += event.per_nft_share;
+                    }
+
+                    // Same rounding as the artist ledger, nothing is credited
+                    // while no split is allocated
+                    for (artist_address, share) in
+                        royalty_math::split_shares(&artist_splits, event.artist_total).flatten()
+                    {
+                        artists.entry(artist_address).0 += share;
+                    }
+
+                    entries.push(HistoryEntry {
+                        stream: Some(stream_name(event.stream).to_string()),
+                        amount: event.amount,
+                        artist_total: event.artist_total,
+                        holder_total: event.holder_total,
+                        per_nft_share: event.per_nft_share,
+                        ..entry("distribution")
+                    });
+                }
+                ProgramEvent::LicensedTracksCredited(event) => {
+                    for nft in &event.nfts {
+                        nfts.entry(*nft).0 += event.per_nft_share;
+                        entries.push(HistoryEntry {
+                            stream: Some(stream_name(RoyaltyStream::Licensing).to_string()),
+                            address: Some(nft.to_string()),
+                            amount: event.per_nft_share,
+                            ..entry("track_credit")
+                        });
+                    }
+                }
+                ProgramEvent::MemberWithdrawn(event) => {
+                    nfts.entry(event.nft_address).1 += event.amount;
+                    entries.push(HistoryEntry {
+                        address: Some(event.nft_address.to_string()),
+                        amount: event.amount,
+                        ..entry("member_withdrawal")
+                    });
+                }
+                ProgramEvent::ArtistWithdrawn(event) => {
+                    artists.entry(event.artist_address).1 += event.amount;
+                    entries.push(HistoryEntry {
+                        address: Some(event.artist_address.to_string()),
+                        amount: event.amount,
+                        ..entry("artist_withdrawal")
+                    });
+                }
+                ProgramEvent::CollectionAuthorityTransferred(_)
+                | ProgramEvent::LicenseCreated(_)
+                | ProgramEvent::LicenseFeePaid(_)
+                | ProgramEvent::LicenseRenewed(_)
+                | ProgramEvent::LicenseLapsed(_)
+                | ProgramEvent::FeeScheduleUpdated(_)
+                | ProgramEvent::LicenseFeeConverted(_)
+                | ProgramEvent::LicensePaymentHeld(_)
+                | ProgramEvent::LicensePaymentReleased(_)
+                | ProgramEvent::LicensePaymentRefunded(_)
+                | ProgramEvent::UsageReported(_)
+                | ProgramEvent::SecondarySaleRecorded(_) => {}
+            }
+        }
+    }
+
+    History {
+        entries,
+        nfts: nfts.summaries(),
+        artists: artists.summaries(),
+    }
+}
+
+pub fn fetch_history(client: &RpcClient) -> Result<History, RpcError> {
+    Ok(replay_history(&client.program_events()?))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv<T: CsvRecord>(records: &[T]) -> String {
+    let mut csv = T::HEADER.join(",");
+    csv.push('\n');
+
+    for record in records {
+        let fields: Vec<String> = record
+            .fields()
+            .iter()
+            .map(|field| csv_field(field))
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{
+        ArtistWithdrawn, CollectionInitialized, LicensedTracksCredited, NftAdded,
+        RoyaltiesDistributed,
+    };
+    use solana_sdk::signature::Signature;
+
+    fn transaction(events: Vec<ProgramEvent>) -> TransactionEvents {
+        TransactionEvents {
+            signature: Signature::default(),
+            slot: 1,
+            block_time: Some(1_640_000_000),
+            events,
+        }
+    }
+
+    fn nft_added(nft_address: Pubkey, nft_count: u64) -> ProgramEvent {
+        ProgramEvent::NftAdded(NftAdded {
+            nft_address,
+            amount_paid: 0,
+            nft_count,
+        })
+    }
+
+    fn distributed(artist_total: u64, per_nft_share: u64, nft_count: u64) -> ProgramEvent {
+        ProgramEvent::RoyaltiesDistributed(RoyaltiesDistributed {
+            stream: RoyaltyStream::Licensing,
+            amount: artist_total + per_nft_share * nft_count,
+            artist_total,
+            holder_total: per_nft_share * nft_count,
+            per_nft_share,
+            nft_count,
+        })
+    }
+
+    #[test]
+    fn replays_distributions_and_withdrawals() {
+        let artist_a = Pubkey::new_unique();
+        let artist_b = Pubkey::new_unique();
+        let nft_1 = Pubkey::new_unique();
+        let nft_2 = Pubkey::new_unique();
+
+        let history = replay_history(&[
+            transaction(vec![ProgramEvent::CollectionInitialized(
+                CollectionInitialized {
+                    collection_authority: Pubkey::new_unique(),
+                    artist_mint_percentage: 8000,
+                    label_mint_percentage: 2000,
+                    artist_secondary_percentage: 5000,
+                    label_secondary_percentage: 5000,
+                    artist_licensing_percentage: 6000,
+                    label_licensing_percentage: 4000,
+                    artist_splits: vec![
+                        ArtistPercentage {
+                            artist_address: artist_a,
+                            allocated_percentage: 7000,
+                        },
+                        ArtistPercentage {
+                            artist_address: artist_b,
+                            allocated_percentage: 3000,
+                        },
+                    ],
+                },
+            )]),
+            transaction(vec![nft_added(nft_1, 1)]),
+            // nft_2 joins after the first distribution
+            transaction(vec![distributed(1000, 100, 1)]),
+            transaction(vec![nft_added(nft_2, 2)]),
+            transaction(vec![distributed(1001, 50, 2)]),
+            transaction(vec![ProgramEvent::ArtistWithdrawn(ArtistWithdrawn {
+                artist_address: artist_a,
+                amount: 1400,
+            })]),
+        ]);
+
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(
+            history.nfts,
+            vec![
+                EarningsSummary {
+                    address: nft_1.to_string(),
+                    earned: 150,
+                    withdrawn: 0
+                },
+                EarningsSummary {
+                    address: nft_2.to_string(),
+                    earned: 50,
+                    withdrawn: 0
+                },
+            ]
+        );
+        // 700 + 700 (1001 * 7000 / 10000 rounded down) and 300 + 300
+        assert_eq!(history.artists[0].earned, 1400);
+        assert_eq!(history.artists[0].withdrawn, 1400);
+        assert_eq!(history.artists[1].earned, 600);
+    }
+
+    #[test]
+    fn credits_licensed_tracks() {
+        let nft_1 = Pubkey::new_unique();
+        let nft_2 = Pubkey::new_unique();
+
+        let history = replay_history(&[
+            transaction(vec![nft_added(nft_1, 1), nft_added(nft_2, 2)]),
+            transaction(vec![
+                distributed(600, 100, 2),
+                ProgramEvent::LicensedTracksCredited(LicensedTracksCredited {
+                    license: Pubkey::new_unique(),
+                    nfts: vec![nft_2],
+                    amount: 200,
+                    per_nft_share: 200,
+                }),
+            ]),
+        ]);
+
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries[1].event, "track_credit");
+        assert_eq!(history.entries[1].address, Some(nft_2.to_string()));
+        assert_eq!(history.nfts[0].earned, 100);
+        assert_eq!(history.nfts[1].earned, 300);
+    }
+
+    #[test]
+    fn writes_csv_with_header() {
+        let statements = vec![
+            NftStatement {
+                nft_address: "nft".to_string(),
+                owner: None,
+                claimable: 5,
+            },
+            NftStatement {
+                nft_address: "a,b".to_string(),
+                owner: Some("owner".to_string()),
+                claimable: 7,
+            },
+        ];
+
+        assert_eq!(
+            to_csv(&statements),
+            "nft_address,owner,claimable\nnft,,5\n\"a,b\",owner,7\n"
+        );
+    }
+}
@@ -0,0 +1,1303 @@
+//! Instruction builders, one per program instruction
+//!
+//! PDAs are derived here, callers only pass the keys that vary.
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token;
+use nft_vault_prototype::fee_schedule::{FeeCurrency, FeeTier};
+use nft_vault_prototype::governance::GovernanceAction;
+use nft_vault_prototype::licensing::{TrackUsage, UsageType};
+use nft_vault_prototype::{accounts, instruction, ArtistDilution, ArtistPercentage, ID};
+
+use crate::pda;
+
+/**
+ * Artist/label percentages for each revenue stream
+ *
+ * percentages are in basis points, each pair must add up to 10,000
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CollectionPercentages {
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+}
+
+/**
+ * Terms of a licensing deal
+ *
+ * content_hash is the hash of the terms document at terms_uri, fee is in
+ * lamports and the license is valid from start_slot to end_slot inclusive,
+ * then for grace_slots more until it is renewed. volume is the licensed
+ * units, which pick the fee schedule tier payments are checked against.
+ * track_share_percentage of the holder share of each payment is split
+ * between licensed_nfts, the tracks licensed. Licenses paid per use
+ * charge per_use_rate lamports for every use their reporter reports, the
+ * licensee reports when reporter is None.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct LicenseTerms {
+    pub content_hash: [u8; 32],
+    pub terms_uri: String,
+    pub fee: u64,
+    pub usage_type: UsageType,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub grace_slots: u64,
+    pub volume: u64,
+    pub licensed_nfts: Vec<Pubkey>,
+    pub track_share_percentage: u64,
+    pub reporter: Option<Pubkey>,
+    pub per_use_rate: u64,
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// Artists that must co-sign, passed as remaining accounts
+fn with_artist_signers(mut ix: Instruction, artist_signers: &[Pubkey]) -> Instruction {
+    ix.accounts.extend(
+        artist_signers
+            .iter()
+            .map(|artist| AccountMeta::new_readonly(*artist, true)),
+    );
+    ix
+}
+
+/**
+ * Appends the price feed of a USD fee schedule, which licensing payments
+ * and renewals need to convert the scheduled price
+ */
+pub fn with_price_feed(mut ix: Instruction, price_feed: Pubkey) -> Instruction {
+    ix.accounts
+        .push(AccountMeta::new_readonly(price_feed, false));
+    ix
+}
+
+pub fn initialize_collection(
+    payer: Pubkey,
+    percentages: CollectionPercentages,
+    single_artist: Pubkey,
+) -> Instruction {
+    build(
+        accounts::InitializeCollection {
+            collection_config: pda::collection_config().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeCollection {
+            artist_mint_percentage: percentages.artist_mint_percentage,
+            label_mint_percentage: percentages.label_mint_percentage,
+            artist_secondary_percentage: percentages.artist_secondary_percentage,
+            label_secondary_percentage: percentages.label_secondary_percentage,
+            artist_licensing_percentage: percentages.artist_licensing_percentage,
+            label_licensing_percentage: percentages.label_licensing_percentage,
+            single_artist,
+        },
+    )
+}
+
+pub fn member_withdraw(to: Pubkey, nft: Pubkey, nft_associated_account: Pubkey) -> Instruction {
+    build(
+        accounts::MemberWithdraw {
+            to,
+            pda_vault: pda::vault().0,
+            nft,
+            nft_associated_account,
+            nft_balance_ledger: pda::nft_ledger().0,
+            system_program: system_program::ID,
+        },
+        instruction::MemberWithdraw {},
+    )
+}
+
+pub fn artist_withdraw(artist_account: Pubkey) -> Instruction {
+    build(
+        accounts::ArtistWithdraw {
+            artist_account,
+            pda_vault: pda::vault().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            system_program: system_program::ID,
+        },
+        instruction::ArtistWithdraw {},
+    )
+}
+
+pub fn distribute_secondary_pool() -> Instruction {
+    build(
+        accounts::DistributeSecondaryPool {
+            pda_vault: pda::vault().0,
+            pda_secondary_pool: pda::secondary_pool().0,
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            system_program: system_program::ID,
+        },
+        instruction::DistributeSecondaryPool {},
+    )
+}
+
+/**
+ * from is the licensee paying the fee, license the account create_license
+ * made for the deal
+ */
+pub fn pay_licensing_fee(from: Pubkey, license: Pubkey, amount: u64) -> Instruction {
+    build(
+        pay_licensing_fee_accounts(from, license),
+        instruction::PayLicensingFee { amount },
+    )
+}
+
+/**
+ * Pays usd_cents in lamports at the fee schedule's price feed, failing if
+ * that comes to more than slippage_bps over quoted_lamports
+ */
+pub fn pay_licensing_fee_usd(
+    from: Pubkey,
+    license: Pubkey,
+    price_feed: Pubkey,
+    usd_cents: u64,
+    quoted_lamports: u64,
+    slippage_bps: u64,
+) -> Instruction {
+    let ix = build(
+        pay_licensing_fee_accounts(from, license),
+        instruction::PayLicensingFeeUsd {
+            usd_cents,
+            quoted_lamports,
+            slippage_bps,
+        },
+    );
+    with_price_feed(ix, price_feed)
+}
+
+fn pay_licensing_fee_accounts(from: Pubkey, license: Pubkey) -> accounts::PayLicensingFee {
+    accounts::PayLicensingFee {
+        from,
+        license,
+        license_mint: pda::license_mint(&license).0,
+        license_token_account: pda::license_token_account(&license).0,
+        license_authority: pda::license_authority().0,
+        pda_vault: pda::vault().0,
+        collection_config: pda::collection_config().0,
+        fee_schedule: pda::fee_schedule().0,
+        artist_balance_ledger: pda::artist_ledger().0,
+        nft_balance_ledger: pda::nft_ledger().0,
+        system_program: system_program::ID,
+        token_program: token::ID,
+    }
+}
+
+pub fn add_nft(
+    payer: Pubkey,
+    collection_authority: Pubkey,
+    nft_address: Pubkey,
+    amount_paid: u64,
+) -> Instruction {
+    build(
+        accounts::AddNft {
+            payer,
+            collection_authority,
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            nft_address,
+            system_program: system_program::ID,
+        },
+        instruction::AddNft { amount_paid },
+    )
+}
+
+pub fn propose_config_update(
+    payer: Pubkey,
+    collection_authority: Pubkey,
+    percentages: CollectionPercentages,
+    artist_splits: Vec<ArtistPercentage>,
+) -> Instruction {
+    build(
+        accounts::ProposeConfigUpdate {
+            config_update_proposal: pda::config_update().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::ProposeConfigUpdate {
+            artist_mint_percentage: percentages.artist_mint_percentage,
+            label_mint_percentage: percentages.label_mint_percentage,
+            artist_secondary_percentage: percentages.artist_secondary_percentage,
+            label_secondary_percentage: percentages.label_secondary_percentage,
+            artist_licensing_percentage: percentages.artist_licensing_percentage,
+            label_licensing_percentage: percentages.label_licensing_percentage,
+            artist_splits,
+        },
+    )
+}
+
+pub fn approve_config_update(artist: Pubkey) -> Instruction {
+    build(
+        accounts::ApproveConfigUpdate {
+            config_update_proposal: pda::config_update().0,
+            artist,
+        },
+        instruction::ApproveConfigUpdate {},
+    )
+}
+
+pub fn execute_config_update(payer: Pubkey, proposer: Pubkey) -> Instruction {
+    build(
+        accounts::ExecuteConfigUpdate {
+            config_update_proposal: pda::config_update().0,
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            proposer,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::ExecuteConfigUpdate {},
+    )
+}
+
+pub fn cancel_config_update(collection_authority: Pubkey, proposer: Pubkey) -> Instruction {
+    build(
+        accounts::CancelConfigUpdate {
+            config_update_proposal: pda::config_update().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+            proposer,
+        },
+        instruction::CancelConfigUpdate {},
+    )
+}
+
+pub fn initialize_holder_governance(
+    payer: Pubkey,
+    collection_authority: Pubkey,
+    quorum_percentage: u64,
+    voting_period: i64,
+) -> Instruction {
+    build(
+        accounts::InitializeHolderGovernance {
+            holder_governance: pda::holder_governance().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeHolderGovernance {
+            quorum_percentage,
+            voting_period,
+        },
+    )
+}
+
+/**
+ * proposal_id must be the governance account's current proposal_count
+ */
+pub fn create_holder_proposal(
+    proposer: Pubkey,
+    proposal_id: u64,
+    nft: Pubkey,
+    nft_associated_account: Pubkey,
+    action: GovernanceAction,
+) -> Instruction {
+    build(
+        accounts::CreateHolderProposal {
+            holder_governance: pda::holder_governance().0,
+            holder_proposal: pda::holder_proposal(proposal_id).0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            nft,
+            nft_associated_account,
+            proposer,
+            system_program: system_program::ID,
+        },
+        instruction::CreateHolderProposal { action },
+    )
+}
+
+pub fn cast_holder_vote(
+    holder: Pubkey,
+    proposal_id: u64,
+    nft: Pubkey,
+    nft_associated_account: Pubkey,
+    approve: bool,
+) -> Instruction {
+    build(
+        accounts::CastHolderVote {
+            holder_proposal: pda::holder_proposal(proposal_id).0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            nft,
+            nft_associated_account,
+            holder,
+        },
+        instruction::CastHolderVote { approve },
+    )
+}
+
+/**
+ * artist_signers are every current artist, needed when the proposal
+ * changes the artist percentages
+ */
+pub fn execute_holder_proposal(proposal_id: u64, artist_signers: &[Pubkey]) -> Instruction {
+    let ix = build(
+        accounts::ExecuteHolderProposal {
+            holder_proposal: pda::holder_proposal(proposal_id).0,
+            collection_config: pda::collection_config().0,
+            fee_schedule: pda::fee_schedule().0,
+        },
+        instruction::ExecuteHolderProposal {},
+    );
+    with_artist_signers(ix, artist_signers)
+}
+
+pub fn transfer_artist_split(
+    payer: Pubkey,
+    artist: Pubkey,
+    new_artist: Pubkey,
+    percentage: u64,
+) -> Instruction {
+    build(
+        accounts::TransferArtistSplit {
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            artist,
+            new_artist,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::TransferArtistSplit { percentage },
+    )
+}
+
+/**
+ * artist_signers are the current artists, required for ArtistDilution::ProRata
+ */
+pub fn add_artist(
+    payer: Pubkey,
+    collection_authority: Pubkey,
+    new_artist: Pubkey,
+    allocated_percentage: u64,
+    source: ArtistDilution,
+    artist_signers: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::AddArtist {
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            collection_authority,
+            new_artist,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::AddArtist {
+            allocated_percentage,
+            source,
+        },
+    );
+    with_artist_signers(ix, artist_signers)
+}
+
+/**
+ * artist_signers are every current artist except the removed one, who
+ * doesn't have to sign
+ */
+pub fn remove_artist(
+    collection_authority: Pubkey,
+    removed_artist: Pubkey,
+    destination: ArtistDilution,
+    artist_signers: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::RemoveArtist {
+            collection_config: pda::collection_config().0,
+            collection_authority,
+            removed_artist,
+        },
+        instruction::RemoveArtist { destination },
+    );
+    with_artist_signers(ix, artist_signers)
+}
+
+pub fn expand_ledger(payer: Pubkey, artist_capacity: u64, nft_capacity: u64) -> Instruction {
+    build(
+        accounts::ExpandLedger {
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::ExpandLedger {
+            artist_capacity,
+            nft_capacity,
+        },
+    )
+}
+
+pub fn migrate_collection_config(payer: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateCollectionConfig {
+            collection_config: pda::collection_config().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateCollectionConfig {},
+    )
+}
+
+pub fn migrate_nft_balance_ledger(payer: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateNftBalanceLedger {
+            nft_balance_ledger: pda::nft_ledger().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateNftBalanceLedger {},
+    )
+}
+
+pub fn migrate_artist_balance_ledger(payer: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateArtistBalanceLedger {
+            artist_balance_ledger: pda::artist_ledger().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateArtistBalanceLedger {},
+    )
+}
+
+pub fn transfer_collection_authority(
+    current_collection_authority: Pubkey,
+    artist_authorizer: Pubkey,
+    new_collection_authority: Pubkey,
+) -> Instruction {
+    build(
+        accounts::TransferCollectionAuthority {
+            collection_config: pda::collection_config().0,
+            current_collection_authority,
+            artist_authorizer,
+            new_collection_authority,
+        },
+        instruction::TransferCollectionAuthority {},
+    )
+}
+
+/**
+ * Terms are agreed by both sides, so the license is signed by the
+ * collection authority and the licensee, who pays for the account
+ */
+pub fn create_license(
+    collection_authority: Pubkey,
+    licensee: Pubkey,
+    terms: LicenseTerms,
+) -> Instruction {
+    let license = pda::license(&licensee, &terms.content_hash).0;
+
+    build(
+        accounts::CreateLicense {
+            license,
+            license_mint: pda::license_mint(&license).0,
+            license_token_account: pda::license_token_account(&license).0,
+            license_authority: pda::license_authority().0,
+            collection_config: pda::collection_config().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            collection_authority,
+            licensee,
+            system_program: system_program::ID,
+            token_program: token::ID,
+            rent: sysvar::rent::ID,
+        },
+        instruction::CreateLicense {
+            content_hash: terms.content_hash,
+            terms_uri: terms.terms_uri,
+            fee: terms.fee,
+            usage_type: terms.usage_type,
+            start_slot: terms.start_slot,
+            end_slot: terms.end_slot,
+            grace_slots: terms.grace_slots,
+            volume: terms.volume,
+            licensed_nfts: terms.licensed_nfts,
+            track_share_percentage: terms.track_share_percentage,
+            reporter: terms.reporter,
+            per_use_rate: terms.per_use_rate,
+        },
+    )
+}
+
+/// Pays the license fee for another term, same accounts as a licensing fee
+pub fn renew_license(licensee: Pubkey, license: Pubkey) -> Instruction {
+    build(
+        pay_licensing_fee_accounts(licensee, license),
+        instruction::RenewLicense {},
+    )
+}
+
+/// Marks a license lapsed once its grace period is over, anyone can send it
+pub fn lapse_license(license: Pubkey) -> Instruction {
+    build(
+        accounts::LapseLicense { license },
+        instruction::LapseLicense {},
+    )
+}
+
+/// Opens the sale totals of an NFT, before its first recorded sale
+pub fn initialize_nft_sales(payer: Pubkey, nft: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeNftSales {
+            nft_sales: pda::nft_sales(&nft).0,
+            nft,
+            nft_balance_ledger: pda::nft_ledger().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeNftSales {},
+    )
+}
+
+/// Lets reporter record secondary sales
+pub fn approve_sale_reporter(
+    payer: Pubkey,
+    collection_authority: Pubkey,
+    reporter: Pubkey,
+) -> Instruction {
+    build(
+        accounts::ApproveSaleReporter {
+            sale_reporter: pda::sale_reporter(&reporter).0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::ApproveSaleReporter { reporter },
+    )
+}
+
+/// Withdraws the reporter's approval, refunding its rent to the authority
+pub fn revoke_sale_reporter(collection_authority: Pubkey, reporter: Pubkey) -> Instruction {
+    build(
+        accounts::RevokeSaleReporter {
+            sale_reporter: pda::sale_reporter(&reporter).0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+        },
+        instruction::RevokeSaleReporter {},
+    )
+}
+
+/**
+ * Records a resale, the reporter pays royalty_amount into the secondary
+ * pool. sale_index is the NFT's sale count before this sale, from its
+ * NftSales account. The reporter must be approved with
+ * approve_sale_reporter.
+ */
+pub fn record_secondary_sale(
+    reporter: Pubkey,
+    nft: Pubkey,
+    sale_index: u64,
+    price: u64,
+    buyer: Pubkey,
+    seller: Pubkey,
+    royalty_amount: u64,
+) -> Instruction {
+    build(
+        accounts::RecordSecondarySale {
+            nft_sales: pda::nft_sales(&nft).0,
+            secondary_sale: pda::secondary_sale(&nft, sale_index).0,
+            pda_secondary_pool: pda::secondary_pool().0,
+            sale_reporter: pda::sale_reporter(&reporter).0,
+            reporter,
+            system_program: system_program::ID,
+        },
+        instruction::RecordSecondarySale {
+            price,
+            buyer,
+            seller,
+            royalty_amount,
+        },
+    )
+}
+
+/// Records a period's uses of each track, signed by the license's reporter
+pub fn report_usage(
+    reporter: Pubkey,
+    license: Pubkey,
+    period: u64,
+    tracks: Vec<TrackUsage>,
+) -> Instruction {
+    build(
+        accounts::ReportUsage {
+            usage_report: pda::usage_report(&license, period).0,
+            license,
+            nft_balance_ledger: pda::nft_ledger().0,
+            reporter,
+            system_program: system_program::ID,
+        },
+        instruction::ReportUsage { period, tracks },
+    )
+}
+
+/// Pays the license's outstanding usage fee
+pub fn pay_usage_fee(licensee: Pubkey, license: Pubkey) -> Instruction {
+    build(
+        pay_licensing_fee_accounts(licensee, license),
+        instruction::PayUsageFee {},
+    )
+}
+
+/// Splits the license's held payments that are due, anyone can send it
+pub fn release_licensing_payments(license: Pubkey) -> Instruction {
+    build(
+        accounts::ReleaseLicensingPayments {
+            license,
+            collection_config: pda::collection_config().0,
+            artist_balance_ledger: pda::artist_ledger().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+        },
+        instruction::ReleaseLicensingPayments {},
+    )
+}
+
+/**
+ * payment_index is the payment's position in the license's pending_payments.
+ * The refunder is the collection authority, or the licensee for a payment
+ * past its hold that can't be released.
+ */
+pub fn refund_licensing_payment(
+    refunder: Pubkey,
+    license: Pubkey,
+    licensee: Pubkey,
+    payment_index: u64,
+) -> Instruction {
+    build(
+        accounts::RefundLicensingPayment {
+            license,
+            licensee,
+            pda_vault: pda::vault().0,
+            collection_config: pda::collection_config().0,
+            nft_balance_ledger: pda::nft_ledger().0,
+            refunder,
+            system_program: system_program::ID,
+        },
+        instruction::RefundLicensingPayment { payment_index },
+    )
+}
+
+/// Tiers must give every usage type a price from volume 0
+pub fn initialize_fee_schedule(
+    payer: Pubkey,
+    collection_authority: Pubkey,
+    tiers: Vec<FeeTier>,
+) -> Instruction {
+    build(
+        accounts::InitializeFeeSchedule {
+            fee_schedule: pda::fee_schedule().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeFeeSchedule { tiers },
+    )
+}
+
+/// Replaces every tier of the fee schedule
+pub fn update_fee_schedule(collection_authority: Pubkey, tiers: Vec<FeeTier>) -> Instruction {
+    build(
+        accounts::UpdateFeeSchedule {
+            fee_schedule: pda::fee_schedule().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+        },
+        instruction::UpdateFeeSchedule { tiers },
+    )
+}
+
+/// Prices the fee schedule in lamports, or in USD cents at a price feed, with
+/// tiers priced in that currency
+pub fn set_fee_currency(
+    collection_authority: Pubkey,
+    currency: FeeCurrency,
+    tiers: Vec<FeeTier>,
+) -> Instruction {
+    build(
+        accounts::UpdateFeeSchedule {
+            fee_schedule: pda::fee_schedule().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+        },
+        instruction::SetFeeCurrency { currency, tiers },
+    )
+}
+
+/// Holds later licensing payments for hold_slots, 0 settles them at once
+pub fn set_payment_hold(collection_authority: Pubkey, hold_slots: u64) -> Instruction {
+    build(
+        accounts::UpdateFeeSchedule {
+            fee_schedule: pda::fee_schedule().0,
+            collection_config: pda::collection_config().0,
+            collection_authority,
+        },
+        instruction::SetPaymentHold { hold_slots },
+    )
+}
+
+/// Fails unless licensee holds the license token of an active license
+pub fn verify_license(license: Pubkey, licensee: Pubkey) -> Instruction {
+    build(
+        accounts::VerifyLicense {
+            license,
+            license_token_account: pda::license_token_account(&license).0,
+            licensee,
+        },
+        instruction::VerifyLicense {},
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pay_licensing_fee_encodes_amount() {
+        let from = Pubkey::new_unique();
+        let license = Pubkey::new_unique();
+        let ix = pay_licensing_fee(from, license, 42);
+
+        assert_eq!(ix.program_id, ID);
+        assert_eq!(ix.accounts[0], AccountMeta::new(from, true));
+        assert_eq!(ix.accounts[1], AccountMeta::new(license, false));
+        assert_eq!(ix.accounts[2].pubkey, pda::license_mint(&license).0);
+        assert_eq!(ix.accounts[5].pubkey, pda::vault().0);
+        // 8 byte sighash followed by the borsh encoded amount
+        assert_eq!(ix.data.len(), 16);
+        assert_eq!(ix.data[8..], 42u64.to_le_bytes());
+    }
+
+    #[test]
+    fn add_artist_appends_artist_signers() {
+        let artists = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let ix = add_artist(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1000,
+            ArtistDilution::ProRata,
+            &artists,
+        );
+
+        let remaining = &ix.accounts[ix.accounts.len() - 2..];
+        assert_eq!(remaining[0], AccountMeta::new_readonly(artists[0], true));
+        assert_eq!(remaining[1], AccountMeta::new_readonly(artists[1], true));
+    }
+
+    // Expected metas below follow the field order and constraints of the
+    // program's #[derive(Accounts)] structs, update both together
+    fn writable(key: Pubkey) -> AccountMeta {
+        AccountMeta::new(key, false)
+    }
+
+    fn readonly(key: Pubkey) -> AccountMeta {
+        AccountMeta::new_readonly(key, false)
+    }
+
+    fn signer(key: Pubkey) -> AccountMeta {
+        AccountMeta::new_readonly(key, true)
+    }
+
+    fn writable_signer(key: Pubkey) -> AccountMeta {
+        AccountMeta::new(key, true)
+    }
+
+    fn key() -> Pubkey {
+        Pubkey::new_unique()
+    }
+
+    fn percentages() -> CollectionPercentages {
+        CollectionPercentages {
+            artist_mint_percentage: 5000,
+            label_mint_percentage: 5000,
+            artist_secondary_percentage: 5000,
+            label_secondary_percentage: 5000,
+            artist_licensing_percentage: 5000,
+            label_licensing_percentage: 5000,
+        }
+    }
+
+    #[test]
+    fn collection_builders_match_program_accounts() {
+        let config = pda::collection_config().0;
+        let nft_ledger = pda::nft_ledger().0;
+        let artist_ledger = pda::artist_ledger().0;
+        let vault = pda::vault().0;
+        let (payer, authority, artist, nft, token_account) = (key(), key(), key(), key(), key());
+
+        let ix = initialize_collection(payer, percentages(), artist);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(config),
+                writable(nft_ledger),
+                writable(artist_ledger),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = member_withdraw(payer, nft, token_account);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(payer),
+                writable(vault),
+                readonly(nft),
+                readonly(token_account),
+                writable(nft_ledger),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = artist_withdraw(artist);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(artist),
+                writable(vault),
+                writable(artist_ledger),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = distribute_secondary_pool();
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(vault),
+                writable(pda::secondary_pool().0),
+                readonly(config),
+                writable(artist_ledger),
+                writable(nft_ledger),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = add_nft(payer, authority, nft, 0);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable_signer(payer),
+                writable_signer(authority),
+                readonly(config),
+                writable(artist_ledger),
+                writable(nft_ledger),
+                readonly(nft),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = expand_ledger(payer, 1, 1);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(config),
+                writable(artist_ledger),
+                writable(nft_ledger),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let new_authority = key();
+        let ix = transfer_collection_authority(authority, artist, new_authority);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(config),
+                signer(authority),
+                signer(artist),
+                readonly(new_authority),
+            ]
+        );
+
+        for (ix, account) in [
+            (migrate_collection_config(payer), config),
+            (migrate_nft_balance_ledger(payer), nft_ledger),
+            (migrate_artist_balance_ledger(payer), artist_ledger),
+        ] {
+            assert_eq!(
+                ix.accounts,
+                vec![
+                    writable(account),
+                    writable_signer(payer),
+                    readonly(system_program::ID),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn artist_builders_match_program_accounts() {
+        let config = pda::collection_config().0;
+        let artist_ledger = pda::artist_ledger().0;
+        let proposal = pda::config_update().0;
+        let (payer, authority, artist, new_artist) = (key(), key(), key(), key());
+
+        let ix = propose_config_update(payer, authority, percentages(), vec![]);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(proposal),
+                readonly(config),
+                signer(authority),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = approve_config_update(artist);
+        assert_eq!(ix.accounts, vec![writable(proposal), signer(artist)]);
+
+        let ix = execute_config_update(payer, authority);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(proposal),
+                writable(config),
+                writable(artist_ledger),
+                writable(authority),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = cancel_config_update(authority, payer);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(proposal),
+                readonly(config),
+                signer(authority),
+                writable(payer),
+            ]
+        );
+
+        let ix = transfer_artist_split(payer, artist, new_artist, 1000);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(config),
+                writable(artist_ledger),
+                signer(artist),
+                readonly(new_artist),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = add_artist(
+            payer,
+            authority,
+            new_artist,
+            1000,
+            ArtistDilution::ProRata,
+            &[artist],
+        );
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(config),
+                writable(artist_ledger),
+                signer(authority),
+                readonly(new_artist),
+                writable_signer(payer),
+                readonly(system_program::ID),
+                signer(artist),
+            ]
+        );
+
+        let ix = remove_artist(authority, new_artist, ArtistDilution::Reserve, &[artist]);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(config),
+                signer(authority),
+                readonly(new_artist),
+                signer(artist),
+            ]
+        );
+    }
+
+    #[test]
+    fn governance_builders_match_program_accounts() {
+        let governance = pda::holder_governance().0;
+        let proposal = pda::holder_proposal(3).0;
+        let (payer, authority, holder, nft, token_account, artist) =
+            (key(), key(), key(), key(), key(), key());
+
+        let ix = initialize_holder_governance(payer, authority, 5000, 100);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(governance),
+                readonly(pda::collection_config().0),
+                signer(authority),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let action = GovernanceAction::SetFeeTiers { tiers: vec![] };
+        let ix = create_holder_proposal(holder, 3, nft, token_account, action);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(governance),
+                writable(proposal),
+                readonly(pda::nft_ledger().0),
+                readonly(nft),
+                readonly(token_account),
+                writable_signer(holder),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = cast_holder_vote(holder, 3, nft, token_account, true);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(proposal),
+                readonly(pda::nft_ledger().0),
+                readonly(nft),
+                readonly(token_account),
+                signer(holder),
+            ]
+        );
+
+        let ix = execute_holder_proposal(3, &[artist]);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(proposal),
+                writable(pda::collection_config().0),
+                writable(pda::fee_schedule().0),
+                signer(artist),
+            ]
+        );
+    }
+
+    #[test]
+    fn licensing_builders_match_program_accounts() {
+        let config = pda::collection_config().0;
+        let nft_ledger = pda::nft_ledger().0;
+        let artist_ledger = pda::artist_ledger().0;
+        let (authority, licensee, reporter, price_feed) = (key(), key(), key(), key());
+
+        let terms = LicenseTerms {
+            content_hash: [7; 32],
+            terms_uri: String::new(),
+            fee: 0,
+            usage_type: UsageType::Commercial,
+            start_slot: 0,
+            end_slot: 0,
+            grace_slots: 0,
+            volume: 0,
+            licensed_nfts: vec![],
+            track_share_percentage: 0,
+            reporter: None,
+            per_use_rate: 0,
+        };
+        let license = pda::license(&licensee, &terms.content_hash).0;
+        let license_mint = pda::license_mint(&license).0;
+        let license_token = pda::license_token_account(&license).0;
+
+        let ix = create_license(authority, licensee, terms);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(license),
+                writable(license_mint),
+                writable(license_token),
+                readonly(pda::license_authority().0),
+                readonly(config),
+                readonly(nft_ledger),
+                signer(authority),
+                writable_signer(licensee),
+                readonly(system_program::ID),
+                readonly(token::ID),
+                readonly(sysvar::rent::ID),
+            ]
+        );
+
+        let payment_accounts = vec![
+            writable_signer(licensee),
+            writable(license),
+            writable(license_mint),
+            writable(license_token),
+            readonly(pda::license_authority().0),
+            writable(pda::vault().0),
+            readonly(config),
+            readonly(pda::fee_schedule().0),
+            writable(artist_ledger),
+            writable(nft_ledger),
+            readonly(system_program::ID),
+            readonly(token::ID),
+        ];
+        for ix in [
+            pay_licensing_fee(licensee, license, 1),
+            renew_license(licensee, license),
+            pay_usage_fee(licensee, license),
+        ] {
+            assert_eq!(ix.accounts, payment_accounts);
+        }
+
+        let ix = pay_licensing_fee_usd(licensee, license, price_feed, 100, 1, 0);
+        let mut usd_accounts = payment_accounts;
+        usd_accounts.push(readonly(price_feed));
+        assert_eq!(ix.accounts, usd_accounts);
+
+        let ix = lapse_license(license);
+        assert_eq!(ix.accounts, vec![writable(license)]);
+
+        let ix = report_usage(reporter, license, 2, vec![]);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(pda::usage_report(&license, 2).0),
+                writable(license),
+                readonly(nft_ledger),
+                writable_signer(reporter),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = release_licensing_payments(license);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(license),
+                readonly(config),
+                writable(artist_ledger),
+                writable(nft_ledger),
+            ]
+        );
+
+        let ix = refund_licensing_payment(authority, license, licensee, 0);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(license),
+                writable(licensee),
+                writable(pda::vault().0),
+                readonly(config),
+                readonly(nft_ledger),
+                signer(authority),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = verify_license(license, licensee);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                readonly(license),
+                readonly(license_token),
+                readonly(licensee),
+            ]
+        );
+    }
+
+    #[test]
+    fn fee_schedule_builders_match_program_accounts() {
+        let fee_schedule = pda::fee_schedule().0;
+        let config = pda::collection_config().0;
+        let (payer, authority) = (key(), key());
+
+        let ix = initialize_fee_schedule(payer, authority, vec![]);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(fee_schedule),
+                readonly(config),
+                signer(authority),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let update_accounts = vec![writable(fee_schedule), readonly(config), signer(authority)];
+        for ix in [
+            update_fee_schedule(authority, vec![]),
+            set_fee_currency(authority, FeeCurrency::Lamports, vec![]),
+            set_payment_hold(authority, 0),
+        ] {
+            assert_eq!(ix.accounts, update_accounts);
+        }
+    }
+
+    #[test]
+    fn secondary_sale_builders_match_program_accounts() {
+        let config = pda::collection_config().0;
+        let (payer, authority, reporter, nft) = (key(), key(), key(), key());
+        let sale_reporter = pda::sale_reporter(&reporter).0;
+
+        let ix = initialize_nft_sales(payer, nft);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(pda::nft_sales(&nft).0),
+                readonly(nft),
+                readonly(pda::nft_ledger().0),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = approve_sale_reporter(payer, authority, reporter);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(sale_reporter),
+                readonly(config),
+                signer(authority),
+                writable_signer(payer),
+                readonly(system_program::ID),
+            ]
+        );
+
+        let ix = revoke_sale_reporter(authority, reporter);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(sale_reporter),
+                readonly(config),
+                writable_signer(authority),
+            ]
+        );
+
+        let ix = record_secondary_sale(reporter, nft, 4, 100, key(), key(), 10);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                writable(pda::nft_sales(&nft).0),
+                writable(pda::secondary_sale(&nft, 4).0),
+                writable(pda::secondary_pool().0),
+                readonly(sale_reporter),
+                writable_signer(reporter),
+                readonly(system_program::ID),
+            ]
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! Rust client for the nft-vault-prototype program
+//!
+//! Instruction builders fill in the instruction data and account structs
+//! the program's `instruction` and `accounts` modules generate, and the
+//! crate adds PDA derivation and account deserializers.
+//!
+//! The program's `cpi` feature isn't enabled, those modules exist without
+//! it. `cpi` turns on `no-entrypoint`, and features unify across the
+//! workspace, so it would strip the program's `entry` from every build,
+//! including the native ones solana-program-test loads.
+
+#[cfg(feature = "events")]
+pub mod events;
+#[cfg(feature = "rpc")]
+pub mod export;
+pub mod instruction;
+pub mod pda;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod state;
+
+pub use nft_vault_prototype::governance::GovernanceAction;
+pub use nft_vault_prototype::{ArtistDilution, ArtistPercentage, ID};
+pub use royalty_math;
@@ -0,0 +1,122 @@
+//! Program derived addresses used by the program
+//!
+//! The program is a single collection per deployment, so every PDA
+//! except holder proposals and licenses has a static seed.
+
+use anchor_lang::prelude::Pubkey;
+use nft_vault_prototype::ID;
+
+pub const COLLECTION_CONFIG_SEED: &[u8] = b"collection-config";
+pub const NFT_LEDGER_SEED: &[u8] = b"nft-ledger";
+pub const ARTIST_LEDGER_SEED: &[u8] = b"artist-ledger";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const SECONDARY_POOL_SEED: &[u8] = b"secondary-pool";
+pub const CONFIG_UPDATE_SEED: &[u8] = b"config-update";
+pub const HOLDER_GOVERNANCE_SEED: &[u8] = b"holder-governance";
+pub const HOLDER_PROPOSAL_SEED: &[u8] = b"holder-proposal";
+pub const LICENSE_SEED: &[u8] = b"license";
+pub const LICENSE_AUTHORITY_SEED: &[u8] = b"license-authority";
+pub const LICENSE_MINT_SEED: &[u8] = b"license-mint";
+pub const LICENSE_TOKEN_SEED: &[u8] = b"license-token";
+pub const FEE_SCHEDULE_SEED: &[u8] = b"fee-schedule";
+pub const USAGE_REPORT_SEED: &[u8] = b"usage-report";
+pub const NFT_SALES_SEED: &[u8] = b"nft-sales";
+pub const SECONDARY_SALE_SEED: &[u8] = b"secondary-sale";
+pub const SALE_REPORTER_SEED: &[u8] = b"sale-reporter";
+
+pub fn collection_config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COLLECTION_CONFIG_SEED], &ID)
+}
+
+pub fn nft_ledger() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[NFT_LEDGER_SEED], &ID)
+}
+
+pub fn artist_ledger() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ARTIST_LEDGER_SEED], &ID)
+}
+
+pub fn vault() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED], &ID)
+}
+
+pub fn secondary_pool() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SECONDARY_POOL_SEED], &ID)
+}
+
+pub fn config_update() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_UPDATE_SEED], &ID)
+}
+
+pub fn holder_governance() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOLDER_GOVERNANCE_SEED], &ID)
+}
+
+pub fn holder_proposal(id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOLDER_PROPOSAL_SEED, &id.to_le_bytes()], &ID)
+}
+
+/// One license per licensee and terms document
+pub fn license(licensee: &Pubkey, content_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LICENSE_SEED, licensee.as_ref(), content_hash], &ID)
+}
+
+/// Mint and freeze authority of every license token
+pub fn license_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LICENSE_AUTHORITY_SEED], &ID)
+}
+
+pub fn license_mint(license: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LICENSE_MINT_SEED, license.as_ref()], &ID)
+}
+
+/// The licensee's token account holding the license token
+pub fn license_token_account(license: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LICENSE_TOKEN_SEED, license.as_ref()], &ID)
+}
+
+pub fn fee_schedule() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_SCHEDULE_SEED], &ID)
+}
+
+/// One report per license and usage period
+pub fn usage_report(license: &Pubkey, period: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USAGE_REPORT_SEED, license.as_ref(), &period.to_le_bytes()],
+        &ID,
+    )
+}
+
+/// Running sale totals of an NFT
+pub fn nft_sales(nft: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[NFT_SALES_SEED, nft.as_ref()], &ID)
+}
+
+/// The NFT's sale_index-th recorded sale, counted from 0
+pub fn secondary_sale(nft: &Pubkey, sale_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SECONDARY_SALE_SEED, nft.as_ref(), &sale_index.to_le_bytes()],
+        &ID,
+    )
+}
+
+/// Approval of a marketplace or relayer to record sales
+pub fn sale_reporter(reporter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SALE_REPORTER_SEED, reporter.as_ref()], &ID)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The program hardcodes these bumps, so the derivations must agree
+    #[test]
+    fn matches_program_bumps() {
+        assert_eq!(collection_config().1, 254);
+        assert_eq!(nft_ledger().1, 255);
+        assert_eq!(artist_ledger().1, 255);
+        assert_eq!(vault().1, 255);
+        assert_eq!(secondary_pool().1, 255);
+        assert_eq!(license_authority().1, 254);
+    }
+}
@@ -0,0 +1,430 @@
+//! Minimal JSON-RPC client for talking to a cluster
+//!
+//! Covers only the methods the admin tooling needs. Works against any RPC
+//! URL, including a local `solana-test-validator`.
+
+use std::fmt;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use serde_json::{json, Value};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::events::{parse_logs, ProgramEvent};
+use crate::{pda, state, ID};
+
+const CONFIRMATION_POLLS: usize = 60;
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Largest page getSignaturesForAddress allows
+const SIGNATURE_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug)]
+pub enum RpcError {
+    Transport(String),
+    Rpc { code: i64, message: String },
+    InvalidResponse(String),
+    TransactionFailed(String),
+    ConfirmationTimeout(Signature),
+    AccountNotFound(Pubkey),
+    Deserialize(ProgramError),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RpcError::Transport(err) => write!(f, "rpc transport error: {}", err),
+            RpcError::Rpc { code, message } => write!(f, "rpc error {}: {}", code, message),
+            RpcError::InvalidResponse(err) => write!(f, "invalid rpc response: {}", err),
+            RpcError::TransactionFailed(err) => write!(f, "transaction failed: {}", err),
+            RpcError::ConfirmationTimeout(signature) => {
+                write!(f, "transaction {} was not confirmed in time", signature)
+            }
+            RpcError::AccountNotFound(address) => write!(f, "account {} not found", address),
+            RpcError::Deserialize(err) => write!(f, "could not deserialize account: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// A confirmed transaction that touched an address
+#[derive(Clone, Debug)]
+pub struct SignatureInfo {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+}
+
+/// The program's events from one transaction
+#[derive(Clone, Debug)]
+pub struct TransactionEvents {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub events: Vec<ProgramEvent>,
+}
+
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        RpcClient {
+            url: url.into(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        let response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(body)
+            .map_err(|err| RpcError::Transport(err.to_string()))?
+            .into_json()
+            .map_err(|err| RpcError::InvalidResponse(err.to_string()))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(RpcError::Rpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+
+        Ok(response["result"].clone())
+    }
+
+    pub fn get_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, RpcError> {
+        let result = self.request(
+            "getAccountInfo",
+            json!([address.to_string(), {"encoding": "base64", "commitment": "confirmed"}]),
+        )?;
+
+        if result["value"].is_null() {
+            return Ok(None);
+        }
+
+        let encoded = result["value"]["data"][0]
+            .as_str()
+            .ok_or_else(|| RpcError::InvalidResponse("missing account data".to_string()))?;
+        base64::decode(encoded)
+            .map(Some)
+            .map_err(|err| RpcError::InvalidResponse(err.to_string()))
+    }
+
+    pub fn get_balance(&self, address: &Pubkey) -> Result<u64, RpcError> {
+        let result = self.request(
+            "getBalance",
+            json!([address.to_string(), {"commitment": "confirmed"}]),
+        )?;
+
+        result["value"]
+            .as_u64()
+            .ok_or_else(|| RpcError::InvalidResponse("missing balance".to_string()))
+    }
+
+    pub fn get_latest_blockhash(&self) -> Result<Hash, RpcError> {
+        let result = self.request("getLatestBlockhash", json!([{"commitment": "confirmed"}]))?;
+
+        result["value"]["blockhash"]
+            .as_str()
+            .and_then(|blockhash| blockhash.parse().ok())
+            .ok_or_else(|| RpcError::InvalidResponse("missing blockhash".to_string()))
+    }
+
+    pub fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Signature, RpcError> {
+        let serialized = bincode::serialize(transaction)
+            .map_err(|err| RpcError::InvalidResponse(err.to_string()))?;
+        self.request(
+            "sendTransaction",
+            json!([base64::encode(serialized), {"encoding": "base64", "preflightCommitment": "confirmed"}]),
+        )?;
+
+        let signature = transaction.signatures[0];
+        for _ in 0..CONFIRMATION_POLLS {
+            let result = self.request("getSignatureStatuses", json!([[signature.to_string()]]))?;
+            let status = &result["value"][0];
+
+            if !status.is_null() {
+                if !status["err"].is_null() {
+                    return Err(RpcError::TransactionFailed(status["err"].to_string()));
+                }
+
+                let confirmation = status["confirmationStatus"].as_str().unwrap_or_default();
+                if confirmation == "confirmed" || confirmation == "finalized" {
+                    return Ok(signature);
+                }
+            }
+
+            sleep(CONFIRMATION_POLL_INTERVAL);
+        }
+
+        Err(RpcError::ConfirmationTimeout(signature))
+    }
+
+    /**
+     * Signs the instructions with payer plus any extra signers and sends them
+     * as a single transaction
+     */
+    pub fn send_instructions(
+        &self,
+        instructions: &[Instruction],
+        payer: &Keypair,
+        signers: &[&Keypair],
+    ) -> Result<Signature, RpcError> {
+        let mut all_signers: Vec<&Keypair> = vec![payer];
+        for signer in signers {
+            if signer.pubkey() != payer.pubkey() {
+                all_signers.push(signer);
+            }
+        }
+
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &all_signers,
+            self.get_latest_blockhash()?,
+        );
+
+        self.send_and_confirm_transaction(&transaction)
+    }
+
+    /**
+     * Signatures touching the address, newest first, between the `before`
+     * and `until` signatures (both exclusive) if given
+     */
+    pub fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<&Signature>,
+        until: Option<&Signature>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>, RpcError> {
+        let mut config = json!({"limit": limit, "commitment": "confirmed"});
+        if let Some(before) = before {
+            config["before"] = json!(before.to_string());
+        }
+        if let Some(until) = until {
+            config["until"] = json!(until.to_string());
+        }
+        let result = self.request(
+            "getSignaturesForAddress",
+            json!([address.to_string(), config]),
+        )?;
+
+        let entries = result
+            .as_array()
+            .ok_or_else(|| RpcError::InvalidResponse("expected signature list".to_string()))?;
+        entries
+            .iter()
+            .map(|entry| {
+                Ok(SignatureInfo {
+                    signature: entry["signature"]
+                        .as_str()
+                        .and_then(|signature| signature.parse().ok())
+                        .ok_or_else(|| {
+                            RpcError::InvalidResponse("missing signature".to_string())
+                        })?,
+                    slot: entry["slot"].as_u64().unwrap_or_default(),
+                    block_time: entry["blockTime"].as_i64(),
+                    failed: !entry["err"].is_null(),
+                })
+            })
+            .collect()
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<Value, RpcError> {
+        self.request(
+            "getTransaction",
+            json!([signature.to_string(), {"encoding": "json", "commitment": "confirmed"}]),
+        )
+    }
+
+    /// Log messages of a confirmed transaction, None if it is unknown
+    pub fn get_transaction_logs(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<Vec<String>>, RpcError> {
+        let result = self.get_transaction(signature)?;
+        if result.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(transaction_logs(&result)))
+    }
+
+    /// Decoded program events of one transaction listed by signature lookup
+    pub fn transaction_events(&self, info: &SignatureInfo) -> Result<TransactionEvents, RpcError> {
+        let logs = self
+            .get_transaction_logs(&info.signature)?
+            .unwrap_or_default();
+
+        Ok(TransactionEvents {
+            signature: info.signature,
+            slot: info.slot,
+            block_time: info.block_time,
+            events: parse_logs(&logs),
+        })
+    }
+
+    /**
+     * Decoded program events of any confirmed transaction, None if it is
+     * unknown. Failed transactions have no events.
+     */
+    pub fn signature_events(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<TransactionEvents>, RpcError> {
+        let result = self.get_transaction(signature)?;
+        if result.is_null() {
+            return Ok(None);
+        }
+
+        let events = if result["meta"]["err"].is_null() {
+            parse_logs(&transaction_logs(&result))
+        } else {
+            Vec::new()
+        };
+
+        Ok(Some(TransactionEvents {
+            signature: *signature,
+            slot: result["slot"].as_u64().unwrap_or_default(),
+            block_time: result["blockTime"].as_i64(),
+            events,
+        }))
+    }
+
+    /**
+     * Every program transaction after `until`, or all of them, oldest first
+     *
+     * Only goes back as far as the node's transaction history.
+     */
+    pub fn program_signatures(
+        &self,
+        until: Option<&Signature>,
+    ) -> Result<Vec<SignatureInfo>, RpcError> {
+        let mut signatures = Vec::new();
+        let mut before = None;
+
+        loop {
+            let page =
+                self.get_signatures_for_address(&ID, before.as_ref(), until, SIGNATURE_PAGE_SIZE)?;
+            let done = page.len() < SIGNATURE_PAGE_SIZE;
+            before = page.last().map(|info| info.signature);
+            signatures.extend(page);
+            if done {
+                break;
+            }
+        }
+
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    /// Replays every successful program transaction, oldest first
+    pub fn program_events(&self) -> Result<Vec<TransactionEvents>, RpcError> {
+        self.program_signatures(None)?
+            .iter()
+            .filter(|info| !info.failed)
+            .map(|info| self.transaction_events(info))
+            .collect()
+    }
+
+    /**
+     * Wallet currently holding the NFT
+     *
+     * Looks up the mint's largest token account and returns its owner,
+     * None if no token account holds the NFT.
+     */
+    pub fn nft_owner(&self, mint: &Pubkey) -> Result<Option<Pubkey>, RpcError> {
+        let result = self.request(
+            "getTokenLargestAccounts",
+            json!([mint.to_string(), {"commitment": "confirmed"}]),
+        )?;
+
+        let holder = result["value"].as_array().and_then(|accounts| {
+            accounts.iter().find(|account| {
+                account["amount"]
+                    .as_str()
+                    .and_then(|amount| amount.parse::<u64>().ok())
+                    .unwrap_or_default()
+                    > 0
+            })
+        });
+        let token_account = match holder.and_then(|account| account["address"].as_str()) {
+            Some(token_account) => token_account,
+            None => return Ok(None),
+        };
+
+        let result = self.request(
+            "getAccountInfo",
+            json!([token_account, {"encoding": "jsonParsed", "commitment": "confirmed"}]),
+        )?;
+
+        Ok(result["value"]["data"]["parsed"]["info"]["owner"]
+            .as_str()
+            .and_then(|owner| owner.parse().ok()))
+    }
+
+    fn fetch_account_data(&self, address: &Pubkey) -> Result<Vec<u8>, RpcError> {
+        self.get_account_data(address)?
+            .ok_or(RpcError::AccountNotFound(*address))
+    }
+
+    pub fn collection_configuration(&self) -> Result<state::CollectionConfiguration, RpcError> {
+        let data = self.fetch_account_data(&pda::collection_config().0)?;
+        state::collection_configuration(&data).map_err(RpcError::Deserialize)
+    }
+
+    pub fn nft_balance_ledger(&self) -> Result<state::NftBalanceLedger, RpcError> {
+        let data = self.fetch_account_data(&pda::nft_ledger().0)?;
+        state::nft_balance_ledger(&data).map_err(RpcError::Deserialize)
+    }
+
+    pub fn artist_balance_ledger(&self) -> Result<state::ArtistBalanceLedger, RpcError> {
+        let data = self.fetch_account_data(&pda::artist_ledger().0)?;
+        state::artist_balance_ledger(&data).map_err(RpcError::Deserialize)
+    }
+
+    /// None until the NFT's sale totals are initialized
+    pub fn nft_sales(&self, nft: &Pubkey) -> Result<Option<state::NftSales>, RpcError> {
+        match self.get_account_data(&pda::nft_sales(nft).0)? {
+            Some(data) => state::nft_sales(&data)
+                .map(Some)
+                .map_err(RpcError::Deserialize),
+            None => Ok(None),
+        }
+    }
+}
+
+fn transaction_logs(transaction: &Value) -> Vec<String> {
+    transaction["meta"]["logMessages"]
+        .as_array()
+        .map(|logs| {
+            logs.iter()
+                .filter_map(|log| log.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
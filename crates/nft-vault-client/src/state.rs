@@ -0,0 +1,60 @@
+//! Deserializers for the program's accounts
+//!
+//! Each expects the raw account data, including the 8 byte discriminator.
+
+use anchor_lang::prelude::ProgramError;
+use anchor_lang::AccountDeserialize;
+
+pub use nft_vault_prototype::fee_schedule::{FeeCurrency, FeeSchedule, FeeTier};
+pub use nft_vault_prototype::governance::{HolderGovernance, HolderProposal};
+pub use nft_vault_prototype::licensing::{License, TrackUsage, UsageReport, UsageType};
+pub use nft_vault_prototype::price_feed::PriceFeedConfig;
+pub use nft_vault_prototype::secondary_sales::{NftSales, SecondarySale};
+pub use nft_vault_prototype::{
+    ArtistBalance, ArtistBalanceLedger, CollectionConfiguration, ConfigUpdateProposal, NftBalance,
+    NftBalanceLedger,
+};
+
+pub fn collection_configuration(data: &[u8]) -> Result<CollectionConfiguration, ProgramError> {
+    CollectionConfiguration::try_deserialize(&mut &data[..])
+}
+
+pub fn nft_balance_ledger(data: &[u8]) -> Result<NftBalanceLedger, ProgramError> {
+    NftBalanceLedger::try_deserialize(&mut &data[..])
+}
+
+pub fn artist_balance_ledger(data: &[u8]) -> Result<ArtistBalanceLedger, ProgramError> {
+    ArtistBalanceLedger::try_deserialize(&mut &data[..])
+}
+
+pub fn config_update_proposal(data: &[u8]) -> Result<ConfigUpdateProposal, ProgramError> {
+    ConfigUpdateProposal::try_deserialize(&mut &data[..])
+}
+
+pub fn holder_governance(data: &[u8]) -> Result<HolderGovernance, ProgramError> {
+    HolderGovernance::try_deserialize(&mut &data[..])
+}
+
+pub fn holder_proposal(data: &[u8]) -> Result<HolderProposal, ProgramError> {
+    HolderProposal::try_deserialize(&mut &data[..])
+}
+
+pub fn license(data: &[u8]) -> Result<License, ProgramError> {
+    License::try_deserialize(&mut &data[..])
+}
+
+pub fn fee_schedule(data: &[u8]) -> Result<FeeSchedule, ProgramError> {
+    FeeSchedule::try_deserialize(&mut &data[..])
+}
+
+pub fn usage_report(data: &[u8]) -> Result<UsageReport, ProgramError> {
+    UsageReport::try_deserialize(&mut &data[..])
+}
+
+pub fn nft_sales(data: &[u8]) -> Result<NftSales, ProgramError> {
+    NftSales::try_deserialize(&mut &data[..])
+}
+
+pub fn secondary_sale(data: &[u8]) -> Result<SecondarySale, ProgramError> {
+    SecondarySale::try_deserialize(&mut &data[..])
+}
@@ -0,0 +1,106 @@
+//! Read-only HTTP query API
+//!
+//! GET /nfts/<address>     earnings history of one NFT
+//! GET /artists/<address>  earnings history of one artist
+//! GET /collection         totals per royalty stream and every distribution
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::json;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::store::Store;
+
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+fn ok(value: &impl Serialize) -> Result<ApiResponse> {
+    Ok(ApiResponse {
+        status: 200,
+        body: serde_json::to_string(value)?,
+    })
+}
+
+fn error(status: u16, message: &str) -> ApiResponse {
+    ApiResponse {
+        status,
+        body: json!({ "error": message }).to_string(),
+    }
+}
+
+pub fn route(store: &Store, method: &Method, url: &str) -> Result<ApiResponse> {
+    if *method != Method::Get {
+        return Ok(error(405, "method not allowed"));
+    }
+
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        ["collection"] => ok(&store.collection_history()?),
+        ["nfts", address] => match store.nft_history(address)? {
+            Some(history) => ok(&history),
+            None => Ok(error(404, "nft not indexed")),
+        },
+        ["artists", address] => match store.artist_history(address)? {
+            Some(history) => ok(&history),
+            None => Ok(error(404, "artist not indexed")),
+        },
+        _ => Ok(error(404, "not found")),
+    }
+}
+
+/// Serves queries until the process exits, with its own database connection
+pub fn serve(database: &Path, listen: &str) -> Result<()> {
+    let store = Store::open(database)?;
+    let server = Server::http(listen).map_err(|err| anyhow!("listening on {}: {}", listen, err))?;
+    println!("serving queries on http://{}", listen);
+
+    for request in server.incoming_requests() {
+        let response = route(&store, request.method(), request.url())
+            .unwrap_or_else(|err| error(500, &err.to_string()));
+        let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+            .expect("static header is valid");
+
+        let _ = request.respond(
+            Response::from_string(response.body)
+                .with_status_code(response.status)
+                .with_header(content_type),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::fixture;
+
+    #[test]
+    fn routes_queries() {
+        let fixture = fixture();
+
+        let response = route(
+            &fixture.store,
+            &Method::Get,
+            &format!("/nfts/{}", fixture.nfts[1]),
+        )
+        .unwrap();
+        assert_eq!(response.status, 200);
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["earned"], 200);
+
+        let response = route(&fixture.store, &Method::Get, "/collection?pretty").unwrap();
+        assert_eq!(response.status, 200);
+
+        let response = route(&fixture.store, &Method::Get, "/artists/unknown").unwrap();
+        assert_eq!(response.status, 404);
+        let response = route(&fixture.store, &Method::Post, "/collection").unwrap();
+        assert_eq!(response.status, 405);
+    }
+}
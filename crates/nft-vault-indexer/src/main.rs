@@ -0,0 +1,141 @@
+//! `nft-vault-indexer`
+//!
+//! Follows the program by polling for new transaction signatures, decodes
+//! the events in their logs into SQLite and serves earnings history over
+//! HTTP. Works against any RPC URL, including a local `solana-test-validator`.
+
+mod api;
+mod store;
+
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use nft_vault_client::rpc::RpcClient;
+use solana_sdk::signature::Signature;
+
+use store::Store;
+
+#[derive(Parser)]
+#[clap(
+    name = "nft-vault-indexer",
+    version,
+    about = "Index nft-vault events into SQLite"
+)]
+struct Cli {
+    /// RPC URL of the cluster
+    #[clap(long, short, global = true, default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// SQLite database file, created if missing
+    #[clap(long, global = true, default_value = "nft-vault-index.sqlite")]
+    database: PathBuf,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Index new transactions as they land and serve the query API
+    Run {
+        #[clap(long, default_value = "127.0.0.1:8900")]
+        listen: String,
+        /// Milliseconds between polls for new transactions
+        #[clap(long, default_value = "2000")]
+        poll_interval: u64,
+    },
+    /// Index everything up to now once and exit
+    Sync,
+    /// Index specific transactions, in the order given
+    Replay { signatures: Vec<String> },
+    /// Serve the query API without indexing
+    Serve {
+        #[clap(long, default_value = "127.0.0.1:8900")]
+        listen: String,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let client = RpcClient::new(cli.url.clone());
+
+    match cli.command {
+        Command::Run {
+            listen,
+            poll_interval,
+        } => {
+            let mut store = Store::open(&cli.database)?;
+            let database = cli.database.clone();
+            thread::spawn(move || {
+                if let Err(err) = api::serve(&database, &listen) {
+                    eprintln!("query API stopped: {}", err);
+                }
+            });
+
+            loop {
+                match sync(&client, &mut store) {
+                    Ok(indexed) if indexed > 0 => println!("indexed {} transactions", indexed),
+                    Ok(_) => {}
+                    Err(err) => eprintln!("sync failed: {}", err),
+                }
+                thread::sleep(Duration::from_millis(poll_interval));
+            }
+        }
+        Command::Sync => {
+            let indexed = sync(&client, &mut Store::open(&cli.database)?)?;
+            println!("indexed {} transactions", indexed);
+            Ok(())
+        }
+        Command::Replay { signatures } => replay(&client, &cli.database, &signatures),
+        Command::Serve { listen } => api::serve(&cli.database, &listen),
+    }
+}
+
+/**
+ * Indexes every program transaction after the cursor, oldest first
+ *
+ * The cursor moves after each transaction, so an interrupted sync resumes
+ * where it stopped.
+ */
+fn sync(client: &RpcClient, store: &mut Store) -> Result<usize> {
+    let cursor = store
+        .cursor()?
+        .map(|signature| signature.parse::<Signature>())
+        .transpose()?;
+
+    let mut indexed = 0;
+    for info in client.program_signatures(cursor.as_ref())? {
+        if !info.failed && store.index_transaction(&client.transaction_events(&info)?)? {
+            indexed += 1;
+        }
+        store.set_cursor(&info.signature.to_string())?;
+    }
+
+    Ok(indexed)
+}
+
+fn replay(client: &RpcClient, database: &Path, signatures: &[String]) -> Result<()> {
+    let mut store = Store::open(database)?;
+
+    for signature in signatures {
+        let signature: Signature = signature.parse()?;
+        let transaction = client
+            .signature_events(&signature)?
+            .ok_or_else(|| anyhow!("transaction {} not found", signature))?;
+
+        if store.index_transaction(&transaction)? {
+            println!(
+                "indexed {} ({} events)",
+                signature,
+                transaction.events.len()
+            );
+        } else {
+            println!("{} already indexed", signature);
+        }
+    }
+
+    Ok(())
+}
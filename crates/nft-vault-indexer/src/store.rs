@@ -0,0 +1,654 @@
+//! SQLite storage for indexed events
+//!
+//! Transactions are indexed at most once, keyed by signature. Per-NFT
+//! earnings are not materialized: an NFT earned from every distribution
+//! whose nft_count is past its position in the ledger, plus the credits of
+//! licenses naming it as a licensed track. Artist earnings are
+//! split at index time with the splits in force, so transactions need to be
+//! indexed in order.
+
+use std::path::Path;
+
+use anyhow::Result;
+use nft_vault_client::events::{stream_name, ProgramEvent};
+use nft_vault_client::rpc::TransactionEvents;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER
+);
+CREATE TABLE IF NOT EXISTS nfts (
+    nft_address TEXT PRIMARY KEY,
+    position INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS artist_splits (
+    artist_address TEXT PRIMARY KEY,
+    allocated_percentage INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS distributions (
+    id INTEGER PRIMARY KEY,
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    stream TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    artist_total INTEGER NOT NULL,
+    holder_total INTEGER NOT NULL,
+    per_nft_share INTEGER NOT NULL,
+    nft_count INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS artist_credits (
+    id INTEGER PRIMARY KEY,
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    artist_address TEXT NOT NULL,
+    stream TEXT NOT NULL,
+    amount INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS artist_credits_by_artist ON artist_credits (artist_address);
+CREATE TABLE IF NOT EXISTS track_credits (
+    id INTEGER PRIMARY KEY,
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    nft_address TEXT NOT NULL,
+    amount INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS track_credits_by_nft ON track_credits (nft_address);
+CREATE TABLE IF NOT EXISTS withdrawals (
+    id INTEGER PRIMARY KEY,
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    kind TEXT NOT NULL,
+    address TEXT NOT NULL,
+    amount INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS withdrawals_by_address ON withdrawals (kind, address);
+CREATE TABLE IF NOT EXISTS cursor (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    signature TEXT NOT NULL
+);
+";
+
+const MEMBER_WITHDRAWAL: &str = "member";
+const ARTIST_WITHDRAWAL: &str = "artist";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HistoryItem {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: String,
+    pub stream: Option<String>,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EarningsHistory {
+    pub address: String,
+    pub earned: u64,
+    pub withdrawn: u64,
+    pub history: Vec<HistoryItem>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StreamTotal {
+    pub stream: String,
+    pub amount: u64,
+    pub artist_total: u64,
+    pub holder_total: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CollectionHistory {
+    pub nft_count: u64,
+    pub streams: Vec<StreamTotal>,
+    pub member_withdrawn: u64,
+    pub artist_withdrawn: u64,
+    pub history: Vec<HistoryItem>,
+}
+
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        // Lets the HTTP server read while the indexer writes
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(connection)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Store { connection })
+    }
+
+    /// Newest signature the follower has indexed
+    pub fn cursor(&self) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row("SELECT signature FROM cursor WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    pub fn set_cursor(&self, signature: &str) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO cursor (id, signature) VALUES (0, ?1)
+             ON CONFLICT (id) DO UPDATE SET signature = excluded.signature",
+            params![signature],
+        )?;
+        Ok(())
+    }
+
+    /// Stores a transaction's events, returns false if it was already indexed
+    pub fn index_transaction(&mut self, transaction: &TransactionEvents) -> Result<bool> {
+        let db = self.connection.transaction()?;
+        let signature = transaction.signature.to_string();
+        let slot = transaction.slot as i64;
+        let block_time = transaction.block_time;
+
+        let inserted = db.execute(
+            "INSERT OR IGNORE INTO transactions (signature, slot, block_time) VALUES (?1, ?2, ?3)",
+            params![signature, slot, block_time],
+        )?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+
+        for event in &transaction.events {
+            match event {
+                ProgramEvent::CollectionInitialized(event) => {
+                    replace_splits(&db, &event.artist_splits)?
+                }
+                ProgramEvent::CollectionConfigUpdated(event) => {
+                    replace_splits(&db, &event.artist_splits)?
+                }
+                ProgramEvent::NftAdded(event) => {
+                    db.execute(
+                        "INSERT OR IGNORE INTO nfts (nft_address, position) VALUES (?1, ?2)",
+                        params![event.nft_address.to_string(), event.nft_count as i64 - 1],
+                    )?;
+                }
+                ProgramEvent::RoyaltiesDistributed(event) => {
+                    let stream = stream_name(event.stream);
+                    db.execute(
+                        "INSERT INTO distributions (signature, slot, block_time, stream, amount,
+                            artist_total, holder_total, per_nft_share, nft_count)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        params![
+                            signature,
+                            slot,
+                            block_time,
+                            stream,
+                            event.amount as i64,
+                            event.artist_total as i64,
+                            event.holder_total as i64,
+                            event.per_nft_share as i64,
+                            event.nft_count as i64,
+                        ],
+                    )?;
+
+                    // Same rounding as the artist ledger: pro-rata to the
+                    // allocated splits, rounded down
+                    let splits = current_splits(&db)?;
+                    let total_allocated: u64 =
+                        splits.iter().map(|(_, percentage)| percentage).sum();
+                    for (artist_address, percentage) in &splits {
+                        let amount = match royalty_math::pro_rata_share(
+                            event.artist_total,
+                            *percentage,
+                            total_allocated,
+                        ) {
+                            Ok(amount) => amount as i64,
+                            Err(_) => continue,
+                        };
+                        db.execute(
+                            "INSERT INTO artist_credits
+                                (signature, slot, block_time, artist_address, stream, amount)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                            params![signature, slot, block_time, artist_address, stream, amount],
+                        )?;
+                    }
+                }
+                ProgramEvent::LicensedTracksCredited(event) => {
+                    for nft in &event.nfts {
+                        db.execute(
+                            "INSERT INTO track_credits
+                                (signature, slot, block_time, nft_address, amount)
+                             VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![
+                                signature,
+                                slot,
+                                block_time,
+                                nft.to_string(),
+                                event.per_nft_share as i64
+                            ],
+                        )?;
+                    }
+                }
+                ProgramEvent::MemberWithdrawn(event) => insert_withdrawal(
+                    &db,
+                    transaction,
+                    MEMBER_WITHDRAWAL,
+                    &event.nft_address.to_string(),
+                    event.amount,
+                )?,
+                ProgramEvent::ArtistWithdrawn(event) => insert_withdrawal(
+                    &db,
+                    transaction,
+                    ARTIST_WITHDRAWAL,
+                    &event.artist_address.to_string(),
+                    event.amount,
+                )?,
+                ProgramEvent::CollectionAuthorityTransferred(_)
+                | ProgramEvent::LicenseCreated(_)
+                | ProgramEvent::LicenseFeePaid(_)
+                | ProgramEvent::LicenseRenewed(_)
+                | ProgramEvent::LicenseLapsed(_)
+                | ProgramEvent::FeeScheduleUpdated(_)
+                | ProgramEvent::LicenseFeeConverted(_)
+                | ProgramEvent::LicensePaymentHeld(_)
+                | ProgramEvent::LicensePaymentReleased(_)
+                | ProgramEvent::LicensePaymentRefunded(_)
+                | ProgramEvent::UsageReported(_)
+                | ProgramEvent::SecondarySaleRecorded(_) => {}
+            }
+        }
+
+        db.commit()?;
+        Ok(true)
+    }
+
+    pub fn nft_history(&self, nft_address: &str) -> Result<Option<EarningsHistory>> {
+        let position: Option<i64> = self
+            .connection
+            .query_row(
+                "SELECT position FROM nfts WHERE nft_address = ?1",
+                params![nft_address],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let position = match position {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        let mut statement = self.connection.prepare(
+            "SELECT signature, slot, block_time, 'distribution', stream, per_nft_share
+             FROM distributions WHERE nft_count > ?1 AND per_nft_share > 0 ORDER BY id",
+        )?;
+        let mut credits = collect_items(statement.query(params![position])?)?;
+
+        let mut statement = self.connection.prepare(
+            "SELECT signature, slot, block_time, 'track_credit', 'licensing', amount
+             FROM track_credits WHERE nft_address = ?1 ORDER BY id",
+        )?;
+        credits.extend(collect_items(statement.query(params![nft_address])?)?);
+
+        Ok(Some(self.earnings_history(
+            nft_address,
+            credits,
+            MEMBER_WITHDRAWAL,
+        )?))
+    }
+
+    pub fn artist_history(&self, artist_address: &str) -> Result<Option<EarningsHistory>> {
+        let mut statement = self.connection.prepare(
+            "SELECT signature, slot, block_time, 'distribution', stream, amount
+             FROM artist_credits WHERE artist_address = ?1 ORDER BY id",
+        )?;
+        let credits = collect_items(statement.query(params![artist_address])?)?;
+
+        let history = self.earnings_history(artist_address, credits, ARTIST_WITHDRAWAL)?;
+        if history.history.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(history))
+    }
+
+    pub fn collection_history(&self) -> Result<CollectionHistory> {
+        let nft_count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM nfts", [], |row| row.get(0))?;
+
+        let mut statement = self.connection.prepare(
+            "SELECT stream, SUM(amount), SUM(artist_total), SUM(holder_total)
+             FROM distributions GROUP BY stream ORDER BY stream",
+        )?;
+        let streams = statement
+            .query_map([], |row| {
+                Ok(StreamTotal {
+                    stream: row.get(0)?,
+                    amount: row.get::<_, i64>(1)? as u64,
+                    artist_total: row.get::<_, i64>(2)? as u64,
+                    holder_total: row.get::<_, i64>(3)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut statement = self.connection.prepare(
+            "SELECT signature, slot, block_time, 'distribution', stream, amount
+             FROM distributions ORDER BY id",
+        )?;
+        let history = collect_items(statement.query([])?)?;
+
+        Ok(CollectionHistory {
+            nft_count: nft_count as u64,
+            streams,
+            member_withdrawn: self.total_withdrawn(MEMBER_WITHDRAWAL)?,
+            artist_withdrawn: self.total_withdrawn(ARTIST_WITHDRAWAL)?,
+            history,
+        })
+    }
+
+    fn total_withdrawn(&self, kind: &str) -> Result<u64> {
+        let total: i64 = self.connection.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM withdrawals WHERE kind = ?1",
+            params![kind],
+            |row| row.get(0),
+        )?;
+        Ok(total as u64)
+    }
+
+    fn earnings_history(
+        &self,
+        address: &str,
+        credits: Vec<HistoryItem>,
+        withdrawal_kind: &str,
+    ) -> Result<EarningsHistory> {
+        let mut statement = self.connection.prepare(
+            "SELECT signature, slot, block_time, 'withdrawal', NULL, amount
+             FROM withdrawals WHERE kind = ?1 AND address = ?2 ORDER BY id",
+        )?;
+        let withdrawals = collect_items(statement.query(params![withdrawal_kind, address])?)?;
+
+        let earned = credits.iter().map(|item| item.amount).sum();
+        let withdrawn = withdrawals.iter().map(|item| item.amount).sum();
+        let mut history: Vec<HistoryItem> = credits.into_iter().chain(withdrawals).collect();
+        // Stable sort keeps index order within a slot
+        history.sort_by_key(|item| item.slot);
+
+        Ok(EarningsHistory {
+            address: address.to_string(),
+            earned,
+            withdrawn,
+            history,
+        })
+    }
+}
+
+fn replace_splits(
+    db: &rusqlite::Transaction,
+    artist_splits: &[nft_vault_client::ArtistPercentage],
+) -> Result<()> {
+    db.execute("DELETE FROM artist_splits", [])?;
+    for split in artist_splits {
+        db.execute(
+            "INSERT INTO artist_splits (artist_address, allocated_percentage) VALUES (?1, ?2)",
+            params![
+                split.artist_address.to_string(),
+                split.allocated_percentage as i64
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn current_splits(db: &rusqlite::Transaction) -> Result<Vec<(String, u64)>> {
+    let mut statement = db.prepare(
+        "SELECT artist_address, allocated_percentage FROM artist_splits ORDER BY artist_address",
+    )?;
+    let splits = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(splits)
+}
+
+fn insert_withdrawal(
+    db: &rusqlite::Transaction,
+    transaction: &TransactionEvents,
+    kind: &str,
+    address: &str,
+    amount: u64,
+) -> Result<()> {
+    db.execute(
+        "INSERT INTO withdrawals (signature, slot, block_time, kind, address, amount)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            transaction.signature.to_string(),
+            transaction.slot as i64,
+            transaction.block_time,
+            kind,
+            address,
+            amount as i64
+        ],
+    )?;
+    Ok(())
+}
+
+fn collect_items(mut rows: rusqlite::Rows) -> Result<Vec<HistoryItem>> {
+    let mut items = Vec::new();
+    while let Some(row) = rows.next()? {
+        items.push(HistoryItem {
+            signature: row.get(0)?,
+            slot: row.get::<_, i64>(1)? as u64,
+            block_time: row.get(2)?,
+            kind: row.get(3)?,
+            stream: row.get(4)?,
+            amount: row.get::<_, i64>(5)? as u64,
+        });
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use nft_vault_client::events::{
+        ArtistWithdrawn, CollectionInitialized, LicensedTracksCredited, MemberWithdrawn, NftAdded,
+        RoyaltiesDistributed, RoyaltyStream,
+    };
+    use nft_vault_client::ArtistPercentage;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+
+    pub struct Fixture {
+        pub store: Store,
+        pub artist: Pubkey,
+        pub nfts: [Pubkey; 2],
+    }
+
+    fn transaction(slot: u64, events: Vec<ProgramEvent>) -> TransactionEvents {
+        TransactionEvents {
+            signature: Signature::new(&[slot as u8; 64]),
+            slot,
+            block_time: None,
+            events,
+        }
+    }
+
+    fn distributed(artist_total: u64, per_nft_share: u64, nft_count: u64) -> ProgramEvent {
+        ProgramEvent::RoyaltiesDistributed(RoyaltiesDistributed {
+            stream: RoyaltyStream::Licensing,
+            amount: artist_total + per_nft_share * nft_count,
+            artist_total,
+            holder_total: per_nft_share * nft_count,
+            per_nft_share,
+            nft_count,
+        })
+    }
+
+    // Two NFTs, the second added after the first licensing payment
+    pub fn fixture() -> Fixture {
+        let mut store = Store::open_in_memory().unwrap();
+        let artist = Pubkey::new_unique();
+        let nfts = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let transactions = vec![
+            transaction(
+                1,
+                vec![ProgramEvent::CollectionInitialized(CollectionInitialized {
+                    collection_authority: Pubkey::new_unique(),
+                    artist_mint_percentage: 8000,
+                    label_mint_percentage: 2000,
+                    artist_secondary_percentage: 5000,
+                    label_secondary_percentage: 5000,
+                    artist_licensing_percentage: 6000,
+                    label_licensing_percentage: 4000,
+                    artist_splits: vec![ArtistPercentage {
+                        artist_address: artist,
+                        allocated_percentage: 10000,
+                    }],
+                })],
+            ),
+            transaction(
+                2,
+                vec![ProgramEvent::NftAdded(NftAdded {
+                    nft_address: nfts[0],
+                    amount_paid: 0,
+                    nft_count: 1,
+                })],
+            ),
+            transaction(3, vec![distributed(600, 400, 1)]),
+            transaction(
+                4,
+                vec![ProgramEvent::NftAdded(NftAdded {
+                    nft_address: nfts[1],
+                    amount_paid: 0,
+                    nft_count: 2,
+                })],
+            ),
+            transaction(5, vec![distributed(600, 200, 2)]),
+            transaction(
+                6,
+                vec![
+                    ProgramEvent::MemberWithdrawn(MemberWithdrawn {
+                        nft_address: nfts[0],
+                        owner: Pubkey::new_unique(),
+                        amount: 600,
+                    }),
+                    ProgramEvent::ArtistWithdrawn(ArtistWithdrawn {
+                        artist_address: artist,
+                        amount: 1200,
+                    }),
+                ],
+            ),
+        ];
+        for transaction in &transactions {
+            assert!(store.index_transaction(transaction).unwrap());
+        }
+
+        Fixture {
+            store,
+            artist,
+            nfts,
+        }
+    }
+
+    #[test]
+    fn nft_earns_from_distributions_after_it_joined() {
+        let fixture = fixture();
+
+        let first = fixture
+            .store
+            .nft_history(&fixture.nfts[0].to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!((first.earned, first.withdrawn), (600, 600));
+        assert_eq!(first.history.len(), 3);
+
+        let second = fixture
+            .store
+            .nft_history(&fixture.nfts[1].to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!((second.earned, second.withdrawn), (200, 0));
+        assert!(fixture
+            .store
+            .nft_history(&Pubkey::new_unique().to_string())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn licensed_tracks_earn_their_credits() {
+        let mut fixture = fixture();
+        let credited = transaction(
+            7,
+            vec![
+                distributed(600, 100, 2),
+                ProgramEvent::LicensedTracksCredited(LicensedTracksCredited {
+                    license: Pubkey::new_unique(),
+                    nfts: vec![fixture.nfts[1]],
+                    amount: 200,
+                    per_nft_share: 200,
+                }),
+            ],
+        );
+        assert!(fixture.store.index_transaction(&credited).unwrap());
+
+        let first = fixture
+            .store
+            .nft_history(&fixture.nfts[0].to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.earned, 600 + 100);
+
+        let second = fixture
+            .store
+            .nft_history(&fixture.nfts[1].to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.earned, 200 + 100 + 200);
+        assert_eq!(second.history.last().unwrap().kind, "track_credit");
+    }
+
+    #[test]
+    fn artist_and_collection_totals() {
+        let fixture = fixture();
+
+        let artist = fixture
+            .store
+            .artist_history(&fixture.artist.to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!((artist.earned, artist.withdrawn), (1200, 1200));
+
+        let collection = fixture.store.collection_history().unwrap();
+        assert_eq!(collection.nft_count, 2);
+        assert_eq!(collection.streams[0].amount, 1000 + 1000);
+        assert_eq!(collection.member_withdrawn, 600);
+        assert_eq!(collection.artist_withdrawn, 1200);
+    }
+
+    #[test]
+    fn indexes_each_transaction_once() {
+        let mut fixture = fixture();
+
+        assert!(!fixture
+            .store
+            .index_transaction(&transaction(3, vec![distributed(600, 400, 1)]))
+            .unwrap());
+        assert_eq!(fixture.store.collection_history().unwrap().history.len(), 2);
+
+        assert_eq!(fixture.store.cursor().unwrap(), None);
+        fixture.store.set_cursor("a").unwrap();
+        fixture.store.set_cursor("b").unwrap();
+        assert_eq!(fixture.store.cursor().unwrap(), Some("b".to_string()));
+    }
+}
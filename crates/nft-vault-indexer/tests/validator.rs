@@ -0,0 +1,43 @@
+//! Runs the indexer against a live cluster
+//!
+//! Needs a `solana-test-validator` with the program deployed, e.g. after
+//! `anchor test --detach`. Run with `cargo test -p nft-vault-indexer -- --ignored`,
+//! `NFT_VAULT_RPC_URL` overrides the default local RPC URL.
+
+use std::process::Command;
+
+fn indexer(database: &std::path::Path, args: &[&str]) -> std::process::Output {
+    let url =
+        std::env::var("NFT_VAULT_RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8899".to_string());
+
+    Command::new(env!("CARGO_BIN_EXE_nft-vault-indexer"))
+        .args(["--url", &url, "--database"])
+        .arg(database)
+        .args(args)
+        .output()
+        .expect("indexer runs")
+}
+
+#[test]
+#[ignore]
+fn syncs_from_local_validator() {
+    let database =
+        std::env::temp_dir().join(format!("nft-vault-index-{}.sqlite", std::process::id()));
+
+    let first = indexer(&database, &["sync"]);
+    assert!(
+        first.status.success(),
+        "{}",
+        String::from_utf8_lossy(&first.stderr)
+    );
+
+    // The cursor is stored, so a second sync has nothing new to index
+    let second = indexer(&database, &["sync"]);
+    assert!(second.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&second.stdout).trim(),
+        "indexed 0 transactions"
+    );
+
+    let _ = std::fs::remove_file(&database);
+}
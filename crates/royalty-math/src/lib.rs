@@ -0,0 +1,398 @@
+//! Royalty distribution math for the nft-vault-prototype program
+//!
+//! Percentages are basis points out of [`ONE_HUNDRED_PERCENT`]. Every stream
+//! is split between the artists and the NFT holders, the artist half is then
+//! split pro-rata over the artist split table and the holder half equally
+//! over the NFTs. The program always rounds down, the dust stays in the
+//! vault, so nothing is ever credited that wasn't paid.
+//!
+//! `no_std` and without dependencies, so the program, the client and
+//! off-chain simulators all run the same code.
+
+#![no_std]
+
+pub const ONE_HUNDRED_PERCENT: u64 = 10000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    // Percentages out of range or not adding up to the expected total
+    InvalidPercentages,
+    // The same recipient twice in a split table
+    DuplicateRecipient,
+    // Nobody to distribute to
+    NoRecipients,
+    // Result doesn't fit in a u64
+    Overflow,
+    // More credited or withdrawn than is available
+    InsufficientFunds,
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    // Never credits more than was paid, used on-chain
+    Down,
+    // Upper bound, for projections
+    Up,
+}
+
+/// `amount * numerator / denominator`, without overflowing the product
+pub fn mul_div(amount: u64, numerator: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    if denominator == 0 {
+        return Err(Error::NoRecipients);
+    }
+
+    let product = amount as u128 * numerator as u128;
+    let denominator = denominator as u128;
+    let quotient = match rounding {
+        Rounding::Down => product / denominator,
+        // div_ceil is newer than the BPF toolchain's rustc
+        Rounding::Up => {
+            product
+                .checked_add(denominator - 1)
+                .ok_or(Error::Overflow)?
+                / denominator
+        }
+    };
+
+    if quotient > u64::MAX as u128 {
+        return Err(Error::Overflow);
+    }
+    Ok(quotient as u64)
+}
+
+/// `bps` basis points of `amount`, rounded down
+pub fn bps_of(amount: u64, bps: u64) -> Result<u64> {
+    mul_div(amount, bps, ONE_HUNDRED_PERCENT, Rounding::Down)
+}
+
+/// Artist and holder percentages of one revenue stream (mint, secondary or
+/// licensing)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamSplit {
+    pub artist: u64,
+    pub holders: u64,
+}
+
+/// A stream payment divided between artists and holders
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamAmounts {
+    pub artist_total: u64,
+    pub holder_total: u64,
+}
+
+impl StreamSplit {
+    pub fn new(artist: u64, holders: u64) -> Result<Self> {
+        let split = StreamSplit { artist, holders };
+        split.validate()?;
+        Ok(split)
+    }
+
+    // Both halves must add up to 100%
+    pub fn validate(&self) -> Result<()> {
+        match self.artist.checked_add(self.holders) {
+            Some(ONE_HUNDRED_PERCENT) => Ok(()),
+            _ => Err(Error::InvalidPercentages),
+        }
+    }
+
+    /// Each half rounded down, the remainder stays undistributed
+    pub fn divide(&self, amount: u64) -> Result<StreamAmounts> {
+        Ok(StreamAmounts {
+            artist_total: bps_of(amount, self.artist)?,
+            holder_total: bps_of(amount, self.holders)?,
+        })
+    }
+
+    /// Mint proceeds, all going to the artists while there are no holders
+    /// to share them with
+    pub fn divide_mint(&self, amount: u64, holder_count: u64) -> Result<StreamAmounts> {
+        if holder_count == 0 {
+            return Ok(StreamAmounts {
+                artist_total: amount,
+                holder_total: 0,
+            });
+        }
+
+        self.divide(amount)
+    }
+}
+
+impl StreamAmounts {
+    /// Part of `amount` that went to neither half
+    pub fn remainder(&self, amount: u64) -> u64 {
+        amount - self.artist_total - self.holder_total
+    }
+}
+
+/// Same rounded down share of `amount` for each of `holder_count` holders
+pub fn per_holder_share(amount: u64, holder_count: u64) -> Result<u64> {
+    if holder_count == 0 {
+        return Err(Error::NoRecipients);
+    }
+
+    Ok(amount / holder_count)
+}
+
+/// Share of `amount` for a split of `bps` out of `total_allocated`, rounded
+/// down
+pub fn pro_rata_share(amount: u64, bps: u64, total_allocated: u64) -> Result<u64> {
+    mul_div(amount, bps, total_allocated, Rounding::Down)
+}
+
+/// Entry of a split table, e.g. an artist and its percentage
+pub trait Allocation {
+    type Recipient: PartialEq;
+
+    fn recipient(&self) -> Self::Recipient;
+    fn bps(&self) -> u64;
+    fn set_bps(&mut self, bps: u64);
+}
+
+pub fn total_allocated<A: Allocation>(table: &[A]) -> u64 {
+    table.iter().map(Allocation::bps).sum()
+}
+
+/// Recipients must be unique and their percentages add up to expected_total
+pub fn validate_split_table<A: Allocation>(table: &[A], expected_total: u64) -> Result<()> {
+    let mut total: u64 = 0;
+    for (i, allocation) in table.iter().enumerate() {
+        let recipient = allocation.recipient();
+        if table[..i]
+            .iter()
+            .any(|previous| previous.recipient() == recipient)
+        {
+            return Err(Error::DuplicateRecipient);
+        }
+        total = total
+            .checked_add(allocation.bps())
+            .ok_or(Error::InvalidPercentages)?;
+    }
+
+    if total != expected_total {
+        return Err(Error::InvalidPercentages);
+    }
+
+    Ok(())
+}
+
+/// Pro-rata shares of `amount` over the table. Tables that don't add up to
+/// 100% (unallocated artist reserve) still hand out all of `amount`, minus
+/// rounding.
+pub fn split_shares<A: Allocation>(
+    table: &[A],
+    amount: u64,
+) -> impl Iterator<Item = Result<(A::Recipient, u64)>> + '_ {
+    let total = total_allocated(table);
+    table.iter().map(move |allocation| {
+        let share = pro_rata_share(amount, allocation.bps(), total)?;
+        Ok((allocation.recipient(), share))
+    })
+}
+
+/// Scales the table so it adds up to new_total, keeping the ratios. Rounding
+/// dust goes to the largest allocation (the last one on a tie), allocations
+/// can round down to 0.
+pub fn rescale<A: Allocation>(table: &mut [A], new_total: u64) -> Result<()> {
+    let old_total = total_allocated(table);
+
+    let mut distributed: u64 = 0;
+    for allocation in table.iter_mut() {
+        let bps = mul_div(allocation.bps(), new_total, old_total, Rounding::Down)?;
+        allocation.set_bps(bps);
+        distributed += bps;
+    }
+
+    if let Some(largest) = table.iter_mut().max_by_key(|allocation| allocation.bps()) {
+        let bps = largest.bps() + (new_total - distributed);
+        largest.set_bps(bps);
+    }
+
+    Ok(())
+}
+
+/// Running totals of a vault: paid in, credited to ledgers and withdrawn.
+/// What was paid but never credited is the rounding dust.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accumulator {
+    pub deposited: u64,
+    pub credited: u64,
+    pub withdrawn: u64,
+}
+
+impl Accumulator {
+    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        self.deposited = self.deposited.checked_add(amount).ok_or(Error::Overflow)?;
+        Ok(())
+    }
+
+    // Can't credit more than was deposited
+    pub fn credit(&mut self, amount: u64) -> Result<()> {
+        let credited = self.credited.checked_add(amount).ok_or(Error::Overflow)?;
+        if credited > self.deposited {
+            return Err(Error::InsufficientFunds);
+        }
+        self.credited = credited;
+        Ok(())
+    }
+
+    // Can't withdraw more than is owed
+    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        if amount > self.owed() {
+            return Err(Error::InsufficientFunds);
+        }
+        self.withdrawn += amount;
+        Ok(())
+    }
+
+    /// Credited to ledgers and not yet withdrawn
+    pub fn owed(&self) -> u64 {
+        self.credited - self.withdrawn
+    }
+
+    /// Deposited but never credited to anyone
+    pub fn retained(&self) -> u64 {
+        self.deposited - self.credited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Split(u8, u64);
+
+    impl Allocation for Split {
+        type Recipient = u8;
+
+        fn recipient(&self) -> u8 {
+            self.0
+        }
+
+        fn bps(&self) -> u64 {
+            self.1
+        }
+
+        fn set_bps(&mut self, bps: u64) {
+            self.1 = bps;
+        }
+    }
+
+    #[test]
+    fn mul_div_rounds_and_does_not_overflow() {
+        assert_eq!(mul_div(10, 1, 3, Rounding::Down), Ok(3));
+        assert_eq!(mul_div(10, 1, 3, Rounding::Up), Ok(4));
+        assert_eq!(mul_div(9, 1, 3, Rounding::Up), Ok(3));
+        assert_eq!(
+            mul_div(u64::MAX, 9999, ONE_HUNDRED_PERCENT, Rounding::Down),
+            Ok((u64::MAX as u128 * 9999 / 10000) as u64)
+        );
+        assert_eq!(
+            mul_div(u64::MAX, 2, 1, Rounding::Down),
+            Err(Error::Overflow)
+        );
+        assert_eq!(
+            mul_div(u64::MAX, u64::MAX, u64::MAX, Rounding::Up),
+            Ok(u64::MAX)
+        );
+        assert_eq!(mul_div(1, 1, 0, Rounding::Down), Err(Error::NoRecipients));
+    }
+
+    #[test]
+    fn stream_split_must_add_up() {
+        assert!(StreamSplit::new(8000, 2000).is_ok());
+        assert_eq!(StreamSplit::new(8000, 2001), Err(Error::InvalidPercentages));
+        assert_eq!(
+            StreamSplit::new(u64::MAX, 10001),
+            Err(Error::InvalidPercentages)
+        );
+    }
+
+    #[test]
+    fn stream_split_rounds_each_half_down() {
+        let split = StreamSplit::new(3333, 6667).unwrap();
+        let amounts = split.divide(10).unwrap();
+        assert_eq!(
+            amounts,
+            StreamAmounts {
+                artist_total: 3,
+                holder_total: 6,
+            }
+        );
+        assert_eq!(amounts.remainder(10), 1);
+    }
+
+    #[test]
+    fn first_mint_goes_to_artists() {
+        let split = StreamSplit::new(6000, 4000).unwrap();
+        assert_eq!(split.divide_mint(100, 0).unwrap().artist_total, 100);
+        assert_eq!(split.divide_mint(100, 1).unwrap().artist_total, 60);
+    }
+
+    #[test]
+    fn holders_get_equal_shares() {
+        assert_eq!(per_holder_share(100, 3), Ok(33));
+        assert_eq!(per_holder_share(100, 0), Err(Error::NoRecipients));
+    }
+
+    #[test]
+    fn split_table_validation() {
+        assert_eq!(
+            validate_split_table(&[Split(1, 7000), Split(2, 3000)], 10000),
+            Ok(())
+        );
+        assert_eq!(
+            validate_split_table(&[Split(1, 7000), Split(2, 2000)], 10000),
+            Err(Error::InvalidPercentages)
+        );
+        assert_eq!(
+            validate_split_table(&[Split(1, 5000), Split(1, 5000)], 10000),
+            Err(Error::DuplicateRecipient)
+        );
+        assert_eq!(
+            validate_split_table(&[Split(1, u64::MAX), Split(2, 1)], 0),
+            Err(Error::InvalidPercentages)
+        );
+    }
+
+    #[test]
+    fn split_shares_are_pro_rata_to_allocated() {
+        // 1000 bps unallocated reserve, shared pro-rata
+        let table = [Split(1, 6000), Split(2, 3000)];
+        let mut shares = split_shares(&table, 900);
+        assert_eq!(shares.next(), Some(Ok((1, 600))));
+        assert_eq!(shares.next(), Some(Ok((2, 300))));
+        assert_eq!(shares.next(), None);
+
+        let table = [Split(1, 7000), Split(2, 3000)];
+        let total: u64 = split_shares(&table, 1001).map(|s| s.unwrap().1).sum();
+        assert_eq!(total, 1000);
+    }
+
+    #[test]
+    fn rescale_keeps_ratios_and_total() {
+        let mut table = [Split(1, 3333), Split(2, 3333), Split(3, 3334)];
+        rescale(&mut table, 9000).unwrap();
+        assert_eq!(table, [Split(1, 2999), Split(2, 2999), Split(3, 3002)]);
+        assert_eq!(total_allocated(&table), 9000);
+
+        let mut table = [Split(1, 1), Split(2, 9999)];
+        rescale(&mut table, 5000).unwrap();
+        assert_eq!(table, [Split(1, 0), Split(2, 5000)]);
+    }
+
+    #[test]
+    fn accumulator_tracks_dust() {
+        let mut vault = Accumulator::default();
+        vault.deposit(100).unwrap();
+        vault.credit(99).unwrap();
+        assert_eq!(vault.credit(2), Err(Error::InsufficientFunds));
+
+        vault.withdraw(50).unwrap();
+        assert_eq!(vault.withdraw(50), Err(Error::InsufficientFunds));
+        assert_eq!(vault.owed(), 49);
+        assert_eq!(vault.retained(), 1);
+    }
+}
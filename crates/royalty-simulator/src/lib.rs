@@ -0,0 +1,350 @@
+//! Off-chain projection of nft-vault-prototype royalties
+//!
+//! [`Simulation`] replays mints, secondary sale royalties and licensing fees
+//! through the same `royalty-math` the program runs, so projected balances
+//! match what the ledgers would hold to the lamport. [`Scenario`] drives it
+//! from a file, period by period.
+
+pub mod scenario;
+
+pub use scenario::{HolderProjection, PeriodProjection, Projection, Scenario};
+
+use nft_vault_client::state::CollectionConfiguration;
+use royalty_math::{Accumulator, Allocation, Error, Result, StreamSplit, ONE_HUNDRED_PERCENT};
+use serde::{Deserialize, Serialize};
+
+/// Collection percentages, as in the on-chain `CollectionConfiguration`
+#[derive(Clone, Debug)]
+pub struct Collection {
+    pub mint: StreamSplit,
+    pub secondary: StreamSplit,
+    pub licensing: StreamSplit,
+    pub artist_reserve_percentage: u64,
+    pub artists: Vec<Artist>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Artist {
+    pub name: String,
+    pub percentage: u64,
+}
+
+impl Allocation for Artist {
+    type Recipient = String;
+
+    fn recipient(&self) -> String {
+        self.name.clone()
+    }
+
+    fn bps(&self) -> u64 {
+        self.percentage
+    }
+
+    fn set_bps(&mut self, bps: u64) {
+        self.percentage = bps;
+    }
+}
+
+impl Collection {
+    // Same checks as initialize_collection
+    pub fn validate(&self) -> Result<()> {
+        self.mint.validate()?;
+        self.secondary.validate()?;
+        self.licensing.validate()?;
+
+        let allocated = ONE_HUNDRED_PERCENT
+            .checked_sub(self.artist_reserve_percentage)
+            .ok_or(Error::InvalidPercentages)?;
+        royalty_math::validate_split_table(&self.artists, allocated)
+    }
+}
+
+// Artists are named by address
+impl From<&CollectionConfiguration> for Collection {
+    fn from(config: &CollectionConfiguration) -> Self {
+        Collection {
+            mint: StreamSplit {
+                artist: config.artist_mint_percentage,
+                holders: config.label_mint_percentage,
+            },
+            secondary: StreamSplit {
+                artist: config.artist_secondary_percentage,
+                holders: config.label_secondary_percentage,
+            },
+            licensing: StreamSplit {
+                artist: config.artist_licensing_percentage,
+                holders: config.label_licensing_percentage,
+            },
+            artist_reserve_percentage: config.artist_reserve_percentage,
+            artists: config
+                .artist_splits
+                .iter()
+                .map(|split| Artist {
+                    name: split.artist_address.to_string(),
+                    percentage: split.allocated_percentage,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Mint,
+    Secondary,
+    Licensing,
+}
+
+/// One distribution, mirrors the program's `RoyaltiesDistributed` event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Distribution {
+    pub stream: Stream,
+    pub amount: u64,
+    pub artist_total: u64,
+    pub holder_total: u64,
+    pub per_nft_share: u64,
+    pub nft_count: u64,
+}
+
+/**
+ * Collection state as the program would hold it
+ *
+ * Every NFT gets the same share of a distribution, so instead of a balance
+ * per NFT it keeps the running total of shares and where each NFT joined it.
+ */
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    collection: Collection,
+    artist_earnings: Vec<u64>,
+    holder_entries: Vec<u64>,
+    cumulative_share: u64,
+    secondary_pool: u64,
+    vault: Accumulator,
+}
+
+impl Simulation {
+    pub fn new(collection: Collection) -> Result<Self> {
+        collection.validate()?;
+
+        Ok(Simulation {
+            artist_earnings: vec![0; collection.artists.len()],
+            collection,
+            holder_entries: Vec::new(),
+            cumulative_share: 0,
+            secondary_pool: 0,
+            vault: Accumulator::default(),
+        })
+    }
+
+    /// add_nft, the price is shared with the NFTs minted before this one
+    pub fn mint(&mut self, price: u64) -> Result<Distribution> {
+        let nft_count = self.nft_count();
+        let amounts = self.collection.mint.divide_mint(price, nft_count)?;
+        let distribution = self.distribute(Stream::Mint, price, amounts, nft_count)?;
+
+        self.holder_entries.push(self.cumulative_share);
+
+        Ok(distribution)
+    }
+
+    /// Royalties paid into the secondary pool by marketplaces
+    pub fn deposit_secondary_royalties(&mut self, amount: u64) -> Result<()> {
+        self.secondary_pool = self
+            .secondary_pool
+            .checked_add(amount)
+            .ok_or(Error::Overflow)?;
+        Ok(())
+    }
+
+    /// distribute_secondary_pool, `None` while there are no NFTs to share
+    /// with and the pool stays put
+    pub fn distribute_secondary_pool(&mut self) -> Result<Option<Distribution>> {
+        let nft_count = self.nft_count();
+        if nft_count == 0 {
+            return Ok(None);
+        }
+
+        let amount = self.secondary_pool;
+        let amounts = self.collection.secondary.divide(amount)?;
+        self.secondary_pool = 0;
+
+        self.distribute(Stream::Secondary, amount, amounts, nft_count)
+            .map(Some)
+    }
+
+    /// pay_licensing_fee, `None` when the program would reject it because
+    /// there are no NFTs yet
+    pub fn pay_licensing_fee(&mut self, amount: u64) -> Result<Option<Distribution>> {
+        let nft_count = self.nft_count();
+        if nft_count == 0 {
+            return Ok(None);
+        }
+
+        let amounts = self.collection.licensing.divide(amount)?;
+        self.distribute(Stream::Licensing, amount, amounts, nft_count)
+            .map(Some)
+    }
+
+    fn distribute(
+        &mut self,
+        stream: Stream,
+        amount: u64,
+        amounts: royalty_math::StreamAmounts,
+        nft_count: u64,
+    ) -> Result<Distribution> {
+        self.vault.deposit(amount)?;
+
+        let shares = royalty_math::split_shares(&self.collection.artists, amounts.artist_total);
+        for (earnings, share) in self.artist_earnings.iter_mut().zip(shares) {
+            let (_, share) = share?;
+            *earnings += share;
+            self.vault.credit(share)?;
+        }
+
+        let mut per_nft_share = 0;
+        if nft_count > 0 {
+            per_nft_share = royalty_math::per_holder_share(amounts.holder_total, nft_count)?;
+            self.cumulative_share += per_nft_share;
+            self.vault.credit(per_nft_share * nft_count)?;
+        }
+
+        Ok(Distribution {
+            stream,
+            amount,
+            artist_total: amounts.artist_total,
+            holder_total: amounts.holder_total,
+            per_nft_share,
+            nft_count,
+        })
+    }
+
+    pub fn collection(&self) -> &Collection {
+        &self.collection
+    }
+
+    pub fn nft_count(&self) -> u64 {
+        self.holder_entries.len() as u64
+    }
+
+    /// Everything credited to each artist so far, in split order
+    pub fn artist_earnings(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.collection
+            .artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .zip(self.artist_earnings.iter().copied())
+    }
+
+    /// Everything credited to the nth NFT minted (from 0) so far
+    pub fn holder_earnings(&self, nft: usize) -> Option<u64> {
+        self.holder_entries
+            .get(nft)
+            .map(|entry| self.cumulative_share - entry)
+    }
+
+    /// Running share credited to any NFT held since the first mint
+    pub fn cumulative_share(&self) -> u64 {
+        self.cumulative_share
+    }
+
+    pub fn secondary_pool(&self) -> u64 {
+        self.secondary_pool
+    }
+
+    /// Vault totals, `retained()` is the rounding dust
+    pub fn vault(&self) -> &Accumulator {
+        &self.vault
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection() -> Collection {
+        Collection {
+            mint: StreamSplit::new(8000, 2000).unwrap(),
+            secondary: StreamSplit::new(5000, 5000).unwrap(),
+            licensing: StreamSplit::new(6000, 4000).unwrap(),
+            artist_reserve_percentage: 0,
+            artists: vec![
+                Artist {
+                    name: "alice".to_string(),
+                    percentage: 7000,
+                },
+                Artist {
+                    name: "bob".to_string(),
+                    percentage: 3000,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_collection() {
+        let mut invalid = collection();
+        invalid.artists[1].percentage = 2000;
+        assert_eq!(
+            Simulation::new(invalid).unwrap_err(),
+            Error::InvalidPercentages
+        );
+
+        let mut reserved = collection();
+        reserved.artist_reserve_percentage = 1000;
+        reserved.artists[1].percentage = 2000;
+        assert!(Simulation::new(reserved).is_ok());
+    }
+
+    #[test]
+    fn first_mint_goes_to_artists() {
+        let mut simulation = Simulation::new(collection()).unwrap();
+        let distribution = simulation.mint(1000).unwrap();
+
+        assert_eq!(distribution.artist_total, 1000);
+        assert_eq!(distribution.nft_count, 0);
+        assert_eq!(
+            simulation.artist_earnings().collect::<Vec<_>>(),
+            [("alice", 700), ("bob", 300)]
+        );
+        assert_eq!(simulation.holder_earnings(0), Some(0));
+    }
+
+    #[test]
+    fn holders_share_later_distributions() {
+        let mut simulation = Simulation::new(collection()).unwrap();
+        simulation.mint(1000).unwrap();
+        // 200 to the first NFT
+        simulation.mint(1000).unwrap();
+        // 400 shared by both NFTs
+        simulation.pay_licensing_fee(1000).unwrap();
+        // 200 shared by both NFTs
+        simulation.mint(1000).unwrap();
+        // 1001 * 50% rounds down to 500, shared by 3 NFTs as 166 each
+        simulation.deposit_secondary_royalties(1001).unwrap();
+        simulation.distribute_secondary_pool().unwrap();
+
+        assert_eq!(simulation.holder_earnings(0), Some(200 + 200 + 100 + 166));
+        assert_eq!(simulation.holder_earnings(1), Some(200 + 100 + 166));
+        assert_eq!(simulation.holder_earnings(2), Some(166));
+        assert_eq!(simulation.holder_earnings(3), None);
+
+        assert_eq!(
+            simulation.artist_earnings().nth(1),
+            Some(("bob", 300 + 240 + 180 + 240 + 150))
+        );
+        assert_eq!(simulation.vault().deposited, 5001);
+        assert_eq!(simulation.vault().retained(), 3);
+    }
+
+    #[test]
+    fn nothing_is_distributed_without_holders() {
+        let mut simulation = Simulation::new(collection()).unwrap();
+        simulation.deposit_secondary_royalties(500).unwrap();
+
+        assert_eq!(simulation.pay_licensing_fee(1000), Ok(None));
+        assert_eq!(simulation.distribute_secondary_pool(), Ok(None));
+        assert_eq!(simulation.secondary_pool(), 500);
+        assert_eq!(simulation.vault().deposited, 0);
+    }
+}
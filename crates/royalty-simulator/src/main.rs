@@ -0,0 +1,157 @@
+//! `royalty-sim` projects a collection's earnings from a scenario file
+//!
+//! Prints a table per period with each artist's running earnings, and one
+//! per mint period with what its NFTs earned by the end of the scenario.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::{ArgEnum, Parser};
+use royalty_simulator::{Projection, Scenario};
+
+#[derive(Parser)]
+#[clap(
+    name = "royalty-sim",
+    version,
+    about = "Project royalties of an nft-vault collection"
+)]
+struct Cli {
+    /// JSON scenario file, amounts in lamports
+    scenario: PathBuf,
+
+    #[clap(long, arg_enum, default_value = "table")]
+    format: OutputFormat,
+
+    /// List every NFT instead of grouping them by mint period
+    #[clap(long)]
+    holders: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let scenario = Scenario::read(&cli.scenario)?;
+    let projection = scenario
+        .project()
+        .map_err(|error| anyhow!("projection failed: {:?}", error))?;
+
+    match cli.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&projection)?),
+        OutputFormat::Table => {
+            print_periods(&projection);
+            println!();
+            if cli.holders {
+                print_holders(&projection);
+            } else {
+                print_mint_periods(&projection);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_periods(projection: &Projection) {
+    let mut headers: Vec<String> = [
+        "period",
+        "nfts",
+        "deposited",
+        "per nft",
+        "pool",
+        "rejected",
+        "retained",
+    ]
+    .iter()
+    .map(|header| header.to_string())
+    .collect();
+    headers.extend(projection.artists.iter().cloned());
+
+    let rows = projection
+        .periods
+        .iter()
+        .map(|period| {
+            let mut row = vec![
+                period.period.to_string(),
+                period.nft_count.to_string(),
+                period.deposited.to_string(),
+                period.per_nft_share.to_string(),
+                period.secondary_pool.to_string(),
+                period.rejected_licensing.to_string(),
+                period.retained.to_string(),
+            ];
+            row.extend(period.artist_earnings.iter().map(u64::to_string));
+            row
+        })
+        .collect();
+
+    print_table(&headers, rows);
+}
+
+fn print_holders(projection: &Projection) {
+    let rows = projection
+        .holders
+        .iter()
+        .map(|holder| {
+            vec![
+                holder.nft.to_string(),
+                holder.minted_in.to_string(),
+                holder.earned.to_string(),
+            ]
+        })
+        .collect();
+
+    print_table(&["nft", "minted in", "earned"], rows);
+}
+
+// NFTs minted early in a period earn from the later mints, hence the range
+fn print_mint_periods(projection: &Projection) {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut holders = projection.holders.iter().peekable();
+
+    while let Some(first) = holders.next() {
+        let (mut count, mut min, mut max) = (1, first.earned, first.earned);
+        while let Some(holder) = holders.next_if(|holder| holder.minted_in == first.minted_in) {
+            count += 1;
+            min = min.min(holder.earned);
+            max = max.max(holder.earned);
+        }
+
+        rows.push(vec![
+            first.minted_in.to_string(),
+            count.to_string(),
+            min.to_string(),
+            max.to_string(),
+        ]);
+    }
+
+    print_table(&["minted in", "nfts", "min earned", "max earned"], rows);
+}
+
+fn print_table<H: AsRef<str>>(headers: &[H], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.as_ref().len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  "));
+    };
+
+    line(headers.iter().map(AsRef::as_ref).collect());
+    for row in &rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
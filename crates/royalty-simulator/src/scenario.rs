@@ -0,0 +1,313 @@
+//! Scenario files and the projections run from them
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use royalty_math::{Result, StreamSplit, ONE_HUNDRED_PERCENT};
+use serde::{Deserialize, Serialize};
+
+use crate::{Artist, Collection, Simulation};
+
+/**
+ * Launch scenario, amounts are in lamports and percentages in basis points
+ *
+ * {
+ *   "artist_mint_percentage": 8000, "label_mint_percentage": 2000,
+ *   "artist_secondary_percentage": 5000, "label_secondary_percentage": 5000,
+ *   "artist_licensing_percentage": 6000, "label_licensing_percentage": 4000,
+ *   "artists": [{ "name": "alice", "percentage": 10000 }],
+ *   "periods": 12,
+ *   "secondary_royalty_percentage": 500,
+ *   "mints": [{ "period": 1, "count": 100, "price": 1000000000 }],
+ *   "secondary_sales": [{ "period": 2, "volume": 50000000000 }],
+ *   "licensing": [{ "period": 3, "amount": 2000000000 }]
+ * }
+ *
+ * Periods are numbered from 1. In each period the mints go first, then the
+ * secondary pool is distributed, then the licensing fees are paid.
+ * `secondary_royalty_percentage` is the part of the sale volume marketplaces
+ * pay into the secondary pool.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+    #[serde(default)]
+    pub artist_reserve_percentage: u64,
+    pub artists: Vec<Artist>,
+    pub periods: u32,
+    #[serde(default)]
+    pub secondary_royalty_percentage: u64,
+    #[serde(default)]
+    pub mints: Vec<MintBatch>,
+    #[serde(default)]
+    pub secondary_sales: Vec<SecondarySales>,
+    #[serde(default)]
+    pub licensing: Vec<LicensingDeal>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MintBatch {
+    pub period: u32,
+    pub count: u64,
+    pub price: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecondarySales {
+    pub period: u32,
+    pub volume: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LicensingDeal {
+    pub period: u32,
+    pub amount: u64,
+}
+
+/// Earnings over time, artist amounts are in split order
+#[derive(Debug, Serialize)]
+pub struct Projection {
+    pub artists: Vec<String>,
+    pub periods: Vec<PeriodProjection>,
+    pub holders: Vec<HolderProjection>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodProjection {
+    pub period: u32,
+    pub nft_count: u64,
+    // Paid into the vault during the period
+    pub deposited: u64,
+    // Credited to each artist up to the end of the period
+    pub artist_earnings: Vec<u64>,
+    // Credited during the period to an NFT held for all of it
+    pub per_nft_share: u64,
+    // Waiting in the secondary pool for the first NFT
+    pub secondary_pool: u64,
+    // Licensing fees the program would reject, no NFTs yet
+    pub rejected_licensing: u64,
+    // Rounding dust kept by the vault up to the end of the period
+    pub retained: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HolderProjection {
+    // Position in the mint order, from 1
+    pub nft: u64,
+    pub minted_in: u32,
+    pub earned: u64,
+}
+
+impl Scenario {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading scenario {}", path.display()))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let scenario: Scenario = serde_json::from_str(contents).context("parsing scenario")?;
+
+        scenario
+            .collection()
+            .validate()
+            .map_err(|error| anyhow!("invalid collection percentages: {:?}", error))?;
+
+        if scenario.periods == 0 {
+            bail!("scenario needs at least one period");
+        }
+        if scenario.secondary_royalty_percentage > ONE_HUNDRED_PERCENT {
+            bail!(
+                "secondary royalty percentage {} is over 10000",
+                scenario.secondary_royalty_percentage
+            );
+        }
+
+        let periods = scenario
+            .mints
+            .iter()
+            .map(|mint| mint.period)
+            .chain(scenario.secondary_sales.iter().map(|sales| sales.period))
+            .chain(scenario.licensing.iter().map(|deal| deal.period));
+        for period in periods {
+            if period == 0 || period > scenario.periods {
+                bail!("period {} is outside of 1..={}", period, scenario.periods);
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    pub fn collection(&self) -> Collection {
+        Collection {
+            mint: StreamSplit {
+                artist: self.artist_mint_percentage,
+                holders: self.label_mint_percentage,
+            },
+            secondary: StreamSplit {
+                artist: self.artist_secondary_percentage,
+                holders: self.label_secondary_percentage,
+            },
+            licensing: StreamSplit {
+                artist: self.artist_licensing_percentage,
+                holders: self.label_licensing_percentage,
+            },
+            artist_reserve_percentage: self.artist_reserve_percentage,
+            artists: self.artists.clone(),
+        }
+    }
+
+    pub fn project(&self) -> Result<Projection> {
+        let mut simulation = Simulation::new(self.collection())?;
+        let mut periods = Vec::new();
+        let mut minted_in = Vec::new();
+
+        for period in 1..=self.periods {
+            let deposited_before = simulation.vault().deposited;
+            let share_before = simulation.cumulative_share();
+            let mut rejected_licensing = 0;
+
+            for mint in self.mints.iter().filter(|mint| mint.period == period) {
+                for _ in 0..mint.count {
+                    simulation.mint(mint.price)?;
+                    minted_in.push(period);
+                }
+            }
+
+            for sales in self
+                .secondary_sales
+                .iter()
+                .filter(|sales| sales.period == period)
+            {
+                let royalties =
+                    royalty_math::bps_of(sales.volume, self.secondary_royalty_percentage)?;
+                simulation.deposit_secondary_royalties(royalties)?;
+            }
+            simulation.distribute_secondary_pool()?;
+
+            for deal in self.licensing.iter().filter(|deal| deal.period == period) {
+                if simulation.pay_licensing_fee(deal.amount)?.is_none() {
+                    rejected_licensing += deal.amount;
+                }
+            }
+
+            periods.push(PeriodProjection {
+                period,
+                nft_count: simulation.nft_count(),
+                deposited: simulation.vault().deposited - deposited_before,
+                artist_earnings: simulation
+                    .artist_earnings()
+                    .map(|(_, earned)| earned)
+                    .collect(),
+                per_nft_share: simulation.cumulative_share() - share_before,
+                secondary_pool: simulation.secondary_pool(),
+                rejected_licensing,
+                retained: simulation.vault().retained(),
+            });
+        }
+
+        let holders = minted_in
+            .iter()
+            .enumerate()
+            .map(|(index, &minted_in)| HolderProjection {
+                nft: index as u64 + 1,
+                minted_in,
+                earned: simulation.holder_earnings(index).unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(Projection {
+            artists: self
+                .artists
+                .iter()
+                .map(|artist| artist.name.clone())
+                .collect(),
+            periods,
+            holders,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(events: &str) -> anyhow::Result<Scenario> {
+        Scenario::parse(&format!(
+            r#"{{
+                "artist_mint_percentage": 8000, "label_mint_percentage": 2000,
+                "artist_secondary_percentage": 5000, "label_secondary_percentage": 5000,
+                "artist_licensing_percentage": 6000, "label_licensing_percentage": 4000,
+                "artists": [
+                    {{ "name": "alice", "percentage": 7000 }},
+                    {{ "name": "bob", "percentage": 3000 }}
+                ],
+                "periods": 3,
+                "secondary_royalty_percentage": 1000,
+                {}
+            }}"#,
+            events
+        ))
+    }
+
+    #[test]
+    fn projects_earnings_per_period() {
+        let projection = scenario(
+            r#"
+            "mints": [{ "period": 2, "count": 2, "price": 1000 }, { "period": 3, "count": 1, "price": 1000 }],
+            "secondary_sales": [{ "period": 1, "volume": 10000 }],
+            "licensing": [{ "period": 1, "amount": 500 }, { "period": 3, "amount": 1000 }]
+            "#,
+        )
+        .unwrap()
+        .project()
+        .unwrap();
+
+        let first = &projection.periods[0];
+        assert_eq!(first.deposited, 0);
+        assert_eq!(first.secondary_pool, 1000);
+        assert_eq!(first.rejected_licensing, 500);
+
+        // Both mints, then the pool: 200 to the first NFT and 250 each
+        let second = &projection.periods[1];
+        assert_eq!(second.nft_count, 2);
+        assert_eq!(second.deposited, 3000);
+        assert_eq!(second.per_nft_share, 200 + 250);
+        assert_eq!(second.artist_earnings, [700 + 560 + 350, 300 + 240 + 150]);
+
+        // Third mint shares 200 with two NFTs, licensing 400 with three
+        let third = &projection.periods[2];
+        assert_eq!(third.per_nft_share, 100 + 133);
+        assert_eq!(third.retained, 1);
+
+        let earned: Vec<_> = projection.holders.iter().map(|h| h.earned).collect();
+        assert_eq!(earned, [200 + 250 + 233, 250 + 233, 133]);
+        assert_eq!(projection.holders[2].minted_in, 3);
+    }
+
+    #[test]
+    fn rejects_invalid_scenarios() {
+        assert!(scenario(r#""mints": [{ "period": 4, "count": 1, "price": 1 }]"#).is_err());
+        assert!(scenario(r#""mints": [{ "period": 0, "count": 1, "price": 1 }]"#).is_err());
+        assert!(scenario(r#""unknown": []"#).is_err());
+        assert!(Scenario::parse(
+            r#"{
+                "artist_mint_percentage": 8000, "label_mint_percentage": 2001,
+                "artist_secondary_percentage": 5000, "label_secondary_percentage": 5000,
+                "artist_licensing_percentage": 6000, "label_licensing_percentage": 4000,
+                "artists": [{ "name": "alice", "percentage": 10000 }],
+                "periods": 1
+            }"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Events emitted on every state change, so indexers can rebuild
+//! the history of a collection without diffing the ledgers
+
+use crate::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum RoyaltyStream {
+    Mint,
+    Secondary,
+    Licensing,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct CollectionInitialized {
+    pub collection_authority: Pubkey,
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+    pub artist_splits: Vec<ArtistPercentage>,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct NftAdded {
+    pub nft_address: Pubkey,
+    pub amount_paid: u64,
+    pub nft_count: u64,
+}
+
+/**
+ * One payment split between the artist and NFT ledgers
+ *
+ * per_nft_share is what each NFT in the ledger was credited. Licensing
+ * payments for named tracks credit part of holder_total to those NFTs
+ * only, see LicensedTracksCredited.
+ */
+#[event]
+#[derive(Clone, Debug)]
+pub struct RoyaltiesDistributed {
+    pub stream: RoyaltyStream,
+    pub amount: u64,
+    pub artist_total: u64,
+    pub holder_total: u64,
+    pub per_nft_share: u64,
+    pub nft_count: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct MemberWithdrawn {
+    pub nft_address: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct ArtistWithdrawn {
+    pub artist_address: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct CollectionAuthorityTransferred {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub artist_authorizer: Pubkey,
+}
+
+// Emitted whenever percentages or artist splits change after initialization
+#[event]
+#[derive(Clone, Debug)]
+pub struct CollectionConfigUpdated {
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+    pub artist_splits: Vec<ArtistPercentage>,
+    pub artist_reserve_percentage: u64,
+}
+
+impl From<&CollectionConfiguration> for CollectionConfigUpdated {
+    fn from(collection_config: &CollectionConfiguration) -> Self {
+        CollectionConfigUpdated {
+            artist_mint_percentage: collection_config.artist_mint_percentage,
+            label_mint_percentage: collection_config.label_mint_percentage,
+            artist_secondary_percentage: collection_config.artist_secondary_percentage,
+            label_secondary_percentage: collection_config.label_secondary_percentage,
+            artist_licensing_percentage: collection_config.artist_licensing_percentage,
+            label_licensing_percentage: collection_config.label_licensing_percentage,
+            artist_splits: collection_config.artist_splits.clone(),
+            artist_reserve_percentage: collection_config.artist_reserve_percentage,
+        }
+    }
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicenseCreated {
+    pub license: Pubkey,
+    pub licensee: Pubkey,
+    pub token_mint: Pubkey,
+    pub content_hash: [u8; 32],
+    pub fee: u64,
+    pub usage_type: UsageType,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub grace_slots: u64,
+    pub volume: u64,
+    pub licensed_nfts: Vec<Pubkey>,
+    pub track_share_percentage: u64,
+    pub reporter: Pubkey,
+    pub per_use_rate: u64,
+}
+
+/**
+ * Part of the holder share of a licensing payment split between the NFTs
+ * the license names, emitted after its RoyaltiesDistributed
+ *
+ * per_nft_share is what each of nfts was credited, on top of the share
+ * every NFT got
+ */
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicensedTracksCredited {
+    pub license: Pubkey,
+    pub nfts: Vec<Pubkey>,
+    pub amount: u64,
+    pub per_nft_share: u64,
+}
+
+// Emitted with the Licensing RoyaltiesDistributed of the same payment
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicenseFeePaid {
+    pub license: Pubkey,
+    pub licensee: Pubkey,
+    pub amount: u64,
+    pub paid_to_date: u64,
+    pub fee_tier: FeeTier, // tier the payment was priced at
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicenseRenewed {
+    pub license: Pubkey,
+    pub licensee: Pubkey,
+    pub fee: u64,
+    pub end_slot: u64, // end of the new term
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicenseLapsed {
+    pub license: Pubkey,
+    pub licensee: Pubkey,
+    pub end_slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct FeeScheduleUpdated {
+    pub currency: FeeCurrency,
+    pub hold_slots: u64,
+    pub tiers: Vec<FeeTier>,
+}
+
+impl From<&FeeSchedule> for FeeScheduleUpdated {
+    fn from(fee_schedule: &FeeSchedule) -> Self {
+        FeeScheduleUpdated {
+            currency: fee_schedule.currency,
+            hold_slots: fee_schedule.hold_slots,
+            tiers: fee_schedule.tiers.clone(),
+        }
+    }
+}
+
+// Emitted after the LicenseFeePaid of a payment made in USD
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicenseFeeConverted {
+    pub license: Pubkey,
+    pub usd_cents: u64,
+    pub lamports: u64,
+    pub price: i64, // SOL/USD price * 10^expo the payment converted at
+    pub expo: i32,
+}
+
+// Emitted after the LicenseFeePaid of a payment the fee schedule holds
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicensePaymentHeld {
+    pub license: Pubkey,
+    pub amount: u64,
+    pub release_slot: u64, // refundable until, claimable from
+}
+
+// Emitted after the RoyaltiesDistributed splitting the payment
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicensePaymentReleased {
+    pub license: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct LicensePaymentRefunded {
+    pub license: Pubkey,
+    pub licensee: Pubkey,
+    pub amount: u64,
+    pub paid_to_date: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct UsageReported {
+    pub license: Pubkey,
+    pub usage_report: Pubkey,
+    pub period: u64,
+    pub tracks: Vec<TrackUsage>,
+    pub total_uses: u64,
+    pub fee: u64,
+    pub outstanding_fee: u64, // owed on the license after this report
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct SecondarySaleRecorded {
+    pub nft: Pubkey,
+    pub sale: Pubkey,
+    pub price: u64,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub royalty_amount: u64,
+    pub reporter: Pubkey,
+    pub volume: u64, // the NFT's sale volume including this sale
+}
@@ -0,0 +1,223 @@
+//! Licensing fee schedule
+//!
+//! The collection authority prices licenses by usage type, optionally in
+//! volume tiers. Every usage type has a base price from volume 0, a tier
+//! takes over from its min_volume on. Licensing payments must cover the
+//! price of the tier the license's volume falls in, and the tier is
+//! recorded on the license.
+//!
+//! Schedules are priced in lamports, or in USD cents converted at a SOL/USD
+//! price feed. USD schedules need the feed as the first remaining account
+//! of every licensing payment.
+//!
+//! hold_slots, when set, holds licensing payments for that many slots
+//! before they reach the ledgers, see licensing.rs.
+
+use crate::*;
+
+// Most tiers a schedule can hold, across all usage types
+pub const MAX_FEE_TIERS: usize = 16;
+
+pub fn initialize_fee_schedule(
+    ctx: Context<InitializeFeeSchedule>,
+    tiers: Vec<FeeTier>,
+) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    let fee_schedule = &mut ctx.accounts.fee_schedule;
+    fee_schedule.version = ACCOUNT_VERSION;
+    fee_schedule.currency = FeeCurrency::Lamports;
+    fee_schedule.hold_slots = 0;
+    fee_schedule.set_tiers(tiers)?;
+
+    emit!(FeeScheduleUpdated::from(&**fee_schedule));
+
+    Ok(())
+}
+
+pub fn update_fee_schedule(ctx: Context<UpdateFeeSchedule>, tiers: Vec<FeeTier>) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    ctx.accounts.fee_schedule.set_tiers(tiers)?;
+
+    emit!(FeeScheduleUpdated::from(&*ctx.accounts.fee_schedule));
+
+    Ok(())
+}
+
+// Tiers are replaced along with the currency, old prices mean nothing in the new one
+pub fn set_fee_currency(
+    ctx: Context<UpdateFeeSchedule>,
+    currency: FeeCurrency,
+    tiers: Vec<FeeTier>,
+) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    if let FeeCurrency::Usd(config) = currency {
+        if config.max_confidence_bps > ONE_HUNDRED_PERCENT {
+            return Err(ErrorCode::InvalidFeeSchedule.into());
+        }
+    }
+
+    ctx.accounts.fee_schedule.set_tiers(tiers)?;
+    ctx.accounts.fee_schedule.currency = currency;
+
+    emit!(FeeScheduleUpdated::from(&*ctx.accounts.fee_schedule));
+
+    Ok(())
+}
+
+// Payments made before keep the release slot they were held until
+pub fn set_payment_hold(ctx: Context<UpdateFeeSchedule>, hold_slots: u64) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    ctx.accounts.fee_schedule.hold_slots = hold_slots;
+
+    emit!(FeeScheduleUpdated::from(&*ctx.accounts.fee_schedule));
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeSchedule<'info> {
+    #[account(init, payer = payer, space = FeeSchedule::SIZE, seeds = [b"fee-schedule"], bump)]
+    pub fee_schedule: Account<'info, FeeSchedule>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    pub collection_authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeSchedule<'info> {
+    #[account(mut, seeds = [b"fee-schedule"], bump)]
+    pub fee_schedule: Account<'info, FeeSchedule>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    pub collection_authority: Signer<'info>,
+}
+
+#[account]
+pub struct FeeSchedule {
+    pub version: u8,
+    pub currency: FeeCurrency, // tier prices are in this currency
+    pub hold_slots: u64,       // licensing payments are refundable this long
+    pub tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    // discriminator + version + currency + hold slots + vec prefix + tiers
+    pub const SIZE: usize = 8 + 1 + FeeCurrency::SIZE + 8 + 4 + MAX_FEE_TIERS * FeeTier::SIZE;
+
+    /**
+     * The tier a license of this usage type and volume is priced at, the
+     * one with the highest min_volume not above volume
+     */
+    pub fn tier(&self, usage_type: UsageType, volume: u64) -> Result<FeeTier> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.usage_type == usage_type && tier.min_volume <= volume)
+            .max_by_key(|tier| tier.min_volume)
+            .copied()
+            .ok_or_else(|| ErrorCode::InvalidFeeSchedule.into())
+    }
+
+    pub(crate) fn set_tiers(&mut self, tiers: Vec<FeeTier>) -> Result<()> {
+        verify_fee_tiers(&tiers)?;
+        self.tiers = tiers;
+
+        Ok(())
+    }
+
+    /**
+     * A price in the schedule's currency, in lamports. USD prices convert
+     * at the price feed, which must be the one the schedule names.
+     */
+    pub fn price_in_lamports(
+        &self,
+        price: u64,
+        price_feed: Option<&AccountInfo>,
+        slot: u64,
+    ) -> Result<u64> {
+        match self.currency {
+            FeeCurrency::Lamports => Ok(price),
+            FeeCurrency::Usd(config) => config.price(price_feed, slot)?.usd_to_lamports(price),
+        }
+    }
+}
+
+// Tiers of any fee schedule, checked before they are set or proposed
+pub(crate) fn verify_fee_tiers(tiers: &[FeeTier]) -> Result<()> {
+    if tiers.len() > MAX_FEE_TIERS {
+        return Err(ErrorCode::InvalidFeeSchedule.into());
+    }
+
+    // Every usage type needs a base price
+    for usage_type in UsageType::ALL {
+        if !tiers
+            .iter()
+            .any(|tier| tier.usage_type == usage_type && tier.min_volume == 0)
+        {
+            return Err(ErrorCode::InvalidFeeSchedule.into());
+        }
+    }
+
+    // and a volume can only fall in one tier
+    for (i, tier) in tiers.iter().enumerate() {
+        if tiers[i + 1..]
+            .iter()
+            .any(|other| other.usage_type == tier.usage_type && other.min_volume == tier.min_volume)
+        {
+            return Err(ErrorCode::InvalidFeeSchedule.into());
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FeeCurrency {
+    Lamports,
+    Usd(PriceFeedConfig), // prices in USD cents
+}
+
+impl FeeCurrency {
+    // variant + price feed config
+    pub const SIZE: usize = 1 + PriceFeedConfig::SIZE;
+}
+
+/// Price of a license of usage_type for min_volume and up, in the unit of
+/// the schedule's currency: lamports, or USD cents under `FeeCurrency::Usd`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FeeTier {
+    pub usage_type: UsageType,
+    pub min_volume: u64,
+    pub price: u64,
+}
+
+impl FeeTier {
+    // usage type + min volume + price
+    pub const SIZE: usize = 1 + 8 + 8;
+}
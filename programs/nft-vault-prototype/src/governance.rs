@@ -0,0 +1,366 @@
+//! NFT holder governance
+//!
+//! Holders registered in the NFT Balance Ledger vote on proposals that
+//! change holder-facing percentages or the tiers of the license fee
+//! schedule. One NFT is one vote, and only NFTs in the ledger when the
+//! proposal is created can vote on it.
+//!
+//! Each artist percentage is the remainder of its label percentage, so a
+//! proposal that changes them also needs every artist to sign its
+//! execution, as config updates do.
+
+use crate::*;
+
+pub fn initialize_holder_governance(
+    ctx: Context<InitializeHolderGovernance>,
+    quorum_percentage: u64,
+    voting_period: i64,
+) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    if quorum_percentage == 0 || quorum_percentage > ONE_HUNDRED_PERCENT || voting_period <= 0 {
+        return Err(ErrorCode::InvalidGovernanceConfig.into());
+    }
+
+    let holder_governance = &mut ctx.accounts.holder_governance;
+    holder_governance.version = ACCOUNT_VERSION;
+    holder_governance.quorum_percentage = quorum_percentage;
+    holder_governance.voting_period = voting_period;
+    holder_governance.proposal_count = 0;
+
+    Ok(())
+}
+
+pub fn create_holder_proposal(
+    ctx: Context<CreateHolderProposal>,
+    action: GovernanceAction,
+) -> ProgramResult {
+    action.verify()?;
+
+    // Only holders of an NFT in the ledger can create proposals
+    verify_nft_holder(
+        ctx.accounts.nft.key(),
+        &ctx.accounts.nft_associated_account,
+        ctx.accounts.proposer.key(),
+    )?;
+    ctx.accounts
+        .nft_balance_ledger
+        .snapshot_position(ctx.accounts.nft.key())?;
+
+    let holder_governance = &mut ctx.accounts.holder_governance;
+    let proposal = &mut ctx.accounts.holder_proposal;
+
+    proposal.version = ACCOUNT_VERSION;
+    proposal.id = holder_governance.proposal_count;
+    proposal.proposer = ctx.accounts.proposer.key();
+    proposal.action = action;
+
+    // Snapshot the voters and the voting rules at creation
+    proposal.snapshot_size = ctx.accounts.nft_balance_ledger.size;
+    proposal.votes = vec![0; HolderProposal::votes_len(proposal.snapshot_size)];
+    proposal.quorum_percentage = holder_governance.quorum_percentage;
+    proposal.voting_ends_at = Clock::get()?.unix_timestamp + holder_governance.voting_period;
+
+    holder_governance.proposal_count += 1;
+
+    Ok(())
+}
+
+pub fn cast_holder_vote(ctx: Context<CastHolderVote>, approve: bool) -> ProgramResult {
+    let proposal = &mut ctx.accounts.holder_proposal;
+
+    if Clock::get()?.unix_timestamp >= proposal.voting_ends_at {
+        return Err(ErrorCode::VotingPeriodEnded.into());
+    }
+
+    verify_nft_holder(
+        ctx.accounts.nft.key(),
+        &ctx.accounts.nft_associated_account,
+        ctx.accounts.holder.key(),
+    )?;
+
+    // NFTs added after the proposal was created can't vote
+    let position = ctx
+        .accounts
+        .nft_balance_ledger
+        .snapshot_position(ctx.accounts.nft.key())?;
+    if position as u64 >= proposal.snapshot_size {
+        return Err(ErrorCode::NftNotInSnapshot.into());
+    }
+
+    // One bit per NFT in the snapshot, by ledger position
+    let (byte, bit) = (position / 8, 1 << (position % 8));
+    if proposal.votes[byte] & bit != 0 {
+        return Err(ErrorCode::NftAlreadyVoted.into());
+    }
+
+    proposal.votes[byte] |= bit;
+    if approve {
+        proposal.yes_votes += 1;
+    } else {
+        proposal.no_votes += 1;
+    }
+
+    Ok(())
+}
+
+/**
+ * Applies a proposal holders passed once voting is over. When it changes
+ * the artist percentages every artist must sign (passed as remaining
+ * accounts). Fee tiers replace the fee schedule's, in its currency.
+ */
+pub fn execute_holder_proposal<'info>(
+    ctx: Context<'_, '_, '_, 'info, ExecuteHolderProposal<'info>>,
+) -> ProgramResult {
+    let proposal = &mut ctx.accounts.holder_proposal;
+
+    if proposal.executed {
+        return Err(ErrorCode::ProposalAlreadyExecuted.into());
+    }
+
+    if Clock::get()?.unix_timestamp < proposal.voting_ends_at {
+        return Err(ErrorCode::VotingPeriodActive.into());
+    }
+
+    let total_votes = proposal.yes_votes + proposal.no_votes;
+    if total_votes * ONE_HUNDRED_PERCENT < proposal.quorum_percentage * proposal.snapshot_size {
+        return Err(ErrorCode::QuorumNotReached.into());
+    }
+
+    if proposal.yes_votes <= proposal.no_votes {
+        return Err(ErrorCode::ProposalRejected.into());
+    }
+
+    match &proposal.action {
+        GovernanceAction::UpdateHolderPercentages { .. } => {
+            let collection_config = &mut ctx.accounts.collection_config;
+            if proposal
+                .action
+                .changes_artist_percentages(collection_config)
+            {
+                verify_artist_consensus(
+                    &collection_config.artist_splits,
+                    ctx.remaining_accounts,
+                    None,
+                )?;
+            }
+
+            proposal.action.apply(collection_config);
+
+            emit!(CollectionConfigUpdated::from(
+                &*ctx.accounts.collection_config
+            ));
+        }
+        GovernanceAction::SetFeeTiers { tiers } => {
+            let mut fee_schedule: Account<FeeSchedule> =
+                Account::try_from(&ctx.accounts.fee_schedule)?;
+            fee_schedule.set_tiers(tiers.clone())?;
+            fee_schedule.exit(ctx.program_id)?;
+
+            emit!(FeeScheduleUpdated::from(&*fee_schedule));
+        }
+    }
+
+    proposal.executed = true;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeHolderGovernance<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = HolderGovernance::SIZE,
+        seeds = [b"holder-governance"],
+        bump
+    )]
+    pub holder_governance: Account<'info, HolderGovernance>,
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    pub collection_authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(action: GovernanceAction)]
+pub struct CreateHolderProposal<'info> {
+    #[account(mut, seeds = [b"holder-governance"], bump)]
+    pub holder_governance: Account<'info, HolderGovernance>,
+    #[account(
+        init,
+        payer = proposer,
+        space = HolderProposal::space(&action, nft_balance_ledger.size),
+        seeds = [b"holder-proposal", holder_governance.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub holder_proposal: Account<'info, HolderProposal>,
+    #[account(seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    pub nft: Account<'info, Mint>,
+    pub nft_associated_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CastHolderVote<'info> {
+    #[account(mut, seeds = [b"holder-proposal", holder_proposal.id.to_le_bytes().as_ref()], bump)]
+    pub holder_proposal: Account<'info, HolderProposal>,
+    #[account(seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    pub nft: Account<'info, Mint>,
+    pub nft_associated_account: Account<'info, TokenAccount>,
+    pub holder: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteHolderProposal<'info> {
+    #[account(mut, seeds = [b"holder-proposal", holder_proposal.id.to_le_bytes().as_ref()], bump)]
+    pub holder_proposal: Account<'info, HolderProposal>,
+    #[account(mut, seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    // Only read by fee tier proposals, collections without a fee schedule
+    // can still execute the others
+    #[account(mut, seeds = [b"fee-schedule"], bump)]
+    pub fee_schedule: UncheckedAccount<'info>,
+}
+
+/**
+ * Voting rules for holder proposals, set by the collection authority
+ *
+ * quorum is in basis points of the NFTs in the proposal snapshot
+ */
+#[account]
+pub struct HolderGovernance {
+    pub version: u8,
+    pub quorum_percentage: u64,
+    pub voting_period: i64, // seconds
+    pub proposal_count: u64,
+}
+
+impl HolderGovernance {
+    // discriminator + version + quorum + voting period + proposal count
+    pub const SIZE: usize = 8 + 1 + 8 + 8 + 8;
+}
+
+#[account]
+pub struct HolderProposal {
+    pub version: u8,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: GovernanceAction,
+    pub snapshot_size: u64, // NFTs in ledger when proposal was created
+    pub quorum_percentage: u64,
+    pub voting_ends_at: i64, // unix timestamp
+    pub yes_votes: u64,
+    pub no_votes: u64,
+    pub votes: Vec<u8>, // bit set for each NFT that voted, by ledger position
+    pub executed: bool,
+}
+
+impl HolderProposal {
+    // discriminator + version + id + proposer + snapshot size + quorum
+    // + voting end + yes and no votes + vec prefix + executed
+    pub const BASE_SIZE: usize = 8 + 1 + 8 + 32 + 8 + 8 + 8 + 8 + 8 + 4 + 1;
+
+    pub fn space(action: &GovernanceAction, snapshot_size: u64) -> usize {
+        Self::BASE_SIZE + action.size() + Self::votes_len(snapshot_size)
+    }
+
+    // Bytes of the vote bitmap for the NFTs in the snapshot. div_ceil is
+    // newer than the BPF toolchain's rustc
+    #[allow(clippy::manual_div_ceil)]
+    pub const fn votes_len(snapshot_size: u64) -> usize {
+        ((snapshot_size + 7) / 8) as usize
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum GovernanceAction {
+    // Artist percentages become the remainder of each label percentage
+    UpdateHolderPercentages {
+        label_mint_percentage: u64,
+        label_secondary_percentage: u64,
+        label_licensing_percentage: u64,
+    },
+    // Replaces every tier of the fee schedule, priced in its currency
+    SetFeeTiers {
+        tiers: Vec<FeeTier>,
+    },
+}
+
+impl GovernanceAction {
+    pub fn size(&self) -> usize {
+        match self {
+            // variant + 3 percentages
+            GovernanceAction::UpdateHolderPercentages { .. } => 1 + 3 * 8,
+            // variant + vec prefix + tiers
+            GovernanceAction::SetFeeTiers { tiers } => 1 + 4 + tiers.len() * FeeTier::SIZE,
+        }
+    }
+
+    fn verify(&self) -> Result<()> {
+        match self {
+            GovernanceAction::UpdateHolderPercentages {
+                label_mint_percentage,
+                label_secondary_percentage,
+                label_licensing_percentage,
+            } => {
+                if *label_mint_percentage > ONE_HUNDRED_PERCENT
+                    || *label_secondary_percentage > ONE_HUNDRED_PERCENT
+                    || *label_licensing_percentage > ONE_HUNDRED_PERCENT
+                {
+                    return Err(ErrorCode::InvalidCollectionConfig.into());
+                }
+            }
+            GovernanceAction::SetFeeTiers { tiers } => verify_fee_tiers(tiers)?,
+        }
+
+        Ok(())
+    }
+
+    fn changes_artist_percentages(&self, collection_config: &CollectionConfiguration) -> bool {
+        match self {
+            GovernanceAction::UpdateHolderPercentages {
+                label_mint_percentage,
+                label_secondary_percentage,
+                label_licensing_percentage,
+            } => {
+                *label_mint_percentage != collection_config.label_mint_percentage
+                    || *label_secondary_percentage != collection_config.label_secondary_percentage
+                    || *label_licensing_percentage != collection_config.label_licensing_percentage
+            }
+            GovernanceAction::SetFeeTiers { .. } => false,
+        }
+    }
+
+    fn apply(&self, collection_config: &mut CollectionConfiguration) {
+        match self {
+            GovernanceAction::UpdateHolderPercentages {
+                label_mint_percentage,
+                label_secondary_percentage,
+                label_licensing_percentage,
+            } => {
+                collection_config.label_mint_percentage = *label_mint_percentage;
+                collection_config.artist_mint_percentage =
+                    ONE_HUNDRED_PERCENT - label_mint_percentage;
+                collection_config.label_secondary_percentage = *label_secondary_percentage;
+                collection_config.artist_secondary_percentage =
+                    ONE_HUNDRED_PERCENT - label_secondary_percentage;
+                collection_config.label_licensing_percentage = *label_licensing_percentage;
+                collection_config.artist_licensing_percentage =
+                    ONE_HUNDRED_PERCENT - label_licensing_percentage;
+            }
+            // Applied to the fee schedule by execute_holder_proposal
+            GovernanceAction::SetFeeTiers { .. } => {}
+        }
+    }
+}
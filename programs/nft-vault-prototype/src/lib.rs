@@ -1,7 +1,28 @@
+// Instructions take one argument per percentage, and the generated cpi
+// module mirrors every instruction signature
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::program::{invoke, invoke_signed};
 use anchor_lang::solana_program::system_instruction;
-use anchor_spl::token::{Mint, TokenAccount};
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use royalty_math::{Allocation, StreamSplit, ONE_HUNDRED_PERCENT};
+
+pub mod events;
+pub mod fee_schedule;
+pub mod governance;
+pub mod licensing;
+pub mod migration;
+pub mod price_feed;
+pub mod secondary_sales;
+
+use events::*;
+use fee_schedule::*;
+use governance::*;
+use licensing::*;
+use migration::*;
+use price_feed::*;
+use secondary_sales::*;
 
 declare_id!("5GL4DTAqK5j4MFWkdrf51TGGvcFePMuLrDSpnAvfNgqT");
 
@@ -20,18 +41,22 @@ pub mod nft_vault_prototype {
         // artist_splits: Vec<ArtistPercentage>,
         single_artist: Pubkey,
     ) -> ProgramResult {
-        let one_hundred_percent = 10000;
-
         // Verify label splits add up to 100%
-        if artist_mint_percentage + label_mint_percentage != one_hundred_percent
-            || artist_secondary_percentage + label_secondary_percentage != one_hundred_percent
-            || artist_licensing_percentage + label_licensing_percentage != one_hundred_percent
-        {
-            return Err(ErrorCode::InvalidCollectionConfig.into());
-        }
+        verify_stream_percentages(
+            artist_mint_percentage,
+            label_mint_percentage,
+            artist_secondary_percentage,
+            label_secondary_percentage,
+            artist_licensing_percentage,
+            label_licensing_percentage,
+        )?;
 
         // TODO: verify artist_splits add to 100%
 
+        _ctx.accounts.collection_config.version = ACCOUNT_VERSION;
+        _ctx.accounts.nft_balance_ledger.version = ACCOUNT_VERSION;
+        _ctx.accounts.artist_balance_ledger.version = ACCOUNT_VERSION;
+
         // Set collection authority
         _ctx.accounts.collection_config.collection_authority = _ctx.accounts.payer.key();
 
@@ -62,6 +87,17 @@ pub mod nft_vault_prototype {
             .artist_balance_ledger
             .initialize_artist_ledger(_ctx.accounts.collection_config.artist_splits.clone())?;
 
+        emit!(CollectionInitialized {
+            collection_authority: _ctx.accounts.collection_config.collection_authority,
+            artist_mint_percentage,
+            label_mint_percentage,
+            artist_secondary_percentage,
+            label_secondary_percentage,
+            artist_licensing_percentage,
+            label_licensing_percentage,
+            artist_splits: _ctx.accounts.collection_config.artist_splits.clone(),
+        });
+
         Ok(())
     }
 
@@ -75,24 +111,12 @@ pub mod nft_vault_prototype {
             return Err(ErrorCode::InvalidBalanceLedger.into());
         }
 
-        // Check associated account is of correct mint type
-        let nft_associated_account_mint = ctx.accounts.nft_associated_account.mint;
-        let actual_nft_mint = ctx.accounts.nft.key();
-        if nft_associated_account_mint != actual_nft_mint {
-            return Err(ErrorCode::InvalidNftAssociatedAccount.into());
-        }
-
-        // Check associated account balance is not 0
-        if ctx.accounts.nft_associated_account.amount == 0 {
-            return Err(ErrorCode::AssociatedAccountBalanceZero.into());
-        }
-
-        // Check associated account is owned by withdrawer
-        let nft_associated_account_owner = ctx.accounts.nft_associated_account.owner;
-
-        if ctx.accounts.to.key() != nft_associated_account_owner {
-            return Err(ErrorCode::NftNotOwnedByWithdrawer.into());
-        }
+        // Check withdrawer holds the NFT
+        verify_nft_holder(
+            ctx.accounts.nft.key(),
+            &ctx.accounts.nft_associated_account,
+            ctx.accounts.to.key(),
+        )?;
 
         // Empty royalties_balance in ledger for given NFT
         // Errors out if NFT is not found in ledger
@@ -103,7 +127,7 @@ pub mod nft_vault_prototype {
 
         let ix = system_instruction::transfer(
             &ctx.accounts.pda_vault.key(),
-            &ctx.accounts.to.key,
+            ctx.accounts.to.key,
             amount,
         );
 
@@ -117,6 +141,13 @@ pub mod nft_vault_prototype {
             ],
             &[&[b"vault", &[255]]],
         )?;
+
+        emit!(MemberWithdrawn {
+            nft_address: ctx.accounts.nft.key(),
+            owner: ctx.accounts.to.key(),
+            amount,
+        });
+
         Ok(())
     }
 
@@ -140,7 +171,7 @@ pub mod nft_vault_prototype {
 
         let ix = system_instruction::transfer(
             &ctx.accounts.pda_vault.key(),
-            &ctx.accounts.artist_account.key,
+            ctx.accounts.artist_account.key,
             amount,
         );
 
@@ -155,6 +186,11 @@ pub mod nft_vault_prototype {
             &[&[b"vault", &[255]]],
         )?;
 
+        emit!(ArtistWithdrawn {
+            artist_address: ctx.accounts.artist_account.key(),
+            amount,
+        });
+
         Ok(())
     }
 
@@ -164,8 +200,6 @@ pub mod nft_vault_prototype {
      */
     pub fn distribute_secondary_pool(ctx: Context<DistributeSecondaryPool>) -> ProgramResult {
         // TODO: add annotations or manually check PDAs
-        let one_hundred_percent = 10000;
-
         let amount = ctx.accounts.pda_secondary_pool.lamports();
 
         // Can't license if no NFTs were minted
@@ -173,9 +207,16 @@ pub mod nft_vault_prototype {
             return Err(ErrorCode::NoNftsInCollection.into());
         }
 
+        let config = &ctx.accounts.collection_config;
+        let amounts = StreamSplit {
+            artist: config.artist_secondary_percentage,
+            holders: config.label_secondary_percentage,
+        }
+        .divide(amount)
+        .map_err(math_error)?;
+
         // Calculate and distribute payment to Artists ledger
-        let amount_to_artists = amount * ctx.accounts.collection_config.artist_secondary_percentage
-            / one_hundred_percent;
+        let amount_to_artists = amounts.artist_total;
         ctx.accounts
             .artist_balance_ledger
             .distribute_artist_payments(
@@ -184,14 +225,14 @@ pub mod nft_vault_prototype {
             )?;
 
         // Calculate and distribute payment to Label ledger
-        let amount_to_label = amount * ctx.accounts.collection_config.label_secondary_percentage
-            / one_hundred_percent;
-        ctx.accounts
+        let amount_to_label = amounts.holder_total;
+        let per_nft_share = ctx
+            .accounts
             .nft_balance_ledger
             .distribute_payments(amount_to_label)?;
 
         let ix = system_instruction::transfer(
-            &ctx.accounts.pda_secondary_pool.key,
+            ctx.accounts.pda_secondary_pool.key,
             &ctx.accounts.pda_vault.key(),
             amount,
         );
@@ -207,21 +248,93 @@ pub mod nft_vault_prototype {
             &[&[b"secondary-pool", &[255]]],
         )?;
 
+        emit!(RoyaltiesDistributed {
+            stream: RoyaltyStream::Secondary,
+            amount,
+            artist_total: amount_to_artists,
+            holder_total: amount_to_label,
+            per_nft_share,
+            nft_count: ctx.accounts.nft_balance_ledger.size,
+        });
+
         Ok(())
     }
 
+    pub fn initialize_nft_sales(ctx: Context<InitializeNftSales>) -> ProgramResult {
+        secondary_sales::initialize_nft_sales(ctx)
+    }
+
+    pub fn record_secondary_sale(
+        ctx: Context<RecordSecondarySale>,
+        price: u64,
+        buyer: Pubkey,
+        seller: Pubkey,
+        royalty_amount: u64,
+    ) -> ProgramResult {
+        secondary_sales::record_secondary_sale(ctx, price, buyer, seller, royalty_amount)
+    }
+
+    pub fn approve_sale_reporter(
+        ctx: Context<ApproveSaleReporter>,
+        reporter: Pubkey,
+    ) -> ProgramResult {
+        secondary_sales::approve_sale_reporter(ctx, reporter)
+    }
+
+    pub fn revoke_sale_reporter(ctx: Context<RevokeSaleReporter>) -> ProgramResult {
+        secondary_sales::revoke_sale_reporter(ctx)
+    }
+
     pub fn pay_licensing_fee(ctx: Context<PayLicensingFee>, amount: u64) -> ProgramResult {
-        // TODO: add annotations or manually check PDAs
-        let one_hundred_percent = 10000;
+        ctx.accounts.license.verify_payment(
+            ctx.accounts.from.key(),
+            ctx.accounts.collection_config.key(),
+            Clock::get()?.slot,
+        )?;
 
-        // Can't license if no NFTs were minted
-        if ctx.accounts.nft_balance_ledger.size == 0 {
-            return Err(ErrorCode::NoNftsInCollection.into());
+        ctx.accounts.settle_licensing_fee(
+            amount,
+            PaymentPurpose::Fee,
+            ctx.remaining_accounts.first(),
+        )
+    }
+
+    pub fn add_nft(ctx: Context<AddNft>, amount_paid: u64) -> ProgramResult {
+        // Verify Collection Authority signed
+        if ctx.accounts.collection_config.collection_authority
+            != ctx.accounts.collection_authority.key()
+        {
+            return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
         }
 
-        // Calculate and distribute payment to Artists ledger
-        let amount_to_artists = amount * ctx.accounts.collection_config.artist_licensing_percentage
-            / one_hundred_percent;
+        // TODO: check pda accounts are as expected
+
+        // TODO: verify if collection splits add up to 100%
+
+        if ctx
+            .accounts
+            .nft_balance_ledger
+            .snapshot_position(ctx.accounts.nft_address.key())
+            .is_ok()
+        {
+            return Err(ErrorCode::NftAlreadyInCollection.into());
+        }
+
+        // Holders in the ledger before this NFT is added
+        let nft_count = ctx.accounts.nft_balance_ledger.size;
+
+        // On first mint, label size is 0, so all revenue goes to artist
+        let config = &ctx.accounts.collection_config;
+        let amounts = StreamSplit {
+            artist: config.artist_mint_percentage,
+            holders: config.label_mint_percentage,
+        }
+        .divide_mint(amount_paid, nft_count)
+        .map_err(math_error)?;
+        let amount_to_artists = amounts.artist_total;
+        let amount_to_label = amounts.holder_total;
+
+        // update the artist ledger with royalties
         ctx.accounts
             .artist_balance_ledger
             .distribute_artist_payments(
@@ -229,35 +342,176 @@ pub mod nft_vault_prototype {
                 ctx.accounts.collection_config.artist_splits.clone(),
             )?;
 
-        // Calculate and distribute payment to Label ledger
-        let amount_to_label = amount * ctx.accounts.collection_config.label_licensing_percentage
-            / one_hundred_percent;
+        // update the label nft balance ledger with royalties
+        let mut per_nft_share = 0;
+        if nft_count > 0 {
+            per_nft_share = ctx
+                .accounts
+                .nft_balance_ledger
+                .distribute_payments(amount_to_label)?;
+        }
+
+        // Make room for the new NFT in the ledger, payer covers the rent
+        grow_account(
+            &ctx.accounts.nft_balance_ledger.to_account_info(),
+            NftBalanceLedger::space(nft_count as usize + 1),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        // Add nft address to ledger
         ctx.accounts
             .nft_balance_ledger
-            .distribute_payments(amount_to_label)?;
+            .add_nft_to_ledger(ctx.accounts.nft_address.key());
 
-        // Send Sol to Vault account
-        let ix = system_instruction::transfer(
-            &ctx.accounts.from.key,
-            &ctx.accounts.pda_vault.key,
-            amount,
-        );
+        emit!(RoyaltiesDistributed {
+            stream: RoyaltyStream::Mint,
+            amount: amount_paid,
+            artist_total: amount_to_artists,
+            holder_total: amount_to_label,
+            per_nft_share,
+            nft_count,
+        });
+
+        emit!(NftAdded {
+            nft_address: ctx.accounts.nft_address.key(),
+            amount_paid,
+            nft_count: ctx.accounts.nft_balance_ledger.size,
+        });
 
-        invoke(
-            &ix,
-            &[
-                ctx.accounts.system_program.to_account_info(),
-                ctx.accounts.pda_vault.to_account_info(),
-                ctx.accounts.from.to_account_info(),
-            ],
+        Ok(())
+    }
+
+    /**
+     * Proposes new split percentages and artist splits for the collection.
+     * Every artist affected by the change must approve it, and it can only
+     * be executed once the timelock has passed.
+     */
+    pub fn propose_config_update(
+        ctx: Context<ProposeConfigUpdate>,
+        artist_mint_percentage: u64,
+        label_mint_percentage: u64,
+        artist_secondary_percentage: u64,
+        label_secondary_percentage: u64,
+        artist_licensing_percentage: u64,
+        label_licensing_percentage: u64,
+        artist_splits: Vec<ArtistPercentage>,
+    ) -> ProgramResult {
+        // Verify Collection Authority signed
+        if ctx.accounts.collection_config.collection_authority
+            != ctx.accounts.collection_authority.key()
+        {
+            return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+        }
+
+        verify_stream_percentages(
+            artist_mint_percentage,
+            label_mint_percentage,
+            artist_secondary_percentage,
+            label_secondary_percentage,
+            artist_licensing_percentage,
+            label_licensing_percentage,
+        )?;
+        verify_artist_splits(
+            &artist_splits,
+            ONE_HUNDRED_PERCENT - ctx.accounts.collection_config.artist_reserve_percentage,
         )?;
 
+        let collection_config = &ctx.accounts.collection_config;
+        let proposal = &mut ctx.accounts.config_update_proposal;
+
+        proposal.version = ACCOUNT_VERSION;
+        proposal.proposer = ctx.accounts.payer.key();
+        proposal.artist_mint_percentage = artist_mint_percentage;
+        proposal.label_mint_percentage = label_mint_percentage;
+        proposal.artist_secondary_percentage = artist_secondary_percentage;
+        proposal.label_secondary_percentage = label_secondary_percentage;
+        proposal.artist_licensing_percentage = artist_licensing_percentage;
+        proposal.label_licensing_percentage = label_licensing_percentage;
+        proposal.artist_splits = artist_splits;
+
+        // Snapshot which current artists have to sign off on the change
+        proposal.required_approvals = proposal.affected_artists(collection_config);
+        proposal.executable_at = Clock::get()?.unix_timestamp + CONFIG_UPDATE_TIMELOCK;
+
         Ok(())
     }
 
-    pub fn add_nft(ctx: Context<AddNft>, amount_paid: u64) -> ProgramResult {
-        let one_hundred_percent = 10000;
+    pub fn approve_config_update(ctx: Context<ApproveConfigUpdate>) -> ProgramResult {
+        let artist = ctx.accounts.artist.key();
+        let proposal = &mut ctx.accounts.config_update_proposal;
+
+        if !proposal.required_approvals.contains(&artist) {
+            return Err(ErrorCode::InvalidArtist.into());
+        }
 
+        if proposal.approvals.contains(&artist) {
+            return Err(ErrorCode::ConfigUpdateAlreadyApproved.into());
+        }
+
+        proposal.approvals.push(artist);
+
+        Ok(())
+    }
+
+    /**
+     * Applies an approved proposal once its timelock has passed.
+     * Balances already in the artist ledger are left untouched, new
+     * artists are added with an empty balance.
+     *
+     * Approvals and splits are checked again against the config as it is
+     * now, so a proposal made before the splits changed needs the consent
+     * of the current artists. When that includes artists that weren't
+     * asked, it has to be cancelled and proposed again.
+     */
+    pub fn execute_config_update(ctx: Context<ExecuteConfigUpdate>) -> ProgramResult {
+        let proposal = &ctx.accounts.config_update_proposal;
+
+        if Clock::get()?.unix_timestamp < proposal.executable_at {
+            return Err(ErrorCode::ConfigUpdateTimelockActive.into());
+        }
+
+        let collection_config = &ctx.accounts.collection_config;
+        for artist in proposal.affected_artists(collection_config).iter() {
+            if !proposal.approvals.contains(artist) {
+                return Err(ErrorCode::ConfigUpdateMissingApprovals.into());
+            }
+        }
+
+        verify_artist_splits(
+            &proposal.artist_splits,
+            ONE_HUNDRED_PERCENT - collection_config.artist_reserve_percentage,
+        )?;
+
+        let collection_config = &mut ctx.accounts.collection_config;
+        collection_config.artist_mint_percentage = proposal.artist_mint_percentage;
+        collection_config.label_mint_percentage = proposal.label_mint_percentage;
+        collection_config.artist_secondary_percentage = proposal.artist_secondary_percentage;
+        collection_config.label_secondary_percentage = proposal.label_secondary_percentage;
+        collection_config.artist_licensing_percentage = proposal.artist_licensing_percentage;
+        collection_config.label_licensing_percentage = proposal.label_licensing_percentage;
+        collection_config.artist_splits = proposal.artist_splits.clone();
+
+        // Make room for the new splits and artists, payer covers the rent
+        grow_for_artist_splits(
+            &ctx.accounts.collection_config,
+            &ctx.accounts.artist_balance_ledger,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        ctx.accounts
+            .artist_balance_ledger
+            .add_missing_artists(&proposal.artist_splits);
+
+        emit!(CollectionConfigUpdated::from(
+            &*ctx.accounts.collection_config
+        ));
+
+        Ok(())
+    }
+
+    pub fn cancel_config_update(ctx: Context<CancelConfigUpdate>) -> ProgramResult {
         // Verify Collection Authority signed
         if ctx.accounts.collection_config.collection_authority
             != ctx.accounts.collection_authority.key()
@@ -265,76 +519,443 @@ pub mod nft_vault_prototype {
             return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
         }
 
-        // TODO: check pda accounts are as expected
+        Ok(())
+    }
 
-        // TODO: verify if collection splits add up to 100%
+    pub fn initialize_holder_governance(
+        ctx: Context<InitializeHolderGovernance>,
+        quorum_percentage: u64,
+        voting_period: i64,
+    ) -> ProgramResult {
+        governance::initialize_holder_governance(ctx, quorum_percentage, voting_period)
+    }
 
-        // TODO: verify if NFT is already part of collection
+    pub fn create_holder_proposal(
+        ctx: Context<CreateHolderProposal>,
+        action: GovernanceAction,
+    ) -> ProgramResult {
+        governance::create_holder_proposal(ctx, action)
+    }
 
-        // On first mint, label size is 0, so all revenue goes to artist
-        if ctx.accounts.nft_balance_ledger.size == 0 {
-            // update the artist ledger with royalties
-            ctx.accounts
-                .artist_balance_ledger
-                .distribute_artist_payments(
-                    amount_paid,
-                    ctx.accounts.collection_config.artist_splits.clone(),
-                )?;
-        } else {
-            let amount_to_artists = amount_paid
-                * ctx.accounts.collection_config.artist_mint_percentage
-                / one_hundred_percent;
-
-            // update the artist ledger with royalties
-            ctx.accounts
-                .artist_balance_ledger
-                .distribute_artist_payments(
-                    amount_to_artists,
-                    ctx.accounts.collection_config.artist_splits.clone(),
+    pub fn cast_holder_vote(ctx: Context<CastHolderVote>, approve: bool) -> ProgramResult {
+        governance::cast_holder_vote(ctx, approve)
+    }
+
+    pub fn execute_holder_proposal<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteHolderProposal<'info>>,
+    ) -> ProgramResult {
+        governance::execute_holder_proposal(ctx)
+    }
+
+    /**
+     * Moves part or all of an artist's allocated percentage to another address.
+     * Balances already accrued stay with the original artist ledger entry.
+     * Payer covers the rent of a new artist's entries.
+     */
+    pub fn transfer_artist_split(
+        ctx: Context<TransferArtistSplit>,
+        percentage: u64,
+    ) -> ProgramResult {
+        let artist = ctx.accounts.artist.key();
+        let new_artist = ctx.accounts.new_artist.key();
+
+        if percentage == 0 || artist == new_artist {
+            return Err(ErrorCode::InvalidArtistSplitTransfer.into());
+        }
+
+        let artist_splits = &mut ctx.accounts.collection_config.artist_splits;
+
+        let from_index = artist_splits
+            .iter()
+            .position(|split| split.artist_address == artist)
+            .ok_or(ErrorCode::InvalidArtist)?;
+
+        if artist_splits[from_index].allocated_percentage < percentage {
+            return Err(ErrorCode::InvalidArtistSplitTransfer.into());
+        }
+
+        artist_splits[from_index].allocated_percentage -= percentage;
+
+        match artist_splits
+            .iter_mut()
+            .find(|split| split.artist_address == new_artist)
+        {
+            Some(split) => split.allocated_percentage += percentage,
+            None => artist_splits.push(ArtistPercentage {
+                artist_address: new_artist,
+                allocated_percentage: percentage,
+            }),
+        }
+
+        // Artists that sold their whole split no longer receive payments,
+        // but keep their ledger entry so they can withdraw what is owed
+        artist_splits.retain(|split| split.allocated_percentage > 0);
+
+        // Make room for a new artist in the config and ledger, payer covers the rent
+        grow_for_artist_splits(
+            &ctx.accounts.collection_config,
+            &ctx.accounts.artist_balance_ledger,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        ctx.accounts
+            .artist_balance_ledger
+            .add_missing_artists(&ctx.accounts.collection_config.artist_splits);
+
+        emit!(CollectionConfigUpdated::from(
+            &*ctx.accounts.collection_config
+        ));
+
+        Ok(())
+    }
+
+    /**
+     * Adds an artist to the collection after initialization.
+     *
+     * ProRata dilutes every current artist and needs all of them to sign
+     * (passed as remaining accounts), Reserve takes from the unallocated reserve.
+     */
+    pub fn add_artist<'info>(
+        ctx: Context<'_, '_, '_, 'info, AddArtist<'info>>,
+        allocated_percentage: u64,
+        source: ArtistDilution,
+    ) -> ProgramResult {
+        // Verify Collection Authority signed
+        if ctx.accounts.collection_config.collection_authority
+            != ctx.accounts.collection_authority.key()
+        {
+            return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+        }
+
+        let new_artist = ctx.accounts.new_artist.key();
+        let collection_config = &mut ctx.accounts.collection_config;
+
+        if allocated_percentage == 0
+            || collection_config
+                .artist_splits
+                .iter()
+                .any(|split| split.artist_address == new_artist)
+        {
+            return Err(ErrorCode::InvalidArtistDilution.into());
+        }
+
+        match source {
+            ArtistDilution::ProRata => {
+                verify_artist_consensus(
+                    &collection_config.artist_splits,
+                    ctx.remaining_accounts,
+                    None,
                 )?;
 
-            let amount_to_label = amount_paid
-                * ctx.accounts.collection_config.label_mint_percentage
-                / one_hundred_percent;
+                let total_allocated = collection_config.total_allocated();
+                if allocated_percentage >= total_allocated {
+                    return Err(ErrorCode::InvalidArtistDilution.into());
+                }
 
-            // update the label nft balance ledger with royalties
-            ctx.accounts
-                .nft_balance_ledger
-                .distribute_payments(amount_to_label)?;
+                rescale_artist_splits(
+                    &mut collection_config.artist_splits,
+                    total_allocated - allocated_percentage,
+                )?;
+            }
+            ArtistDilution::Reserve => {
+                if allocated_percentage > collection_config.artist_reserve_percentage {
+                    return Err(ErrorCode::InvalidArtistDilution.into());
+                }
+
+                collection_config.artist_reserve_percentage -= allocated_percentage;
+            }
         }
 
-        // Add nft address to ledger
+        collection_config.artist_splits.push(ArtistPercentage {
+            artist_address: new_artist,
+            allocated_percentage,
+        });
+
+        // Make room for the new artist in the config and ledger, payer covers the rent
+        grow_account(
+            &ctx.accounts.collection_config.to_account_info(),
+            CollectionConfiguration::space(ctx.accounts.collection_config.artist_splits.len()),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        // Artists removed earlier kept their ledger entry, only new ones add to it
         ctx.accounts
-            .nft_balance_ledger
-            .add_nft_to_ledger(ctx.accounts.nft_address.key());
+            .artist_balance_ledger
+            .add_missing_artists(&ctx.accounts.collection_config.artist_splits);
+
+        let artist_count = ctx.accounts.artist_balance_ledger.size as usize;
+        grow_account(
+            &ctx.accounts.artist_balance_ledger.to_account_info(),
+            ArtistBalanceLedger::space(artist_count),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        emit!(CollectionConfigUpdated::from(
+            &*ctx.accounts.collection_config
+        ));
+
+        Ok(())
+    }
+
+    /**
+     * Removes an artist from the splits, every other artist must sign
+     * (passed as remaining accounts). The removed artist's consent isn't
+     * needed, so one artist can't block the others or be lost for good
+     * with their key. They keep their ledger entry so accrued balances
+     * can still be withdrawn.
+     */
+    pub fn remove_artist<'info>(
+        ctx: Context<'_, '_, '_, 'info, RemoveArtist<'info>>,
+        destination: ArtistDilution,
+    ) -> ProgramResult {
+        // Verify Collection Authority signed
+        if ctx.accounts.collection_config.collection_authority
+            != ctx.accounts.collection_authority.key()
+        {
+            return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+        }
+
+        let removed_artist = ctx.accounts.removed_artist.key();
+        let collection_config = &mut ctx.accounts.collection_config;
+
+        verify_artist_consensus(
+            &collection_config.artist_splits,
+            ctx.remaining_accounts,
+            Some(removed_artist),
+        )?;
+
+        let index = collection_config
+            .artist_splits
+            .iter()
+            .position(|split| split.artist_address == removed_artist)
+            .ok_or(ErrorCode::InvalidArtist)?;
+
+        // At least one artist has to stay to receive the artist share
+        if collection_config.artist_splits.len() == 1 {
+            return Err(ErrorCode::InvalidArtistDilution.into());
+        }
+
+        let removed = collection_config.artist_splits.remove(index);
+
+        match destination {
+            ArtistDilution::ProRata => {
+                let total_allocated =
+                    collection_config.total_allocated() + removed.allocated_percentage;
+                rescale_artist_splits(&mut collection_config.artist_splits, total_allocated)?;
+            }
+            ArtistDilution::Reserve => {
+                collection_config.artist_reserve_percentage += removed.allocated_percentage;
+            }
+        }
+
+        emit!(CollectionConfigUpdated::from(&**collection_config));
 
         Ok(())
     }
 
+    /**
+     * Grows the config and ledgers to hold the given number of artists and NFTs.
+     * Payer covers the extra rent, accounts are never shrunk.
+     * A single call can grow each account by at most 10KB.
+     */
+    pub fn expand_ledger(
+        ctx: Context<ExpandLedger>,
+        artist_capacity: u64,
+        nft_capacity: u64,
+    ) -> ProgramResult {
+        let payer = ctx.accounts.payer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+
+        grow_account(
+            &ctx.accounts.collection_config.to_account_info(),
+            CollectionConfiguration::space(artist_capacity as usize),
+            &payer,
+            &system_program,
+        )?;
+        grow_account(
+            &ctx.accounts.artist_balance_ledger.to_account_info(),
+            ArtistBalanceLedger::space(artist_capacity as usize),
+            &payer,
+            &system_program,
+        )?;
+        grow_account(
+            &ctx.accounts.nft_balance_ledger.to_account_info(),
+            NftBalanceLedger::space(nft_capacity as usize),
+            &payer,
+            &system_program,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn migrate_collection_config(ctx: Context<MigrateCollectionConfig>) -> ProgramResult {
+        migration::migrate_collection_config(ctx)
+    }
+
+    pub fn migrate_nft_balance_ledger(ctx: Context<MigrateNftBalanceLedger>) -> ProgramResult {
+        migration::migrate_nft_balance_ledger(ctx)
+    }
+
+    pub fn migrate_artist_balance_ledger(
+        ctx: Context<MigrateArtistBalanceLedger>,
+    ) -> ProgramResult {
+        migration::migrate_artist_balance_ledger(ctx)
+    }
+
     pub fn transfer_collection_authority(
         ctx: Context<TransferCollectionAuthority>,
     ) -> ProgramResult {
         for artist in ctx.accounts.collection_config.artist_splits.iter_mut() {
             if artist.artist_address == ctx.accounts.artist_authorizer.key() {
+                let previous_authority = ctx.accounts.collection_config.collection_authority;
                 ctx.accounts.collection_config.collection_authority =
                     ctx.accounts.new_collection_authority.key();
+
+                emit!(CollectionAuthorityTransferred {
+                    previous_authority,
+                    new_authority: ctx.accounts.new_collection_authority.key(),
+                    artist_authorizer: ctx.accounts.artist_authorizer.key(),
+                });
+
                 return Ok(());
             }
         }
 
-        return Err(ErrorCode::InvalidArtist.into());
+        Err(ErrorCode::InvalidArtist.into())
+    }
+
+    pub fn create_license(
+        ctx: Context<CreateLicense>,
+        content_hash: [u8; 32],
+        terms_uri: String,
+        fee: u64,
+        usage_type: UsageType,
+        start_slot: u64,
+        end_slot: u64,
+        grace_slots: u64,
+        volume: u64,
+        licensed_nfts: Vec<Pubkey>,
+        track_share_percentage: u64,
+        reporter: Option<Pubkey>,
+        per_use_rate: u64,
+    ) -> ProgramResult {
+        licensing::create_license(
+            ctx,
+            content_hash,
+            terms_uri,
+            fee,
+            usage_type,
+            start_slot,
+            end_slot,
+            grace_slots,
+            volume,
+            licensed_nfts,
+            track_share_percentage,
+            reporter,
+            per_use_rate,
+        )
+    }
+
+    pub fn renew_license(ctx: Context<PayLicensingFee>) -> ProgramResult {
+        licensing::renew_license(ctx)
+    }
+
+    pub fn report_usage(
+        ctx: Context<ReportUsage>,
+        period: u64,
+        tracks: Vec<TrackUsage>,
+    ) -> ProgramResult {
+        licensing::report_usage(ctx, period, tracks)
+    }
+
+    pub fn pay_usage_fee(ctx: Context<PayLicensingFee>) -> ProgramResult {
+        licensing::pay_usage_fee(ctx)
+    }
+
+    pub fn release_licensing_payments(ctx: Context<ReleaseLicensingPayments>) -> ProgramResult {
+        licensing::release_licensing_payments(ctx)
+    }
+
+    pub fn refund_licensing_payment(
+        ctx: Context<RefundLicensingPayment>,
+        payment_index: u64,
+    ) -> ProgramResult {
+        licensing::refund_licensing_payment(ctx, payment_index)
+    }
+
+    pub fn pay_licensing_fee_usd(
+        ctx: Context<PayLicensingFee>,
+        usd_cents: u64,
+        quoted_lamports: u64,
+        slippage_bps: u64,
+    ) -> ProgramResult {
+        licensing::pay_licensing_fee_usd(ctx, usd_cents, quoted_lamports, slippage_bps)
+    }
+
+    pub fn lapse_license(ctx: Context<LapseLicense>) -> ProgramResult {
+        licensing::lapse_license(ctx)
+    }
+
+    pub fn verify_license(ctx: Context<VerifyLicense>) -> ProgramResult {
+        licensing::verify_license(ctx)
+    }
+
+    pub fn initialize_fee_schedule(
+        ctx: Context<InitializeFeeSchedule>,
+        tiers: Vec<FeeTier>,
+    ) -> ProgramResult {
+        fee_schedule::initialize_fee_schedule(ctx, tiers)
+    }
+
+    pub fn update_fee_schedule(
+        ctx: Context<UpdateFeeSchedule>,
+        tiers: Vec<FeeTier>,
+    ) -> ProgramResult {
+        fee_schedule::update_fee_schedule(ctx, tiers)
+    }
+
+    pub fn set_fee_currency(
+        ctx: Context<UpdateFeeSchedule>,
+        currency: FeeCurrency,
+        tiers: Vec<FeeTier>,
+    ) -> ProgramResult {
+        fee_schedule::set_fee_currency(ctx, currency, tiers)
+    }
+
+    pub fn set_payment_hold(ctx: Context<UpdateFeeSchedule>, hold_slots: u64) -> ProgramResult {
+        fee_schedule::set_payment_hold(ctx, hold_slots)
     }
 }
 
 // TODO: can the vault just be combined in balance ledger?
 #[derive(Accounts)]
 pub struct InitializeCollection<'info> {
-    // TODO: calculate proper spacing and stress test max space
-    #[account(init, payer = payer, space = 9000, seeds = [b"collection-config"], bump = 254)]
+    #[account(
+        init,
+        payer = payer,
+        space = CollectionConfiguration::space(INITIAL_ARTIST_CAPACITY),
+        seeds = [b"collection-config"],
+        bump = 254
+    )]
     collection_config: Account<'info, CollectionConfiguration>,
-    #[account(init, payer = payer, space = 9000, seeds = [b"nft-ledger"], bump = 255)]
+    #[account(
+        init,
+        payer = payer,
+        space = NftBalanceLedger::space(INITIAL_NFT_CAPACITY),
+        seeds = [b"nft-ledger"],
+        bump = 255
+    )]
     nft_balance_ledger: Account<'info, NftBalanceLedger>,
-    #[account(init, payer = payer, space = 500, seeds = [b"artist-ledger"], bump = 255)]
+    #[account(
+        init,
+        payer = payer,
+        space = ArtistBalanceLedger::space(INITIAL_ARTIST_CAPACITY),
+        seeds = [b"artist-ledger"],
+        bump = 255
+    )]
     artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
     #[account(mut)]
     payer: Signer<'info>,
@@ -349,6 +970,111 @@ pub struct TransferCollectionAuthority<'info> {
     new_collection_authority: SystemAccount<'info>,
 }
 
+#[derive(Accounts)]
+#[instruction(
+    artist_mint_percentage: u64,
+    label_mint_percentage: u64,
+    artist_secondary_percentage: u64,
+    label_secondary_percentage: u64,
+    artist_licensing_percentage: u64,
+    label_licensing_percentage: u64,
+    artist_splits: Vec<ArtistPercentage>
+)]
+pub struct ProposeConfigUpdate<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = ConfigUpdateProposal::space(artist_splits.len(), collection_config.artist_splits.len()),
+        seeds = [b"config-update"],
+        bump
+    )]
+    config_update_proposal: Account<'info, ConfigUpdateProposal>,
+    collection_config: Account<'info, CollectionConfiguration>,
+    collection_authority: Signer<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveConfigUpdate<'info> {
+    #[account(mut, seeds = [b"config-update"], bump)]
+    config_update_proposal: Account<'info, ConfigUpdateProposal>,
+    artist: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteConfigUpdate<'info> {
+    #[account(mut, seeds = [b"config-update"], bump, has_one = proposer, close = proposer)]
+    config_update_proposal: Account<'info, ConfigUpdateProposal>,
+    #[account(mut, seeds = [b"collection-config"], bump = 254)]
+    collection_config: Account<'info, CollectionConfiguration>,
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255)]
+    artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
+    #[account(mut)]
+    proposer: SystemAccount<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelConfigUpdate<'info> {
+    #[account(mut, seeds = [b"config-update"], bump, has_one = proposer, close = proposer)]
+    config_update_proposal: Account<'info, ConfigUpdateProposal>,
+    collection_config: Account<'info, CollectionConfiguration>,
+    collection_authority: Signer<'info>,
+    #[account(mut)]
+    proposer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferArtistSplit<'info> {
+    #[account(mut, seeds = [b"collection-config"], bump = 254)]
+    collection_config: Account<'info, CollectionConfiguration>,
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255)]
+    artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
+    artist: Signer<'info>,
+    new_artist: SystemAccount<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddArtist<'info> {
+    #[account(mut, seeds = [b"collection-config"], bump = 254)]
+    collection_config: Account<'info, CollectionConfiguration>,
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255)]
+    artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
+    collection_authority: Signer<'info>,
+    new_artist: SystemAccount<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveArtist<'info> {
+    #[account(mut, seeds = [b"collection-config"], bump = 254)]
+    collection_config: Account<'info, CollectionConfiguration>,
+    collection_authority: Signer<'info>,
+    removed_artist: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpandLedger<'info> {
+    #[account(mut, seeds = [b"collection-config"], bump = 254)]
+    collection_config: Account<'info, CollectionConfiguration>,
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255)]
+    artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
+    #[account(mut, seeds = [b"nft-ledger"], bump = 255)]
+    nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
 // TODO: when and where should we use UncheckedAccount? Should we avoid using Signer and check manually?
 #[derive(Accounts)]
 pub struct MemberWithdraw<'info> {
@@ -393,13 +1119,139 @@ pub struct PayLicensingFee<'info> {
     #[account(mut)]
     from: Signer<'info>,
     #[account(mut)]
+    license: Account<'info, License>,
+    #[account(mut, address = license.token_mint)]
+    license_mint: Account<'info, Mint>,
+    #[account(mut, address = license.token_account)]
+    license_token_account: Account<'info, TokenAccount>,
+    #[account(seeds = [LICENSE_AUTHORITY_SEED], bump = LICENSE_AUTHORITY_BUMP)]
+    license_authority: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"vault"], bump = 255)]
     pda_vault: SystemAccount<'info>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
     collection_config: Account<'info, CollectionConfiguration>,
-    #[account(mut)]
+    #[account(seeds = [b"fee-schedule"], bump)]
+    fee_schedule: Account<'info, FeeSchedule>,
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255)]
     artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
-    #[account(mut)]
+    #[account(mut, seeds = [b"nft-ledger"], bump = 255)]
     nft_balance_ledger: Account<'info, NftBalanceLedger>,
     system_program: Program<'info, System>,
+    token_program: Program<'info, Token>,
+}
+
+impl<'info> PayLicensingFee<'info> {
+    /**
+     * Settles a licensing payment, which must cover the fee schedule's
+     * price for the license. Callers check the license first. price_feed
+     * converts the scheduled price of USD fee schedules.
+     */
+    fn settle_licensing_fee(
+        &mut self,
+        amount: u64,
+        purpose: PaymentPurpose,
+        price_feed: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        // Payments must cover the scheduled price for the licensed usage
+        let fee_tier = self
+            .fee_schedule
+            .tier(self.license.usage_type, self.license.volume)?;
+        let scheduled_fee =
+            self.fee_schedule
+                .price_in_lamports(fee_tier.price, price_feed, Clock::get()?.slot)?;
+        if amount < scheduled_fee {
+            return Err(ErrorCode::LicensingFeeTooLow.into());
+        }
+
+        self.settle_payment(amount, purpose, fee_tier)
+    }
+
+    /**
+     * Moves a licensing payment to the vault, splits it into the ledgers
+     * unless the fee schedule holds payments, and records it on the
+     * license along with the tier it was priced at. A held payment keeps
+     * its purpose, to be undone if it is refunded.
+     */
+    fn settle_payment(
+        &mut self,
+        amount: u64,
+        purpose: PaymentPurpose,
+        fee_tier: FeeTier,
+    ) -> ProgramResult {
+        // Can't license if no NFTs were minted
+        if self.nft_balance_ledger.size == 0 {
+            return Err(ErrorCode::NoNftsInCollection.into());
+        }
+
+        let slot = Clock::get()?.slot;
+
+        // Send Sol to Vault account
+        let ix = system_instruction::transfer(self.from.key, self.pda_vault.key, amount);
+
+        invoke(
+            &ix,
+            &[
+                self.system_program.to_account_info(),
+                self.pda_vault.to_account_info(),
+                self.from.to_account_info(),
+            ],
+        )?;
+
+        // Held payments are split into the ledgers once their hold is over
+        let hold_slots = self.fee_schedule.hold_slots;
+        let mut pending_payment = None;
+        if hold_slots == 0 {
+            licensing::distribute_licensing_fee(
+                &self.collection_config,
+                &mut self.artist_balance_ledger,
+                &mut self.nft_balance_ledger,
+                &self.license,
+                amount,
+            )?;
+        } else {
+            let payment = PendingPayment {
+                amount,
+                purpose,
+                release_slot: slot
+                    .checked_add(hold_slots)
+                    .ok_or(ErrorCode::InvalidFeeSchedule)?,
+            };
+            self.license.hold_payment(payment)?;
+            pending_payment = Some(payment);
+        }
+
+        licensing::issue_license_token(
+            &self.license_mint,
+            &self.license_token_account,
+            &self.license_authority,
+            &self.token_program,
+        )?;
+
+        let license = &mut self.license;
+        license.paid_to_date = license
+            .paid_to_date
+            .checked_add(amount)
+            .ok_or(ErrorCode::InvalidLicense)?;
+        license.fee_tier = Some(fee_tier);
+
+        emit!(LicenseFeePaid {
+            license: license.key(),
+            licensee: license.licensee,
+            amount,
+            paid_to_date: license.paid_to_date,
+            fee_tier,
+        });
+
+        if let Some(payment) = pending_payment {
+            emit!(LicensePaymentHeld {
+                license: license.key(),
+                amount: payment.amount,
+                release_slot: payment.release_slot,
+            });
+        }
+
+        Ok(())
+    }
 }
 
 // TODO: see if payer and authority need to be mut
@@ -426,6 +1278,7 @@ pub struct AddNft<'info> {
  */
 #[account]
 pub struct CollectionConfiguration {
+    pub version: u8,
     pub collection_authority: Pubkey,
     pub artist_mint_percentage: u64, // mint % is only used for mint splits
     pub label_mint_percentage: u64,
@@ -434,6 +1287,30 @@ pub struct CollectionConfiguration {
     pub artist_licensing_percentage: u64, // licensing % is used for licensing payments
     pub label_licensing_percentage: u64,
     pub artist_splits: Vec<ArtistPercentage>,
+    pub artist_reserve_percentage: u64, // artist share not yet allocated to any artist
+}
+
+impl CollectionConfiguration {
+    // discriminator + version + authority + 6 percentages + vec prefix + reserve
+    pub const BASE_SIZE: usize = 8 + 1 + 32 + 6 * 8 + 4 + 8;
+
+    pub const fn space(artist_count: usize) -> usize {
+        Self::BASE_SIZE + artist_count * ArtistPercentage::SIZE
+    }
+
+    fn total_allocated(&self) -> u64 {
+        royalty_math::total_allocated(&self.artist_splits)
+    }
+}
+
+/**
+ * Where an added artist's percentage comes from,
+ * or where a removed artist's percentage goes
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum ArtistDilution {
+    ProRata,
+    Reserve,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
@@ -442,8 +1319,93 @@ pub struct ArtistPercentage {
     pub allocated_percentage: u64, // 3000 = 30%, 500 = 5%, divide by 10,000
 }
 
+impl Allocation for ArtistPercentage {
+    type Recipient = Pubkey;
+
+    fn recipient(&self) -> Pubkey {
+        self.artist_address
+    }
+
+    fn bps(&self) -> u64 {
+        self.allocated_percentage
+    }
+
+    fn set_bps(&mut self, bps: u64) {
+        self.allocated_percentage = bps;
+    }
+}
+
+/**
+ * Pending change to the Collection Configuration
+ *
+ * Holds the full set of new percentages and artist splits,
+ * the artists that must approve it and when it can be executed.
+ */
+#[account]
+pub struct ConfigUpdateProposal {
+    pub version: u8,
+    pub proposer: Pubkey,
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+    pub artist_splits: Vec<ArtistPercentage>,
+    pub required_approvals: Vec<Pubkey>,
+    pub approvals: Vec<Pubkey>,
+    pub executable_at: i64, // unix timestamp
+}
+
+impl ConfigUpdateProposal {
+    // discriminator + version + proposer + 6 percentages + 3 vec prefixes
+    // + executable at
+    pub const BASE_SIZE: usize = 8 + 1 + 32 + 6 * 8 + 3 * 4 + 8;
+
+    // Approvals can only come from the artists in the config when proposed
+    pub const fn space(split_count: usize, artist_count: usize) -> usize {
+        Self::BASE_SIZE + split_count * ArtistPercentage::SIZE + 2 * artist_count * 32
+    }
+
+    /**
+     * An artist is affected if their allocated percentage changes (or they are removed).
+     * Changing any of the artist/label percentages affects every current artist.
+     */
+    fn affected_artists(&self, current: &CollectionConfiguration) -> Vec<Pubkey> {
+        let stream_percentages_changed = self.artist_mint_percentage
+            != current.artist_mint_percentage
+            || self.label_mint_percentage != current.label_mint_percentage
+            || self.artist_secondary_percentage != current.artist_secondary_percentage
+            || self.label_secondary_percentage != current.label_secondary_percentage
+            || self.artist_licensing_percentage != current.artist_licensing_percentage
+            || self.label_licensing_percentage != current.label_licensing_percentage;
+
+        let mut affected = Vec::new();
+        for current_split in current.artist_splits.iter() {
+            let new_percentage = self
+                .artist_splits
+                .iter()
+                .find(|split| split.artist_address == current_split.artist_address)
+                .map(|split| split.allocated_percentage);
+
+            if stream_percentages_changed
+                || new_percentage != Some(current_split.allocated_percentage)
+            {
+                affected.push(current_split.artist_address);
+            }
+        }
+
+        affected
+    }
+}
+
+impl ArtistPercentage {
+    pub const SIZE: usize = 32 + 8;
+}
+
 #[account]
 pub struct ArtistBalanceLedger {
+    pub version: u8,
     pub artist_balances: Vec<ArtistBalance>,
     pub size: u64,
 }
@@ -454,7 +1416,18 @@ pub struct ArtistBalance {
     pub royalties_balance: u64,
 }
 
+impl ArtistBalance {
+    pub const SIZE: usize = 32 + 8;
+}
+
 impl ArtistBalanceLedger {
+    // discriminator + version + vec prefix + size
+    pub const BASE_SIZE: usize = 8 + 1 + 4 + 8;
+
+    pub const fn space(artist_count: usize) -> usize {
+        Self::BASE_SIZE + artist_count * ArtistBalance::SIZE
+    }
+
     fn initialize_artist_ledger(&mut self, artist_splits: Vec<ArtistPercentage>) -> Result<()> {
         for artist_split in artist_splits.iter() {
             let init_artist_balance = ArtistBalance {
@@ -479,17 +1452,16 @@ impl ArtistBalanceLedger {
             return Err(ErrorCode::ArtistLedgerNotInitialized.into());
         }
 
-        let one_hundred_percent = 10000;
-
-        // TODO: check for valid artist_splits adds up to 100%
+        // Splits add up to 100% minus the unallocated reserve, until the
+        // reserve is allocated its share goes pro-rata to current artists.
+        // Rounds down so artists are never credited more than amount, the
+        // remainder stays in the vault (tests/conservation.rs)
+        for share in royalty_math::split_shares(&artist_splits, amount) {
+            let (artist_address, amount_to_distribute) = share.map_err(math_error)?;
 
-        // Iterate through balance ledger and splits to distrubte proper amounts
-        for artist_balance in self.artist_balances.iter_mut() {
-            for artist_split in artist_splits.iter() {
-                if artist_split.artist_address == artist_balance.artist_address {
-                    // Todo: Ensure rounding doesn't introduce vulnerability
-                    let amount_to_distribute =
-                        amount * artist_split.allocated_percentage / one_hundred_percent;
+            // Iterate through balance ledger to credit the split's artist
+            for artist_balance in self.artist_balances.iter_mut() {
+                if artist_balance.artist_address == artist_address {
                     artist_balance.royalties_balance += amount_to_distribute;
                 }
             }
@@ -498,10 +1470,36 @@ impl ArtistBalanceLedger {
         Ok(())
     }
 
+    fn has_artist(&self, artist_address: Pubkey) -> bool {
+        self.artist_balances
+            .iter()
+            .any(|balance| balance.artist_address == artist_address)
+    }
+
+    // Number of artists in the splits without a ledger entry
+    fn missing_artists(&self, artist_splits: &[ArtistPercentage]) -> usize {
+        artist_splits
+            .iter()
+            .filter(|split| !self.has_artist(split.artist_address))
+            .count()
+    }
+
+    fn add_missing_artists(&mut self, artist_splits: &[ArtistPercentage]) {
+        for artist_split in artist_splits.iter() {
+            if !self.has_artist(artist_split.artist_address) {
+                self.artist_balances.push(ArtistBalance {
+                    artist_address: artist_split.artist_address,
+                    royalties_balance: 0,
+                });
+                self.size += 1;
+            }
+        }
+    }
+
     fn empty_royalties_balance_for_artist(&mut self, artist_address: Pubkey) -> Result<u64> {
         for artist_balance in self.artist_balances.iter_mut() {
             if artist_balance.artist_address == artist_address {
-                let owed_balance = artist_balance.royalties_balance.clone();
+                let owed_balance = artist_balance.royalties_balance;
                 artist_balance.royalties_balance = 0;
                 return Ok(owed_balance);
             }
@@ -513,6 +1511,7 @@ impl ArtistBalanceLedger {
 
 #[account]
 pub struct NftBalanceLedger {
+    pub version: u8,
     pub nft_balances: Vec<NftBalance>,
     pub size: u64,
 }
@@ -523,24 +1522,50 @@ pub struct NftBalance {
     pub royalties_balance: u64,
 }
 
+impl NftBalance {
+    pub const SIZE: usize = 32 + 8;
+}
+
 impl NftBalanceLedger {
-    fn distribute_payments(&mut self, amount: u64) -> Result<()> {
-        if self.size == 0 {
-            return Err(ErrorCode::InvalidRoyaltiesDistribution.into());
-        }
+    // discriminator + version + vec prefix + size
+    pub const BASE_SIZE: usize = 8 + 1 + 4 + 8;
+
+    pub const fn space(nft_count: usize) -> usize {
+        Self::BASE_SIZE + nft_count * NftBalance::SIZE
+    }
 
-        // Todo: Ensure rounding doesn't introduce vulnerability
-        let amount_to_distribute = amount / self.size;
+    // Returns the amount credited to each NFT
+    fn distribute_payments(&mut self, amount: u64) -> Result<u64> {
+        // Every NFT gets the same rounded down share, the remainder
+        // stays in the vault (tests/conservation.rs)
+        let amount_to_distribute = royalty_math::per_holder_share(amount, self.size)
+            .map_err(|_| ErrorCode::InvalidRoyaltiesDistribution)?;
 
         for nft_balance in self.nft_balances.iter_mut() {
             nft_balance.royalties_balance += amount_to_distribute;
         }
 
-        Ok(())
+        Ok(amount_to_distribute)
     }
+
+    // Splits amount between the given NFTs only, returns the amount
+    // credited to each
+    fn credit_nfts(&mut self, nft_addresses: &[Pubkey], amount: u64) -> Result<u64> {
+        let amount_to_distribute =
+            royalty_math::per_holder_share(amount, nft_addresses.len() as u64)
+                .map_err(|_| ErrorCode::InvalidRoyaltiesDistribution)?;
+
+        for nft_address in nft_addresses {
+            let position = self.snapshot_position(*nft_address)?;
+            self.nft_balances[position].royalties_balance += amount_to_distribute;
+        }
+
+        Ok(amount_to_distribute)
+    }
+
     fn add_nft_to_ledger(&mut self, nft_address: Pubkey) {
         let nft_balance = NftBalance {
-            nft_address: nft_address,
+            nft_address,
             royalties_balance: 0,
         };
 
@@ -548,10 +1573,18 @@ impl NftBalanceLedger {
         self.size += 1;
     }
 
+    // Position of the NFT in the ledger, NFTs are only ever appended
+    fn snapshot_position(&self, nft_address: Pubkey) -> Result<usize> {
+        self.nft_balances
+            .iter()
+            .position(|nft_balance| nft_balance.nft_address == nft_address)
+            .ok_or_else(|| ErrorCode::InvalidNft.into())
+    }
+
     fn empty_royalties_balance_for_nft(&mut self, nft_address: Pubkey) -> Result<u64> {
         for nft_balance in self.nft_balances.iter_mut() {
             if nft_balance.nft_address == nft_address {
-                let owed_balance = nft_balance.royalties_balance.clone();
+                let owed_balance = nft_balance.royalties_balance;
                 nft_balance.royalties_balance = 0;
                 return Ok(owed_balance);
             }
@@ -561,6 +1594,165 @@ impl NftBalanceLedger {
     }
 }
 
+// Layout version written to every account, bumped on layout changes
+pub const ACCOUNT_VERSION: u8 = 1;
+
+// Capacity allocated on initialize_collection, grown with expand_ledger
+pub const INITIAL_ARTIST_CAPACITY: usize = 10;
+pub const INITIAL_NFT_CAPACITY: usize = 200;
+
+// Delay between proposing a config update and being able to execute it
+const CONFIG_UPDATE_TIMELOCK: i64 = 60 * 60 * 24 * 2;
+
+fn verify_nft_holder(
+    nft_mint: Pubkey,
+    nft_associated_account: &TokenAccount,
+    holder: Pubkey,
+) -> Result<()> {
+    // Check associated account is of correct mint type
+    if nft_associated_account.mint != nft_mint {
+        return Err(ErrorCode::InvalidNftAssociatedAccount.into());
+    }
+
+    // Check associated account balance is not 0
+    if nft_associated_account.amount == 0 {
+        return Err(ErrorCode::AssociatedAccountBalanceZero.into());
+    }
+
+    // Check associated account is owned by holder
+    if nft_associated_account.owner != holder {
+        return Err(ErrorCode::NftNotOwnedByWithdrawer.into());
+    }
+
+    Ok(())
+}
+
+// Every artist in the splits (except `excluded`) must have signed
+fn verify_artist_consensus(
+    artist_splits: &[ArtistPercentage],
+    signers: &[AccountInfo],
+    excluded: Option<Pubkey>,
+) -> Result<()> {
+    for artist_split in artist_splits.iter() {
+        if Some(artist_split.artist_address) == excluded {
+            continue;
+        }
+
+        let signed = signers
+            .iter()
+            .any(|signer| signer.is_signer && signer.key() == artist_split.artist_address);
+        if !signed {
+            return Err(ErrorCode::MissingArtistSignatures.into());
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Scales splits so they add up to new_total, keeping their ratios.
+ * Rounding dust goes to the largest split.
+ */
+fn rescale_artist_splits(artist_splits: &mut Vec<ArtistPercentage>, new_total: u64) -> Result<()> {
+    royalty_math::rescale(artist_splits, new_total)
+        .map_err(|_| ErrorCode::InvalidArtistDilution)?;
+
+    artist_splits.retain(|split| split.allocated_percentage > 0);
+
+    Ok(())
+}
+
+// Reallocs a program account to new_len, topping up rent from payer
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    if new_len <= account.data_len() {
+        return Ok(());
+    }
+
+    let rent_exempt_balance = Rent::get()?.minimum_balance(new_len);
+    if rent_exempt_balance > account.lamports() {
+        invoke(
+            &system_instruction::transfer(
+                payer.key,
+                account.key,
+                rent_exempt_balance - account.lamports(),
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+
+    account.realloc(new_len, false)
+}
+
+// Grows the config and artist ledger to hold the config's artist splits,
+// payer covers the rent
+fn grow_for_artist_splits<'info>(
+    collection_config: &Account<'info, CollectionConfiguration>,
+    artist_balance_ledger: &Account<'info, ArtistBalanceLedger>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    grow_account(
+        &collection_config.to_account_info(),
+        CollectionConfiguration::space(collection_config.artist_splits.len()),
+        payer,
+        system_program,
+    )?;
+
+    let artist_count = artist_balance_ledger.size as usize
+        + artist_balance_ledger.missing_artists(&collection_config.artist_splits);
+    grow_account(
+        &artist_balance_ledger.to_account_info(),
+        ArtistBalanceLedger::space(artist_count),
+        payer,
+        system_program,
+    )
+}
+
+fn verify_stream_percentages(
+    artist_mint_percentage: u64,
+    label_mint_percentage: u64,
+    artist_secondary_percentage: u64,
+    label_secondary_percentage: u64,
+    artist_licensing_percentage: u64,
+    label_licensing_percentage: u64,
+) -> Result<()> {
+    StreamSplit::new(artist_mint_percentage, label_mint_percentage)
+        .and(StreamSplit::new(
+            artist_secondary_percentage,
+            label_secondary_percentage,
+        ))
+        .and(StreamSplit::new(
+            artist_licensing_percentage,
+            label_licensing_percentage,
+        ))
+        .map_err(|_| ErrorCode::InvalidCollectionConfig)?;
+
+    Ok(())
+}
+
+// Artist splits must be unique and add up to expected_total
+fn verify_artist_splits(artist_splits: &[ArtistPercentage], expected_total: u64) -> Result<()> {
+    royalty_math::validate_split_table(artist_splits, expected_total)
+        .map_err(|_| ErrorCode::InvalidCollectionConfig)?;
+
+    Ok(())
+}
+
+// Valid configs never overflow or divide by zero, these only show up on
+// percentages or splits that got past validation
+fn math_error(error: royalty_math::Error) -> ErrorCode {
+    match error {
+        royalty_math::Error::Overflow => ErrorCode::RoyaltyOverflow,
+        royalty_math::Error::NoRecipients => ErrorCode::RoyaltyDivisionByZero,
+        _ => ErrorCode::InvalidCollectionConfig,
+    }
+}
+
 #[error]
 pub enum ErrorCode {
     #[msg("Error: NFT address not found in ledger")]
@@ -587,4 +1779,92 @@ pub enum ErrorCode {
     ArtistLedgerNotInitialized,
     #[msg("Error: Cannot pay Licensing as no NFTs were minted in collection")]
     NoNftsInCollection,
+    #[msg("Error: Artist already approved this config update")]
+    ConfigUpdateAlreadyApproved,
+    #[msg("Error: Config update is missing artist approvals")]
+    ConfigUpdateMissingApprovals,
+    #[msg("Error: Config update timelock has not passed")]
+    ConfigUpdateTimelockActive,
+    #[msg("Error: Invalid artist split transfer")]
+    InvalidArtistSplitTransfer,
+    #[msg("Error: Invalid artist addition or removal")]
+    InvalidArtistDilution,
+    #[msg("Error: Missing artist signatures")]
+    MissingArtistSignatures,
+    #[msg("Error: Account is already on the current version")]
+    AccountAlreadyMigrated,
+    #[msg("Error: Account layout is not a known version")]
+    UnknownAccountVersion,
+    #[msg("Error: Invalid Holder Governance parameters")]
+    InvalidGovernanceConfig,
+    #[msg("Error: Voting period for this proposal has ended")]
+    VotingPeriodEnded,
+    #[msg("Error: Voting period for this proposal is still active")]
+    VotingPeriodActive,
+    #[msg("Error: NFT was added after the proposal snapshot")]
+    NftNotInSnapshot,
+    #[msg("Error: NFT already voted on this proposal")]
+    NftAlreadyVoted,
+    #[msg("Error: Proposal did not reach quorum")]
+    QuorumNotReached,
+    #[msg("Error: Proposal was rejected by holders")]
+    ProposalRejected,
+    #[msg("Error: Proposal was already executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Error: NFT is already in the collection")]
+    NftAlreadyInCollection,
+    #[msg("Error: Invalid license terms")]
+    InvalidLicenseTerms,
+    #[msg("Error: License is not for this licensee or collection")]
+    InvalidLicense,
+    #[msg("Error: License is not active at the current slot")]
+    LicenseNotActive,
+    #[msg("Error: Licensee does not hold the license token")]
+    LicenseTokenNotIssued,
+    #[msg("Error: License lapsed without being renewed")]
+    LicenseLapsed,
+    #[msg("Error: License is still within its term or grace period")]
+    LicenseNotLapsed,
+    #[msg("Error: Fee schedule must price every usage type from volume 0, once per volume")]
+    InvalidFeeSchedule,
+    #[msg("Error: Payment is below the scheduled licensing fee")]
+    LicensingFeeTooLow,
+    #[msg("Error: Price feed is not the fee schedule's or not a price account")]
+    InvalidPriceFeed,
+    #[msg("Error: Price feed has no recent price")]
+    StalePrice,
+    #[msg("Error: Price feed confidence interval is too wide")]
+    PriceConfidenceTooWide,
+    #[msg("Error: Fee schedule is not priced in USD")]
+    FeeScheduleNotInUsd,
+    #[msg("Error: Fee in lamports moved past the slippage tolerance")]
+    SlippageExceeded,
+    #[msg("Error: License has too many payments on hold")]
+    PendingPaymentsFull,
+    #[msg("Error: No such pending payment")]
+    InvalidPendingPayment,
+    #[msg("Error: Payment hold is over")]
+    PaymentHoldOver,
+    #[msg("Error: No held payments are due")]
+    NoPaymentsToRelease,
+    #[msg("Error: Usage must be reported by the license's reporter")]
+    InvalidReporter,
+    #[msg("Error: License isn't paid per use")]
+    UsageNotMetered,
+    #[msg("Error: Usage period was already reported")]
+    InvalidUsagePeriod,
+    #[msg("Error: Invalid usage report")]
+    InvalidUsageReport,
+    #[msg("Error: No usage fee is owed")]
+    NoUsageFeeOwed,
+    #[msg("Error: Invalid secondary sale")]
+    InvalidSecondarySale,
+    #[msg("Error: Reporter is not approved to record sales")]
+    UnauthorizedSaleReporter,
+    #[msg("Error: Royalty amount overflowed")]
+    RoyaltyOverflow,
+    #[msg("Error: Royalty split allocates nothing to divide by")]
+    RoyaltyDivisionByZero,
+    #[msg("Error: Every payment for this license was refunded")]
+    LicenseNotPaid,
 }
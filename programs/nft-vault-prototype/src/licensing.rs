@@ -0,0 +1,912 @@
+//! Licensing deals
+//!
+//! Every deal gets a License account, agreed by the collection authority
+//! and the licensee, so sync licenses can be audited on-chain. Licensing
+//! fees are paid against a license and tracked on it.
+//!
+//! The first payment mints the licensee a license token and freezes it in
+//! their account, as proof of rights that can't be transferred. Other
+//! programs check a license through a CPI to verify_license.
+//!
+//! Licenses run from start_slot to end_slot. renew_license pays the fee
+//! again for another term of the same length. Past end_slot the license
+//! keeps working for its grace period, after that it has lapsed and can
+//! only be marked so with lapse_license.
+//!
+//! pay_licensing_fee_usd pays a USD amount in lamports, at the price feed of
+//! a USD fee schedule.
+//!
+//! A license can name the tracks it covers, the NFTs in licensed_nfts.
+//! track_share_percentage of the holder share of its payments is split
+//! between them, the rest goes to the whole collection as usual.
+//!
+//! When the fee schedule sets a hold period, payments wait on the license
+//! in pending_payments. The collection authority can refund them to the
+//! licensee until the hold is over, after that release_licensing_payments
+//! splits them into the ledgers where they can be withdrawn. A refund
+//! undoes what the payment paid for.
+//!
+//! Streaming and broadcast licenses can be paid per use instead. Their
+//! reporter, the licensee unless the terms name someone else, sends a
+//! report_usage for every period with the uses of each track. The fee at
+//! the license's per_use_rate adds up in outstanding_fee, which the
+//! licensee pays through the licensing split with pay_usage_fee.
+
+use crate::*;
+use anchor_spl::token::{self, Token};
+
+// Longest terms URI a license can hold, in bytes
+pub const MAX_TERMS_URI_LEN: usize = 200;
+
+// Most NFTs a license can name
+pub const MAX_LICENSED_NFTS: usize = 8;
+
+// Most payments a license can have on hold at once
+pub const MAX_PENDING_PAYMENTS: usize = 4;
+
+// Most tracks a usage report can list
+pub const MAX_USAGE_TRACKS: usize = 16;
+
+// Mint and freeze authority of every license token
+pub const LICENSE_AUTHORITY_SEED: &[u8] = b"license-authority";
+pub const LICENSE_AUTHORITY_BUMP: u8 = 254;
+
+pub fn create_license(
+    ctx: Context<CreateLicense>,
+    content_hash: [u8; 32],
+    terms_uri: String,
+    fee: u64,
+    usage_type: UsageType,
+    start_slot: u64,
+    end_slot: u64,
+    grace_slots: u64,
+    volume: u64,
+    licensed_nfts: Vec<Pubkey>,
+    track_share_percentage: u64,
+    reporter: Option<Pubkey>,
+    per_use_rate: u64,
+) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    if terms_uri.is_empty() || terms_uri.len() > MAX_TERMS_URI_LEN || start_slot >= end_slot {
+        return Err(ErrorCode::InvalidLicenseTerms.into());
+    }
+
+    // A track share needs tracks to go to
+    if licensed_nfts.len() > MAX_LICENSED_NFTS
+        || track_share_percentage > ONE_HUNDRED_PERCENT
+        || (track_share_percentage > 0 && licensed_nfts.is_empty())
+    {
+        return Err(ErrorCode::InvalidLicenseTerms.into());
+    }
+
+    // Every track is credited on each payment, so it must be in the ledger
+    for (i, nft) in licensed_nfts.iter().enumerate() {
+        if licensed_nfts[i + 1..].contains(nft) {
+            return Err(ErrorCode::InvalidLicenseTerms.into());
+        }
+
+        ctx.accounts.nft_balance_ledger.snapshot_position(*nft)?;
+    }
+
+    let license = &mut ctx.accounts.license;
+    license.version = ACCOUNT_VERSION;
+    license.licensee = ctx.accounts.licensee.key();
+    license.collection = ctx.accounts.collection_config.key();
+    license.terms_uri = terms_uri;
+    license.content_hash = content_hash;
+    license.fee = fee;
+    license.usage_type = usage_type;
+    license.start_slot = start_slot;
+    license.end_slot = end_slot;
+    license.term_slots = end_slot - start_slot;
+    license.grace_slots = grace_slots;
+    license.status = LicenseStatus::Active;
+    license.volume = volume;
+    license.fee_tier = None;
+    license.licensed_nfts = licensed_nfts.clone();
+    license.track_share_percentage = track_share_percentage;
+    license.pending_payments = vec![];
+    license.reporter = reporter.unwrap_or(license.licensee);
+    license.per_use_rate = per_use_rate;
+    license.last_usage_period = None;
+    license.total_uses = 0;
+    license.outstanding_fee = 0;
+    license.paid_to_date = 0;
+    license.token_mint = ctx.accounts.license_mint.key();
+    license.token_account = ctx.accounts.license_token_account.key();
+
+    emit!(LicenseCreated {
+        license: license.key(),
+        licensee: license.licensee,
+        token_mint: license.token_mint,
+        content_hash,
+        fee,
+        usage_type,
+        start_slot,
+        end_slot,
+        grace_slots,
+        volume,
+        licensed_nfts,
+        track_share_percentage,
+        reporter: license.reporter,
+        per_use_rate,
+    });
+
+    Ok(())
+}
+
+/**
+ * Pays the license fee for the next term through the licensing split.
+ * Terms follow each other, renewing early or in the grace period extends
+ * from the current end_slot.
+ */
+pub fn renew_license(ctx: Context<PayLicensingFee>) -> ProgramResult {
+    ctx.accounts.license.verify_payment(
+        ctx.accounts.from.key(),
+        ctx.accounts.collection_config.key(),
+        Clock::get()?.slot,
+    )?;
+
+    let fee = ctx.accounts.license.fee;
+    let term_slots = ctx.accounts.license.term_slots;
+    ctx.accounts.settle_licensing_fee(
+        fee,
+        PaymentPurpose::Renewal { term_slots },
+        ctx.remaining_accounts.first(),
+    )?;
+
+    let license = &mut ctx.accounts.license;
+    license.end_slot = license
+        .end_slot
+        .checked_add(term_slots)
+        .ok_or(ErrorCode::InvalidLicenseTerms)?;
+
+    emit!(LicenseRenewed {
+        license: license.key(),
+        licensee: license.licensee,
+        fee,
+        end_slot: license.end_slot,
+    });
+
+    Ok(())
+}
+
+/**
+ * Pays usd_cents, converted to lamports at the fee schedule's price feed.
+ * The licensee quotes the lamports they expect to pay, and the payment
+ * fails if the conversion comes to more than slippage_bps over the quote.
+ */
+pub fn pay_licensing_fee_usd(
+    ctx: Context<PayLicensingFee>,
+    usd_cents: u64,
+    quoted_lamports: u64,
+    slippage_bps: u64,
+) -> ProgramResult {
+    let slot = Clock::get()?.slot;
+    ctx.accounts.license.verify_payment(
+        ctx.accounts.from.key(),
+        ctx.accounts.collection_config.key(),
+        slot,
+    )?;
+
+    let price_feed = ctx.remaining_accounts.first();
+    let price = match ctx.accounts.fee_schedule.currency {
+        FeeCurrency::Usd(config) => config.price(price_feed, slot)?,
+        FeeCurrency::Lamports => return Err(ErrorCode::FeeScheduleNotInUsd.into()),
+    };
+    let lamports = price.usd_to_lamports(usd_cents)?;
+
+    let max_lamports = quoted_lamports as u128
+        * (ONE_HUNDRED_PERCENT as u128 + slippage_bps as u128)
+        / ONE_HUNDRED_PERCENT as u128;
+    if lamports as u128 > max_lamports {
+        return Err(ErrorCode::SlippageExceeded.into());
+    }
+
+    ctx.accounts
+        .settle_licensing_fee(lamports, PaymentPurpose::Fee, price_feed)?;
+
+    emit!(LicenseFeeConverted {
+        license: ctx.accounts.license.key(),
+        usd_cents,
+        lamports,
+        price: price.price,
+        expo: price.expo,
+    });
+
+    Ok(())
+}
+
+// Anyone can release the payments whose hold is over
+pub fn release_licensing_payments(ctx: Context<ReleaseLicensingPayments>) -> ProgramResult {
+    if ctx.accounts.license.collection != ctx.accounts.collection_config.key() {
+        return Err(ErrorCode::InvalidLicense.into());
+    }
+
+    let slot = Clock::get()?.slot;
+    let (released, held): (Vec<PendingPayment>, Vec<PendingPayment>) = ctx
+        .accounts
+        .license
+        .pending_payments
+        .iter()
+        .partition(|payment| payment.release_slot <= slot);
+    if released.is_empty() {
+        return Err(ErrorCode::NoPaymentsToRelease.into());
+    }
+
+    for payment in released {
+        distribute_licensing_fee(
+            &ctx.accounts.collection_config,
+            &mut ctx.accounts.artist_balance_ledger,
+            &mut ctx.accounts.nft_balance_ledger,
+            &ctx.accounts.license,
+            payment.amount,
+        )?;
+
+        emit!(LicensePaymentReleased {
+            license: ctx.accounts.license.key(),
+            amount: payment.amount,
+        });
+    }
+
+    ctx.accounts.license.pending_payments = held;
+
+    Ok(())
+}
+
+/**
+ * Returns a held payment from the vault to the licensee and undoes what it
+ * paid for: a refunded renewal takes its term back off end_slot, and a
+ * refunded usage fee is owed again. payment_index is its position in
+ * pending_payments.
+ *
+ * The collection authority can refund while the hold runs. After that the
+ * authority or the licensee can refund a payment that can't be released,
+ * because the license names a track missing from the ledger.
+ */
+pub fn refund_licensing_payment(
+    ctx: Context<RefundLicensingPayment>,
+    payment_index: u64,
+) -> ProgramResult {
+    let refunder = ctx.accounts.refunder.key();
+    let by_authority = refunder == ctx.accounts.collection_config.collection_authority;
+    let license = &mut ctx.accounts.license;
+    if !by_authority && refunder != license.licensee {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    if license.collection != ctx.accounts.collection_config.key() {
+        return Err(ErrorCode::InvalidLicense.into());
+    }
+
+    let index = payment_index as usize;
+    let payment = *license
+        .pending_payments
+        .get(index)
+        .ok_or(ErrorCode::InvalidPendingPayment)?;
+    if Clock::get()?.slot < payment.release_slot {
+        if !by_authority {
+            return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+        }
+    } else if license.releasable(&ctx.accounts.nft_balance_ledger) {
+        return Err(ErrorCode::PaymentHoldOver.into());
+    }
+
+    license.pending_payments.remove(index);
+    license.paid_to_date = license
+        .paid_to_date
+        .checked_sub(payment.amount)
+        .ok_or(ErrorCode::InvalidLicense)?;
+    match payment.purpose {
+        PaymentPurpose::Fee => {}
+        PaymentPurpose::Renewal { term_slots } => {
+            license.end_slot = license
+                .end_slot
+                .checked_sub(term_slots)
+                .ok_or(ErrorCode::InvalidLicense)?;
+        }
+        PaymentPurpose::UsageFee => {
+            license.outstanding_fee = license
+                .outstanding_fee
+                .checked_add(payment.amount)
+                .ok_or(ErrorCode::InvalidLicense)?;
+        }
+    }
+
+    let ix = system_instruction::transfer(
+        &ctx.accounts.pda_vault.key(),
+        ctx.accounts.licensee.key,
+        payment.amount,
+    );
+    invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.pda_vault.to_account_info(),
+            ctx.accounts.licensee.to_account_info(),
+        ],
+        &[&[b"vault", &[255]]],
+    )?;
+
+    emit!(LicensePaymentRefunded {
+        license: license.key(),
+        licensee: license.licensee,
+        amount: payment.amount,
+        paid_to_date: license.paid_to_date,
+    });
+
+    Ok(())
+}
+
+/**
+ * Records the uses of each track in a period, reported by the license's
+ * reporter, and adds their fee to the license's outstanding_fee. Periods
+ * are numbered by the reporter and reported in increasing order, once.
+ * Tracks must be NFTs of the collection, and among the licensed NFTs of
+ * licenses that name them.
+ */
+pub fn report_usage(
+    ctx: Context<ReportUsage>,
+    period: u64,
+    tracks: Vec<TrackUsage>,
+) -> ProgramResult {
+    let license = &mut ctx.accounts.license;
+    let (total_uses, fee) = license.record_usage(
+        ctx.accounts.reporter.key(),
+        period,
+        &tracks,
+        &ctx.accounts.nft_balance_ledger,
+    )?;
+
+    let usage_report = &mut ctx.accounts.usage_report;
+    usage_report.version = ACCOUNT_VERSION;
+    usage_report.license = license.key();
+    usage_report.period = period;
+    usage_report.tracks = tracks.clone();
+    usage_report.total_uses = total_uses;
+    usage_report.fee = fee;
+    usage_report.reported_slot = Clock::get()?.slot;
+
+    emit!(UsageReported {
+        license: license.key(),
+        usage_report: usage_report.key(),
+        period,
+        tracks,
+        total_uses,
+        fee,
+        outstanding_fee: license.outstanding_fee,
+    });
+
+    Ok(())
+}
+
+/**
+ * Pays the license's outstanding usage fee through the licensing split.
+ * The fee was agreed at the per-use rate, so the fee schedule's price
+ * doesn't apply. Usage stays payable after the license lapses.
+ */
+pub fn pay_usage_fee(ctx: Context<PayLicensingFee>) -> ProgramResult {
+    let license = &ctx.accounts.license;
+    if license.licensee != ctx.accounts.from.key()
+        || license.collection != ctx.accounts.collection_config.key()
+    {
+        return Err(ErrorCode::InvalidLicense.into());
+    }
+
+    let amount = license.outstanding_fee;
+    if amount == 0 {
+        return Err(ErrorCode::NoUsageFeeOwed.into());
+    }
+
+    let fee_tier = ctx
+        .accounts
+        .fee_schedule
+        .tier(license.usage_type, license.volume)?;
+    ctx.accounts
+        .settle_payment(amount, PaymentPurpose::UsageFee, fee_tier)?;
+
+    ctx.accounts.license.outstanding_fee = 0;
+
+    Ok(())
+}
+
+// Anyone can mark a license lapsed once its grace period is over
+pub fn lapse_license(ctx: Context<LapseLicense>) -> ProgramResult {
+    let license = &mut ctx.accounts.license;
+
+    if license.status == LicenseStatus::Lapsed {
+        return Err(ErrorCode::LicenseLapsed.into());
+    }
+
+    if Clock::get()?.slot <= license.grace_end_slot() {
+        return Err(ErrorCode::LicenseNotLapsed.into());
+    }
+
+    license.status = LicenseStatus::Lapsed;
+
+    emit!(LicenseLapsed {
+        license: license.key(),
+        licensee: license.licensee,
+        end_slot: license.end_slot,
+    });
+
+    Ok(())
+}
+
+/**
+ * Succeeds when the licensee holds the license token and the license is
+ * active, for other programs to gate on through CPI. The token stays
+ * frozen with the licensee when payments are refunded, so a license
+ * whose every payment was refunded fails here.
+ */
+pub fn verify_license(ctx: Context<VerifyLicense>) -> ProgramResult {
+    let license = &ctx.accounts.license;
+    let token_account = &ctx.accounts.license_token_account;
+
+    if license.licensee != ctx.accounts.licensee.key() {
+        return Err(ErrorCode::InvalidLicense.into());
+    }
+
+    if token_account.owner != license.licensee || token_account.amount == 0 {
+        return Err(ErrorCode::LicenseTokenNotIssued.into());
+    }
+
+    if license.paid_to_date == 0 {
+        return Err(ErrorCode::LicenseNotPaid.into());
+    }
+
+    license.verify_active(Clock::get()?.slot)?;
+
+    Ok(())
+}
+
+/**
+ * Splits a licensing payment already in the vault into the ledgers, with
+ * the licensing percentages in force. The licensed tracks get their share
+ * of the holder total first.
+ */
+pub fn distribute_licensing_fee(
+    collection_config: &CollectionConfiguration,
+    artist_balance_ledger: &mut ArtistBalanceLedger,
+    nft_balance_ledger: &mut NftBalanceLedger,
+    license: &Account<License>,
+    amount: u64,
+) -> ProgramResult {
+    let amounts = StreamSplit {
+        artist: collection_config.artist_licensing_percentage,
+        holders: collection_config.label_licensing_percentage,
+    }
+    .divide(amount)
+    .map_err(math_error)?;
+
+    // Calculate and distribute payment to Artists ledger
+    let amount_to_artists = amounts.artist_total;
+    artist_balance_ledger
+        .distribute_artist_payments(amount_to_artists, collection_config.artist_splits.clone())?;
+
+    // Calculate and distribute payment to Label ledger
+    let amount_to_label = amounts.holder_total;
+    let amount_to_tracks = royalty_math::bps_of(amount_to_label, license.track_share_percentage)
+        .map_err(math_error)?;
+    let per_track_share = if amount_to_tracks > 0 {
+        nft_balance_ledger.credit_nfts(&license.licensed_nfts, amount_to_tracks)?
+    } else {
+        0
+    };
+    let per_nft_share =
+        nft_balance_ledger.distribute_payments(amount_to_label - amount_to_tracks)?;
+
+    emit!(RoyaltiesDistributed {
+        stream: RoyaltyStream::Licensing,
+        amount,
+        artist_total: amount_to_artists,
+        holder_total: amount_to_label,
+        per_nft_share,
+        nft_count: nft_balance_ledger.size,
+    });
+
+    if amount_to_tracks > 0 {
+        emit!(LicensedTracksCredited {
+            license: license.key(),
+            nfts: license.licensed_nfts.clone(),
+            amount: amount_to_tracks,
+            per_nft_share: per_track_share,
+        });
+    }
+
+    Ok(())
+}
+
+/**
+ * Mints the license token into the licensee's account and freezes it,
+ * once. Later payments leave the token as it is.
+ */
+pub fn issue_license_token<'info>(
+    license_mint: &Account<'info, Mint>,
+    license_token_account: &Account<'info, TokenAccount>,
+    license_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    if license_token_account.amount > 0 {
+        return Ok(());
+    }
+
+    let signer_seeds: &[&[&[u8]]] = &[&[LICENSE_AUTHORITY_SEED, &[LICENSE_AUTHORITY_BUMP]]];
+    // anchor_spl's mint_to and freeze_account leave the token program out of
+    // the CPI accounts, which program-test can't run, so the instructions
+    // are built here and passed it like the system program on transfers
+    let accounts = [
+        license_mint.to_account_info(),
+        license_token_account.to_account_info(),
+        license_authority.clone(),
+        token_program.clone(),
+    ];
+
+    let ix = spl_token::instruction::mint_to(
+        &token::ID,
+        &license_mint.key(),
+        &license_token_account.key(),
+        license_authority.key,
+        &[],
+        1,
+    )?;
+    invoke_signed(&ix, &accounts, signer_seeds)?;
+
+    let ix = spl_token::instruction::freeze_account(
+        &token::ID,
+        &license_token_account.key(),
+        &license_mint.key(),
+        license_authority.key,
+        &[],
+    )?;
+    invoke_signed(&ix, &accounts, signer_seeds)
+}
+
+#[derive(Accounts)]
+#[instruction(content_hash: [u8; 32])]
+pub struct CreateLicense<'info> {
+    #[account(
+        init,
+        payer = licensee,
+        space = License::SIZE,
+        seeds = [b"license", licensee.key().as_ref(), content_hash.as_ref()],
+        bump
+    )]
+    pub license: Account<'info, License>,
+    #[account(
+        init,
+        payer = licensee,
+        mint::decimals = 0,
+        mint::authority = license_authority,
+        mint::freeze_authority = license_authority,
+        seeds = [b"license-mint", license.key().as_ref()],
+        bump
+    )]
+    pub license_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = licensee,
+        token::mint = license_mint,
+        token::authority = licensee,
+        seeds = [b"license-token", license.key().as_ref()],
+        bump
+    )]
+    pub license_token_account: Account<'info, TokenAccount>,
+    #[account(seeds = [LICENSE_AUTHORITY_SEED], bump = LICENSE_AUTHORITY_BUMP)]
+    pub license_authority: UncheckedAccount<'info>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    #[account(seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    pub collection_authority: Signer<'info>,
+    #[account(mut)]
+    pub licensee: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct LapseLicense<'info> {
+    #[account(mut)]
+    pub license: Account<'info, License>,
+}
+
+#[derive(Accounts)]
+#[instruction(period: u64)]
+pub struct ReportUsage<'info> {
+    #[account(
+        init,
+        payer = reporter,
+        space = UsageReport::SIZE,
+        seeds = [b"usage-report", license.key().as_ref(), period.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub usage_report: Account<'info, UsageReport>,
+    #[account(mut)]
+    pub license: Account<'info, License>,
+    #[account(seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseLicensingPayments<'info> {
+    #[account(mut)]
+    pub license: Account<'info, License>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255)]
+    pub artist_balance_ledger: Account<'info, ArtistBalanceLedger>,
+    #[account(mut, seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+}
+
+#[derive(Accounts)]
+pub struct RefundLicensingPayment<'info> {
+    #[account(mut)]
+    pub license: Account<'info, License>,
+    #[account(mut, address = license.licensee)]
+    pub licensee: SystemAccount<'info>,
+    #[account(mut, seeds = [b"vault"], bump = 255)]
+    pub pda_vault: SystemAccount<'info>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    #[account(seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    pub refunder: Signer<'info>, // collection authority, or licensee once the hold is over
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyLicense<'info> {
+    pub license: Account<'info, License>,
+    #[account(address = license.token_account)]
+    pub license_token_account: Account<'info, TokenAccount>,
+    pub licensee: UncheckedAccount<'info>,
+}
+
+/**
+ * One licensing deal, fees are in lamports
+ *
+ * content_hash is the hash of the terms document at terms_uri, a licensee
+ * has one license per set of terms
+ */
+#[account]
+pub struct License {
+    pub version: u8,
+    pub licensee: Pubkey,
+    pub collection: Pubkey, // collection config the fees are split by
+    pub terms_uri: String,
+    pub content_hash: [u8; 32],
+    pub fee: u64, // agreed fee for the whole term
+    pub usage_type: UsageType,
+    pub start_slot: u64,
+    pub end_slot: u64,    // last slot of the current term
+    pub term_slots: u64,  // each renewal moves end_slot by a term
+    pub grace_slots: u64, // still valid this long after end_slot
+    pub status: LicenseStatus,
+    pub volume: u64,               // licensed units, priced by the fee schedule tiers
+    pub fee_tier: Option<FeeTier>, // tier the last payment was priced at
+    pub licensed_nfts: Vec<Pubkey>, // tracks the license covers
+    pub track_share_percentage: u64, // of the holder share, split between licensed_nfts
+    pub pending_payments: Vec<PendingPayment>, // paid but not yet in the ledgers
+    pub reporter: Pubkey,          // signs usage reports
+    pub per_use_rate: u64,         // lamports per reported use, 0 if unmetered
+    pub last_usage_period: Option<u64>,
+    pub total_uses: u64,
+    pub outstanding_fee: u64, // reported usage not paid for yet
+    pub paid_to_date: u64,
+    pub token_mint: Pubkey,
+    pub token_account: Pubkey, // licensee's account, frozen once the token is minted
+}
+
+impl License {
+    // discriminator + version + licensee + collection + terms URI + hash
+    // + fee + usage type
+    const TERMS_SIZE: usize = 8 + 1 + 32 + 32 + 4 + MAX_TERMS_URI_LEN + 32 + 8 + 1;
+    // + start, end, term and grace slots + status + volume + fee tier
+    const SCHEDULE_SIZE: usize = Self::TERMS_SIZE + 4 * 8 + 1 + 8 + 1 + FeeTier::SIZE;
+    // + licensed NFTs + track share + pending payments
+    const TRACKS_SIZE: usize = Self::SCHEDULE_SIZE
+        + 4
+        + MAX_LICENSED_NFTS * 32
+        + 8
+        + 4
+        + MAX_PENDING_PAYMENTS * PendingPayment::SIZE;
+    // + reporter + per-use rate + last usage period + total uses
+    // + outstanding fee
+    const USAGE_SIZE: usize = Self::TRACKS_SIZE + 32 + 8 + 9 + 8 + 8;
+    // + paid to date + token mint and account
+    pub const SIZE: usize = Self::USAGE_SIZE + 8 + 32 + 32;
+
+    // Fees can only be paid to the collection the license was agreed for,
+    // by its licensee, while it runs
+    pub fn verify_payment(&self, licensee: Pubkey, collection: Pubkey, slot: u64) -> Result<()> {
+        if self.licensee != licensee || self.collection != collection {
+            return Err(ErrorCode::InvalidLicense.into());
+        }
+
+        self.verify_active(slot)
+    }
+
+    // Valid from start_slot until the grace period after end_slot is over
+    pub fn verify_active(&self, slot: u64) -> Result<()> {
+        if self.status == LicenseStatus::Lapsed || slot > self.grace_end_slot() {
+            return Err(ErrorCode::LicenseLapsed.into());
+        }
+
+        if slot < self.start_slot {
+            return Err(ErrorCode::LicenseNotActive.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn grace_end_slot(&self) -> u64 {
+        self.end_slot.saturating_add(self.grace_slots)
+    }
+
+    pub fn hold_payment(&mut self, payment: PendingPayment) -> Result<()> {
+        if self.pending_payments.len() >= MAX_PENDING_PAYMENTS {
+            return Err(ErrorCode::PendingPaymentsFull.into());
+        }
+
+        self.pending_payments.push(payment);
+
+        Ok(())
+    }
+
+    /**
+     * Checks a reporter's usage report for period and adds it to the
+     * license, the fee becomes owed. Returns the report's total uses and
+     * fee.
+     */
+    pub fn record_usage(
+        &mut self,
+        reporter: Pubkey,
+        period: u64,
+        tracks: &[TrackUsage],
+        nft_balance_ledger: &NftBalanceLedger,
+    ) -> Result<(u64, u64)> {
+        if self.reporter != reporter {
+            return Err(ErrorCode::InvalidReporter.into());
+        }
+
+        if self.per_use_rate == 0 {
+            return Err(ErrorCode::UsageNotMetered.into());
+        }
+
+        if matches!(self.last_usage_period, Some(last) if period <= last) {
+            return Err(ErrorCode::InvalidUsagePeriod.into());
+        }
+
+        if tracks.is_empty() || tracks.len() > MAX_USAGE_TRACKS {
+            return Err(ErrorCode::InvalidUsageReport.into());
+        }
+
+        let mut total_uses: u64 = 0;
+        for (i, track) in tracks.iter().enumerate() {
+            if tracks[i + 1..].iter().any(|other| other.nft == track.nft) {
+                return Err(ErrorCode::InvalidUsageReport.into());
+            }
+
+            nft_balance_ledger.snapshot_position(track.nft)?;
+            if !self.licensed_nfts.is_empty() && !self.licensed_nfts.contains(&track.nft) {
+                return Err(ErrorCode::InvalidNft.into());
+            }
+
+            total_uses = total_uses
+                .checked_add(track.uses)
+                .ok_or(ErrorCode::InvalidUsageReport)?;
+        }
+
+        let fee = total_uses
+            .checked_mul(self.per_use_rate)
+            .ok_or(ErrorCode::InvalidUsageReport)?;
+        self.last_usage_period = Some(period);
+        self.total_uses = self
+            .total_uses
+            .checked_add(total_uses)
+            .ok_or(ErrorCode::InvalidUsageReport)?;
+        self.outstanding_fee = self
+            .outstanding_fee
+            .checked_add(fee)
+            .ok_or(ErrorCode::InvalidUsageReport)?;
+
+        Ok((total_uses, fee))
+    }
+
+    // Payments can only be split while every licensed track is in the ledger
+    pub fn releasable(&self, nft_balance_ledger: &NftBalanceLedger) -> bool {
+        self.licensed_nfts
+            .iter()
+            .all(|nft| nft_balance_ledger.snapshot_position(*nft).is_ok())
+    }
+}
+
+/// Licensing payment in the vault, refundable until release_slot
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PendingPayment {
+    pub amount: u64,
+    pub purpose: PaymentPurpose, // undone if the payment is refunded
+    pub release_slot: u64,
+}
+
+impl PendingPayment {
+    // amount + purpose + release slot
+    pub const SIZE: usize = 8 + PaymentPurpose::SIZE + 8;
+}
+
+/// What a licensing payment paid for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PaymentPurpose {
+    Fee,
+    Renewal { term_slots: u64 }, // moved end_slot on by term_slots
+    UsageFee,                    // settled the outstanding fee
+}
+
+impl PaymentPurpose {
+    // variant + term slots
+    pub const SIZE: usize = 1 + 8;
+}
+
+/// Uses of each track a reporter reported for one period of a license
+#[account]
+pub struct UsageReport {
+    pub version: u8,
+    pub license: Pubkey,
+    pub period: u64,
+    pub tracks: Vec<TrackUsage>,
+    pub total_uses: u64,
+    pub fee: u64, // total_uses at the license's per-use rate
+    pub reported_slot: u64,
+}
+
+impl UsageReport {
+    // discriminator + version + license + period + vec prefix + tracks
+    // + total uses + fee + reported slot
+    pub const SIZE: usize = 8 + 1 + 32 + 8 + 4 + MAX_USAGE_TRACKS * TrackUsage::SIZE + 8 + 8 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TrackUsage {
+    pub nft: Pubkey,
+    pub uses: u64, // plays or airings in the period
+}
+
+impl TrackUsage {
+    // NFT + uses
+    pub const SIZE: usize = 32 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum UsageType {
+    Commercial,
+    Broadcast,
+    Games,
+    Personal,
+}
+
+impl UsageType {
+    pub const ALL: [UsageType; 4] = [
+        UsageType::Commercial,
+        UsageType::Broadcast,
+        UsageType::Games,
+        UsageType::Personal,
+    ];
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LicenseStatus {
+    Active,
+    Lapsed,
+}
@@ -0,0 +1,362 @@
+//! In place upgrades of accounts created before layouts were versioned
+//!
+//! Version 0 accounts have no version byte. Each migration reads the old
+//! layout, writes the current one and grows the account by the bytes the
+//! new layout needs, keeping the spare capacity it already had.
+
+use crate::*;
+use anchor_lang::Discriminator;
+
+pub fn migrate_collection_config(ctx: Context<MigrateCollectionConfig>) -> ProgramResult {
+    let account = ctx.accounts.collection_config.to_account_info();
+    let migrated = migrate_collection_config_data(&account.try_borrow_data()?)?;
+
+    write_migrated_account(
+        &account,
+        &migrated,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+    )
+}
+
+pub fn migrate_nft_balance_ledger(ctx: Context<MigrateNftBalanceLedger>) -> ProgramResult {
+    let account = ctx.accounts.nft_balance_ledger.to_account_info();
+    let migrated = migrate_nft_balance_ledger_data(&account.try_borrow_data()?)?;
+
+    write_migrated_account(
+        &account,
+        &migrated,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+    )
+}
+
+pub fn migrate_artist_balance_ledger(ctx: Context<MigrateArtistBalanceLedger>) -> ProgramResult {
+    let account = ctx.accounts.artist_balance_ledger.to_account_info();
+    let migrated = migrate_artist_balance_ledger_data(&account.try_borrow_data()?)?;
+
+    write_migrated_account(
+        &account,
+        &migrated,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+    )
+}
+
+// Migrations don't change what the accounts hold, so anyone can pay to run them
+#[derive(Accounts)]
+pub struct MigrateCollectionConfig<'info> {
+    #[account(mut, seeds = [b"collection-config"], bump = 254, owner = id())]
+    pub collection_config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateNftBalanceLedger<'info> {
+    #[account(mut, seeds = [b"nft-ledger"], bump = 255, owner = id())]
+    pub nft_balance_ledger: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateArtistBalanceLedger<'info> {
+    #[account(mut, seeds = [b"artist-ledger"], bump = 255, owner = id())]
+    pub artist_balance_ledger: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * Version 0 layouts, exactly as they were serialized before
+ * the version byte was added. Configs had no artist reserve yet.
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CollectionConfigurationV0 {
+    pub collection_authority: Pubkey,
+    pub artist_mint_percentage: u64,
+    pub label_mint_percentage: u64,
+    pub artist_secondary_percentage: u64,
+    pub label_secondary_percentage: u64,
+    pub artist_licensing_percentage: u64,
+    pub label_licensing_percentage: u64,
+    pub artist_splits: Vec<ArtistPercentage>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct NftBalanceLedgerV0 {
+    pub nft_balances: Vec<NftBalance>,
+    pub size: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ArtistBalanceLedgerV0 {
+    pub artist_balances: Vec<ArtistBalance>,
+    pub size: u64,
+}
+
+impl CollectionConfiguration {
+    fn is_consistent(&self) -> bool {
+        self.artist_mint_percentage + self.label_mint_percentage == ONE_HUNDRED_PERCENT
+            && self.artist_secondary_percentage + self.label_secondary_percentage
+                == ONE_HUNDRED_PERCENT
+            && self.artist_licensing_percentage + self.label_licensing_percentage
+                == ONE_HUNDRED_PERCENT
+    }
+}
+
+/**
+ * A version 0 account can start with a byte equal to the current version,
+ * so an account only counts as migrated if it also reads back consistently
+ * with the current layout.
+ */
+fn read_current<T: AccountDeserialize>(data: &[u8], is_consistent: impl Fn(&T) -> bool) -> bool {
+    if data.len() <= 8 || data[8] != ACCOUNT_VERSION {
+        return false;
+    }
+
+    match T::try_deserialize(&mut &data[..]) {
+        Ok(account) => is_consistent(&account),
+        Err(_) => false,
+    }
+}
+
+fn verify_discriminator<T: Discriminator>(data: &[u8]) -> Result<()> {
+    if data.len() < 8 || data[..8] != T::discriminator() {
+        return Err(ErrorCode::UnknownAccountVersion.into());
+    }
+
+    Ok(())
+}
+
+pub fn migrate_collection_config_data(data: &[u8]) -> Result<CollectionConfiguration> {
+    verify_discriminator::<CollectionConfiguration>(data)?;
+
+    if read_current(data, CollectionConfiguration::is_consistent) {
+        return Err(ErrorCode::AccountAlreadyMigrated.into());
+    }
+
+    let old = CollectionConfigurationV0::deserialize(&mut &data[8..])
+        .map_err(|_| ErrorCode::UnknownAccountVersion)?;
+
+    // Whatever the splits leave unallocated starts out in the reserve
+    let total_allocated = old
+        .artist_splits
+        .iter()
+        .map(|split| split.allocated_percentage)
+        .sum::<u64>();
+    let artist_reserve_percentage = ONE_HUNDRED_PERCENT
+        .checked_sub(total_allocated)
+        .ok_or(ErrorCode::UnknownAccountVersion)?;
+
+    let migrated = CollectionConfiguration {
+        version: ACCOUNT_VERSION,
+        collection_authority: old.collection_authority,
+        artist_mint_percentage: old.artist_mint_percentage,
+        label_mint_percentage: old.label_mint_percentage,
+        artist_secondary_percentage: old.artist_secondary_percentage,
+        label_secondary_percentage: old.label_secondary_percentage,
+        artist_licensing_percentage: old.artist_licensing_percentage,
+        label_licensing_percentage: old.label_licensing_percentage,
+        artist_splits: old.artist_splits,
+        artist_reserve_percentage,
+    };
+
+    if !migrated.is_consistent() {
+        return Err(ErrorCode::UnknownAccountVersion.into());
+    }
+
+    Ok(migrated)
+}
+
+pub fn migrate_nft_balance_ledger_data(data: &[u8]) -> Result<NftBalanceLedger> {
+    verify_discriminator::<NftBalanceLedger>(data)?;
+
+    if read_current(data, |ledger: &NftBalanceLedger| {
+        ledger.size as usize == ledger.nft_balances.len()
+    }) {
+        return Err(ErrorCode::AccountAlreadyMigrated.into());
+    }
+
+    let old = NftBalanceLedgerV0::deserialize(&mut &data[8..])
+        .map_err(|_| ErrorCode::UnknownAccountVersion)?;
+
+    if old.size as usize != old.nft_balances.len() {
+        return Err(ErrorCode::UnknownAccountVersion.into());
+    }
+
+    Ok(NftBalanceLedger {
+        version: ACCOUNT_VERSION,
+        nft_balances: old.nft_balances,
+        size: old.size,
+    })
+}
+
+pub fn migrate_artist_balance_ledger_data(data: &[u8]) -> Result<ArtistBalanceLedger> {
+    verify_discriminator::<ArtistBalanceLedger>(data)?;
+
+    if read_current(data, |ledger: &ArtistBalanceLedger| {
+        ledger.size as usize == ledger.artist_balances.len()
+    }) {
+        return Err(ErrorCode::AccountAlreadyMigrated.into());
+    }
+
+    let old = ArtistBalanceLedgerV0::deserialize(&mut &data[8..])
+        .map_err(|_| ErrorCode::UnknownAccountVersion)?;
+
+    if old.size as usize != old.artist_balances.len() {
+        return Err(ErrorCode::UnknownAccountVersion.into());
+    }
+
+    Ok(ArtistBalanceLedger {
+        version: ACCOUNT_VERSION,
+        artist_balances: old.artist_balances,
+        size: old.size,
+    })
+}
+
+fn write_migrated_account<'info, T: AccountSerialize + AnchorSerialize>(
+    account: &AccountInfo<'info>,
+    migrated: &T,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    // Keep the spare capacity the account had, plus the version byte
+    let serialized_len = 8 + migrated.try_to_vec()?.len();
+    let new_len = std::cmp::max(account.data_len() + 1, serialized_len);
+    grow_account(account, new_len, payer, system_program)?;
+
+    let mut data = account.try_borrow_mut_data()?;
+    migrated.try_serialize(&mut &mut data[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written with the layout and 9000 byte space initialize_collection had
+    // before accounts were versioned, one artist holding the whole share
+    const COLLECTION_CONFIG_V0: &[u8] =
+        include_bytes!("../tests/fixtures/collection_config_v0.bin");
+    const NFT_BALANCE_LEDGER_V0: &[u8] =
+        include_bytes!("../tests/fixtures/nft_balance_ledger_v0.bin");
+    const ARTIST_BALANCE_LEDGER_V0: &[u8] =
+        include_bytes!("../tests/fixtures/artist_balance_ledger_v0.bin");
+
+    fn migration_error<T>(result: Result<T>) -> ProgramError {
+        match result {
+            Ok(_) => panic!("migration should fail"),
+            Err(err) => err.into(),
+        }
+    }
+
+    fn serialize<T: AccountSerialize>(account: &T, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        account.try_serialize(&mut &mut data[..]).unwrap();
+        data
+    }
+
+    #[test]
+    fn migrates_collection_config_v0() {
+        let migrated = migrate_collection_config_data(COLLECTION_CONFIG_V0).unwrap();
+
+        assert_eq!(migrated.version, ACCOUNT_VERSION);
+        assert_eq!(
+            migrated.collection_authority.to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU"
+        );
+        assert_eq!(migrated.artist_mint_percentage, 8000);
+        assert_eq!(migrated.label_mint_percentage, 2000);
+        assert_eq!(migrated.artist_secondary_percentage, 5000);
+        assert_eq!(migrated.label_secondary_percentage, 5000);
+        assert_eq!(migrated.artist_licensing_percentage, 6000);
+        assert_eq!(migrated.label_licensing_percentage, 4000);
+        assert_eq!(migrated.artist_splits.len(), 1);
+        assert_eq!(migrated.artist_splits[0].allocated_percentage, 10000);
+        assert_eq!(migrated.artist_reserve_percentage, 0);
+
+        // Migrated account reads back through the current layout
+        let data = serialize(&migrated, COLLECTION_CONFIG_V0.len() + 1);
+        let read_back = CollectionConfiguration::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(read_back.artist_splits.len(), 1);
+        assert_eq!(read_back.artist_reserve_percentage, 0);
+    }
+
+    #[test]
+    fn rejects_v0_config_allocating_more_than_the_artist_share() {
+        let mut data = COLLECTION_CONFIG_V0.to_vec();
+        // Single split's allocated percentage, after the discriminator, authority,
+        // 6 percentages, vec prefix and artist address
+        let offset = 8 + 32 + 6 * 8 + 4 + 32;
+        data[offset..offset + 8].copy_from_slice(&10001u64.to_le_bytes());
+
+        assert_eq!(
+            migration_error(migrate_collection_config_data(&data)),
+            ProgramError::from(ErrorCode::UnknownAccountVersion)
+        );
+    }
+
+    #[test]
+    fn migrates_nft_balance_ledger_v0() {
+        let migrated = migrate_nft_balance_ledger_data(NFT_BALANCE_LEDGER_V0).unwrap();
+
+        assert_eq!(migrated.version, ACCOUNT_VERSION);
+        assert_eq!(migrated.size, 3);
+        let balances: Vec<u64> = migrated
+            .nft_balances
+            .iter()
+            .map(|nft_balance| nft_balance.royalties_balance)
+            .collect();
+        assert_eq!(balances, vec![700_000_000, 300_000_000, 0]);
+    }
+
+    #[test]
+    fn migrates_artist_balance_ledger_v0() {
+        let migrated = migrate_artist_balance_ledger_data(ARTIST_BALANCE_LEDGER_V0).unwrap();
+
+        assert_eq!(migrated.version, ACCOUNT_VERSION);
+        assert_eq!(migrated.size, 2);
+        assert_eq!(migrated.artist_balances[0].royalties_balance, 5_000_000_000);
+        assert_eq!(migrated.artist_balances[1].royalties_balance, 1_250_000_000);
+    }
+
+    #[test]
+    fn rejects_already_migrated_accounts() {
+        let config = migrate_collection_config_data(COLLECTION_CONFIG_V0).unwrap();
+        let data = serialize(&config, COLLECTION_CONFIG_V0.len() + 1);
+        assert_eq!(
+            migration_error(migrate_collection_config_data(&data)),
+            ProgramError::from(ErrorCode::AccountAlreadyMigrated)
+        );
+
+        let nft_ledger = migrate_nft_balance_ledger_data(NFT_BALANCE_LEDGER_V0).unwrap();
+        let data = serialize(&nft_ledger, NFT_BALANCE_LEDGER_V0.len() + 1);
+        assert_eq!(
+            migration_error(migrate_nft_balance_ledger_data(&data)),
+            ProgramError::from(ErrorCode::AccountAlreadyMigrated)
+        );
+
+        let artist_ledger = migrate_artist_balance_ledger_data(ARTIST_BALANCE_LEDGER_V0).unwrap();
+        let data = serialize(&artist_ledger, ARTIST_BALANCE_LEDGER_V0.len() + 1);
+        assert_eq!(
+            migration_error(migrate_artist_balance_ledger_data(&data)),
+            ProgramError::from(ErrorCode::AccountAlreadyMigrated)
+        );
+    }
+
+    #[test]
+    fn rejects_other_account_types() {
+        assert_eq!(
+            migration_error(migrate_collection_config_data(NFT_BALANCE_LEDGER_V0)),
+            ProgramError::from(ErrorCode::UnknownAccountVersion)
+        );
+        assert_eq!(
+            migration_error(migrate_nft_balance_ledger_data(ARTIST_BALANCE_LEDGER_V0)),
+            ProgramError::from(ErrorCode::UnknownAccountVersion)
+        );
+    }
+}
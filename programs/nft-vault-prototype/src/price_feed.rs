@@ -0,0 +1,185 @@
+//! SOL/USD price feed
+//!
+//! USD-priced fee schedules convert to lamports at the aggregate price of a
+//! Pyth v2 price account. Only the account header and the aggregate price
+//! are read, by offset, so tests stand in an account with the same layout
+//! for a real feed. The feed is trusted by address, the fee schedule names
+//! it along with the staleness and confidence bounds its price must meet.
+
+use crate::*;
+use std::convert::TryFrom;
+
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+pub const PYTH_VERSION: u32 = 2;
+pub const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+// Aggregate price status while publishers are trading
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+// Offsets into a price account
+pub const MAGIC_OFFSET: usize = 0;
+pub const VERSION_OFFSET: usize = 4;
+pub const ACCOUNT_TYPE_OFFSET: usize = 8;
+pub const EXPO_OFFSET: usize = 20;
+pub const AGG_PRICE_OFFSET: usize = 208;
+pub const AGG_CONF_OFFSET: usize = 216;
+pub const AGG_STATUS_OFFSET: usize = 224;
+pub const AGG_PUB_SLOT_OFFSET: usize = 232;
+// Everything read lies before the publisher components
+pub const PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+// Lamports a cent buys at one USD per SOL
+const LAMPORTS_PER_CENT_AT_PAR: u128 = 10_000_000;
+
+/// Aggregate price of a feed, price * 10^expo USD per SOL give or take conf
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UsdPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub status: u32,
+    pub pub_slot: u64,
+}
+
+impl UsdPrice {
+    pub fn load(price_feed: &AccountInfo) -> Result<Self> {
+        let data = price_feed.try_borrow_data()?;
+        if data.len() < PRICE_ACCOUNT_MIN_LEN
+            || read_u32(&data, MAGIC_OFFSET) != PYTH_MAGIC
+            || read_u32(&data, VERSION_OFFSET) != PYTH_VERSION
+            || read_u32(&data, ACCOUNT_TYPE_OFFSET) != PYTH_PRICE_ACCOUNT_TYPE
+        {
+            return Err(ErrorCode::InvalidPriceFeed.into());
+        }
+
+        Ok(UsdPrice {
+            price: read_u64(&data, AGG_PRICE_OFFSET) as i64,
+            conf: read_u64(&data, AGG_CONF_OFFSET),
+            expo: read_u32(&data, EXPO_OFFSET) as i32,
+            status: read_u32(&data, AGG_STATUS_OFFSET),
+            pub_slot: read_u64(&data, AGG_PUB_SLOT_OFFSET),
+        })
+    }
+
+    // Rounded down, like every share of a payment
+    pub fn usd_to_lamports(&self, usd_cents: u64) -> Result<u64> {
+        if self.price <= 0 {
+            return Err(ErrorCode::InvalidPriceFeed.into());
+        }
+
+        let scale = 10u128
+            .checked_pow(self.expo.unsigned_abs())
+            .ok_or(ErrorCode::InvalidPriceFeed)?;
+        let mut numerator = usd_cents as u128 * LAMPORTS_PER_CENT_AT_PAR;
+        let mut denominator = self.price as u128;
+        if self.expo < 0 {
+            numerator = numerator
+                .checked_mul(scale)
+                .ok_or(ErrorCode::InvalidPriceFeed)?;
+        } else {
+            denominator = denominator
+                .checked_mul(scale)
+                .ok_or(ErrorCode::InvalidPriceFeed)?;
+        }
+
+        u64::try_from(numerator / denominator).map_err(|_| ErrorCode::InvalidPriceFeed.into())
+    }
+}
+
+/// Feed a USD fee schedule converts at, and the bounds its price must meet
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PriceFeedConfig {
+    pub price_feed: Pubkey,
+    pub max_staleness_slots: u64, // since the aggregate price was published
+    pub max_confidence_bps: u64,  // confidence interval over price
+}
+
+impl PriceFeedConfig {
+    pub const SIZE: usize = 32 + 8 + 8;
+
+    /// Current price of the configured feed, within the bounds
+    pub fn price(&self, price_feed: Option<&AccountInfo>, slot: u64) -> Result<UsdPrice> {
+        let price_feed = price_feed.ok_or(ErrorCode::InvalidPriceFeed)?;
+        if price_feed.key() != self.price_feed {
+            return Err(ErrorCode::InvalidPriceFeed.into());
+        }
+
+        let price = UsdPrice::load(price_feed)?;
+        if price.price <= 0 {
+            return Err(ErrorCode::InvalidPriceFeed.into());
+        }
+
+        if price.status != PYTH_STATUS_TRADING
+            || slot.saturating_sub(price.pub_slot) > self.max_staleness_slots
+        {
+            return Err(ErrorCode::StalePrice.into());
+        }
+
+        if price.conf as u128 * ONE_HUNDRED_PERCENT as u128
+            > price.price as u128 * self.max_confidence_bps as u128
+        {
+            return Err(ErrorCode::PriceConfidenceTooWide.into());
+        }
+
+        Ok(price)
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // $150.00 per SOL
+    const PRICE: UsdPrice = UsdPrice {
+        price: 15_000_000_000,
+        conf: 0,
+        expo: -8,
+        status: PYTH_STATUS_TRADING,
+        pub_slot: 0,
+    };
+
+    #[test]
+    fn converts_usd_cents_to_lamports() {
+        assert_eq!(PRICE.usd_to_lamports(15_000).unwrap(), 1_000_000_000);
+        // Rounded down
+        assert_eq!(PRICE.usd_to_lamports(5_000).unwrap(), 333_333_333);
+
+        let positive_expo = UsdPrice {
+            price: 15,
+            expo: 1,
+            ..PRICE
+        };
+        assert_eq!(
+            positive_expo.usd_to_lamports(15_000).unwrap(),
+            1_000_000_000
+        );
+    }
+
+    #[test]
+    fn rejects_unusable_prices() {
+        for price in [
+            UsdPrice { price: 0, ..PRICE },
+            UsdPrice { price: -1, ..PRICE },
+            UsdPrice { expo: -60, ..PRICE },
+            // More lamports than a u64 holds
+            UsdPrice {
+                price: 1,
+                expo: -8,
+                ..PRICE
+            },
+        ] {
+            assert!(price.usd_to_lamports(u64::MAX).is_err());
+        }
+    }
+}
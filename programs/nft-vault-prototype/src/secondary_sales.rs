@@ -0,0 +1,276 @@
+//! Secondary sales
+//!
+//! Marketplaces, or a relayer on their behalf, report each resale of an
+//! NFT with record_secondary_sale. Only reporters the collection authority
+//! approved with approve_sale_reporter can record sales, since the indexer
+//! and exports trust the recorded volume. The royalty is deposited into the
+//! secondary pool, which distribute_secondary_pool sweeps as before, and
+//! the sale is kept in a SecondarySale account. NftSales keeps the running
+//! sale count and volume of each NFT, and is created once per NFT with
+//! initialize_nft_sales before its first recorded sale.
+
+use crate::*;
+
+// Anyone can open the sales record of an NFT in the ledger
+pub fn initialize_nft_sales(ctx: Context<InitializeNftSales>) -> ProgramResult {
+    let nft = ctx.accounts.nft.key();
+    ctx.accounts.nft_balance_ledger.snapshot_position(nft)?;
+
+    let nft_sales = &mut ctx.accounts.nft_sales;
+    nft_sales.version = ACCOUNT_VERSION;
+    nft_sales.nft = nft;
+    nft_sales.sale_count = 0;
+    nft_sales.volume = 0;
+    nft_sales.royalties = 0;
+
+    Ok(())
+}
+
+// The collection authority approves each marketplace or relayer key
+pub fn approve_sale_reporter(ctx: Context<ApproveSaleReporter>, reporter: Pubkey) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    let sale_reporter = &mut ctx.accounts.sale_reporter;
+    sale_reporter.version = ACCOUNT_VERSION;
+    sale_reporter.reporter = reporter;
+    sale_reporter.approved_slot = Clock::get()?.slot;
+
+    Ok(())
+}
+
+// Closes the approval, its rent goes back to the collection authority
+pub fn revoke_sale_reporter(ctx: Context<RevokeSaleReporter>) -> ProgramResult {
+    // Verify Collection Authority signed
+    if ctx.accounts.collection_config.collection_authority
+        != ctx.accounts.collection_authority.key()
+    {
+        return Err(ErrorCode::MissingCollectionAuthoritySignature.into());
+    }
+
+    Ok(())
+}
+
+/**
+ * Records a resale and deposits its royalty, paid by the reporter, into
+ * the secondary pool. price and royalty_amount are in lamports, and every
+ * recorded sale pays a royalty. The reporter must be approved.
+ */
+pub fn record_secondary_sale(
+    ctx: Context<RecordSecondarySale>,
+    price: u64,
+    buyer: Pubkey,
+    seller: Pubkey,
+    royalty_amount: u64,
+) -> ProgramResult {
+    SaleReporter::verify_approval(&ctx.accounts.sale_reporter, ctx.program_id)?;
+    let sale_index = ctx
+        .accounts
+        .nft_sales
+        .record_sale(price, buyer, seller, royalty_amount)?;
+
+    let ix = system_instruction::transfer(
+        ctx.accounts.reporter.key,
+        ctx.accounts.pda_secondary_pool.key,
+        royalty_amount,
+    );
+    invoke(
+        &ix,
+        &[
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.reporter.to_account_info(),
+            ctx.accounts.pda_secondary_pool.to_account_info(),
+        ],
+    )?;
+
+    let nft_sales = &ctx.accounts.nft_sales;
+    let sale = &mut ctx.accounts.secondary_sale;
+    sale.version = ACCOUNT_VERSION;
+    sale.nft = nft_sales.nft;
+    sale.sale_index = sale_index;
+    sale.price = price;
+    sale.buyer = buyer;
+    sale.seller = seller;
+    sale.royalty_amount = royalty_amount;
+    sale.reporter = ctx.accounts.reporter.key();
+    sale.slot = Clock::get()?.slot;
+
+    emit!(SecondarySaleRecorded {
+        nft: sale.nft,
+        sale: sale.key(),
+        price,
+        buyer,
+        seller,
+        royalty_amount,
+        reporter: sale.reporter,
+        volume: nft_sales.volume,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeNftSales<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = NftSales::SIZE,
+        seeds = [b"nft-sales", nft.key().as_ref()],
+        bump
+    )]
+    pub nft_sales: Account<'info, NftSales>,
+    pub nft: Account<'info, Mint>,
+    #[account(seeds = [b"nft-ledger"], bump = 255)]
+    pub nft_balance_ledger: Account<'info, NftBalanceLedger>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSecondarySale<'info> {
+    #[account(mut, seeds = [b"nft-sales", nft_sales.nft.as_ref()], bump)]
+    pub nft_sales: Account<'info, NftSales>,
+    #[account(
+        init,
+        payer = reporter,
+        space = SecondarySale::SIZE,
+        seeds = [b"secondary-sale", nft_sales.nft.as_ref(), nft_sales.sale_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub secondary_sale: Account<'info, SecondarySale>,
+    #[account(mut, seeds = [b"secondary-pool"], bump = 255)]
+    pub pda_secondary_pool: SystemAccount<'info>,
+    #[account(seeds = [b"sale-reporter", reporter.key().as_ref()], bump)]
+    pub sale_reporter: UncheckedAccount<'info>, // approval, empty if there is none
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(reporter: Pubkey)]
+pub struct ApproveSaleReporter<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = SaleReporter::SIZE,
+        seeds = [b"sale-reporter", reporter.as_ref()],
+        bump
+    )]
+    pub sale_reporter: Account<'info, SaleReporter>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    pub collection_authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSaleReporter<'info> {
+    #[account(
+        mut,
+        seeds = [b"sale-reporter", sale_reporter.reporter.as_ref()],
+        bump,
+        close = collection_authority
+    )]
+    pub sale_reporter: Account<'info, SaleReporter>,
+    #[account(seeds = [b"collection-config"], bump = 254)]
+    pub collection_config: Account<'info, CollectionConfiguration>,
+    #[account(mut)]
+    pub collection_authority: Signer<'info>,
+}
+
+/// A marketplace or relayer allowed to record secondary sales
+#[account]
+pub struct SaleReporter {
+    pub version: u8,
+    pub reporter: Pubkey,
+    pub approved_slot: u64,
+}
+
+impl SaleReporter {
+    // discriminator + version + reporter + approved slot
+    pub const SIZE: usize = 8 + 1 + 32 + 8;
+
+    // Approvals are only ever created by approve_sale_reporter, a revoked
+    // one is closed
+    pub fn verify_approval(sale_reporter: &AccountInfo, program_id: &Pubkey) -> Result<()> {
+        if sale_reporter.owner != program_id
+            || Self::try_deserialize(&mut &sale_reporter.data.borrow()[..]).is_err()
+        {
+            return Err(ErrorCode::UnauthorizedSaleReporter.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Running totals of an NFT's recorded resales
+#[account]
+pub struct NftSales {
+    pub version: u8,
+    pub nft: Pubkey,
+    pub sale_count: u64, // also the index of the next sale
+    pub volume: u64,     // sum of sale prices
+    pub royalties: u64,  // deposited into the secondary pool
+}
+
+impl NftSales {
+    // discriminator + version + NFT + sale count + volume + royalties
+    pub const SIZE: usize = 8 + 1 + 32 + 8 + 8 + 8;
+
+    // Adds a sale to the totals, returns its sale index
+    pub fn record_sale(
+        &mut self,
+        price: u64,
+        buyer: Pubkey,
+        seller: Pubkey,
+        royalty_amount: u64,
+    ) -> Result<u64> {
+        if price == 0 || royalty_amount == 0 || royalty_amount > price || buyer == seller {
+            return Err(ErrorCode::InvalidSecondarySale.into());
+        }
+
+        let volume = self
+            .volume
+            .checked_add(price)
+            .ok_or(ErrorCode::InvalidSecondarySale)?;
+        let royalties = self
+            .royalties
+            .checked_add(royalty_amount)
+            .ok_or(ErrorCode::InvalidSecondarySale)?;
+
+        let sale_index = self.sale_count;
+        self.sale_count += 1;
+        self.volume = volume;
+        self.royalties = royalties;
+
+        Ok(sale_index)
+    }
+}
+
+/// One recorded resale, sale_index counts the NFT's sales from 0
+#[account]
+pub struct SecondarySale {
+    pub version: u8,
+    pub nft: Pubkey,
+    pub sale_index: u64,
+    pub price: u64,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub royalty_amount: u64,
+    pub reporter: Pubkey, // marketplace or relayer that recorded the sale
+    pub slot: u64,
+}
+
+impl SecondarySale {
+    // discriminator + version + NFT + sale index + price + buyer + seller
+    // + royalty + reporter + slot
+    pub const SIZE: usize = 8 + 1 + 32 + 8 + 8 + 32 + 32 + 8 + 32 + 8;
+}
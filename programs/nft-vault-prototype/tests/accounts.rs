@@ -0,0 +1,277 @@
+//! Account growth and layout migrations
+//!
+//! Growing an account needs `AccountInfo::realloc`, which native
+//! program-test doesn't support, so those tests only run with cargo test-bpf.
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction;
+use nft_vault_client::pda;
+use nft_vault_prototype::{
+    ArtistBalance, ArtistBalanceLedger, ArtistPercentage, CollectionConfiguration, ErrorCode,
+    NftBalance, NftBalanceLedger, ACCOUNT_VERSION, INITIAL_ARTIST_CAPACITY, INITIAL_NFT_CAPACITY,
+};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+
+const COLLECTION_CONFIG_V0: &[u8] = include_bytes!("fixtures/collection_config_v0.bin");
+const NFT_BALANCE_LEDGER_V0: &[u8] = include_bytes!("fixtures/nft_balance_ledger_v0.bin");
+const ARTIST_BALANCE_LEDGER_V0: &[u8] = include_bytes!("fixtures/artist_balance_ledger_v0.bin");
+
+fn split(artist_address: Pubkey, allocated_percentage: u64) -> ArtistPercentage {
+    ArtistPercentage {
+        artist_address,
+        allocated_percentage,
+    }
+}
+
+// Fills the config and artist ledger with artists holding 1000 each
+async fn fill_artist_capacity(test: &mut TestContext) -> Vec<Keypair> {
+    let artists: Vec<Keypair> = (0..INITIAL_ARTIST_CAPACITY)
+        .map(|_| Keypair::new())
+        .collect();
+    let full_config = CollectionConfiguration {
+        artist_splits: artists
+            .iter()
+            .map(|artist| split(artist.pubkey(), 1000))
+            .collect(),
+        ..test.config().await
+    };
+    test.seed(
+        &pda::collection_config().0,
+        &full_config,
+        CollectionConfiguration::space(INITIAL_ARTIST_CAPACITY),
+    );
+    let full_ledger = ArtistBalanceLedger {
+        version: ACCOUNT_VERSION,
+        artist_balances: artists
+            .iter()
+            .map(|artist| ArtistBalance {
+                artist_address: artist.pubkey(),
+                royalties_balance: 0,
+            })
+            .collect(),
+        size: INITIAL_ARTIST_CAPACITY as u64,
+    };
+    test.seed(
+        &pda::artist_ledger().0,
+        &full_ledger,
+        ArtistBalanceLedger::space(INITIAL_ARTIST_CAPACITY),
+    );
+
+    artists
+}
+
+fn set_program_account(test: &mut TestContext, address: &Pubkey, data: &[u8]) {
+    let lamports = Rent::default().minimum_balance(data.len());
+    test.set_account(address, &nft_vault_prototype::id(), lamports, data.to_vec());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "grows accounts, run with cargo test-bpf"
+)]
+async fn expand_ledger_grows_accounts() {
+    let mut test = TestContext::initialized().await;
+
+    let ix = instruction::expand_ledger(test.payer(), 20, 400);
+    test.process(&[ix], &[]).await.unwrap();
+
+    assert_eq!(
+        test.account_len(&pda::collection_config().0).await,
+        CollectionConfiguration::space(20)
+    );
+    assert_eq!(
+        test.account_len(&pda::artist_ledger().0).await,
+        ArtistBalanceLedger::space(20)
+    );
+    assert_eq!(
+        test.account_len(&pda::nft_ledger().0).await,
+        NftBalanceLedger::space(400)
+    );
+
+    // Accounts are never shrunk
+    let ix = instruction::expand_ledger(test.payer(), 1, 1);
+    test.process(&[ix], &[]).await.unwrap();
+    assert_eq!(
+        test.account_len(&pda::nft_ledger().0).await,
+        NftBalanceLedger::space(400)
+    );
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "grows accounts, run with cargo test-bpf"
+)]
+async fn add_nft_grows_full_ledger() {
+    let mut test = TestContext::initialized().await;
+
+    let full_ledger = NftBalanceLedger {
+        version: ACCOUNT_VERSION,
+        nft_balances: (0..INITIAL_NFT_CAPACITY)
+            .map(|_| NftBalance {
+                nft_address: Pubkey::new_unique(),
+                royalties_balance: 0,
+            })
+            .collect(),
+        size: INITIAL_NFT_CAPACITY as u64,
+    };
+    test.seed(
+        &pda::nft_ledger().0,
+        &full_ledger,
+        NftBalanceLedger::space(INITIAL_NFT_CAPACITY),
+    );
+
+    let nft = test.add_nft(LAMPORTS_PER_SOL).await;
+
+    assert_eq!(
+        test.account_len(&pda::nft_ledger().0).await,
+        NftBalanceLedger::space(INITIAL_NFT_CAPACITY + 1)
+    );
+    assert_eq!(test.nft_balance(&nft.mint).await, 0);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "grows accounts, run with cargo test-bpf"
+)]
+async fn config_update_grows_full_config() {
+    let mut test = TestContext::initialized().await;
+    let artists = fill_artist_capacity(&mut test).await;
+
+    // Every artist gives up 100 for a newcomer
+    let newcomer = Pubkey::new_unique();
+    let mut artist_splits: Vec<ArtistPercentage> = artists
+        .iter()
+        .map(|artist| split(artist.pubkey(), 900))
+        .collect();
+    artist_splits.push(split(newcomer, 1000));
+    test.propose_config_update(
+        PERCENTAGES,
+        artist_splits,
+        artists.iter().map(|artist| artist.pubkey()).collect(),
+    )
+    .await;
+    for artist in artists.iter() {
+        let ix = instruction::approve_config_update(artist.pubkey());
+        test.process(&[ix], &[artist]).await.unwrap();
+    }
+    test.advance_clock(CONFIG_UPDATE_TIMELOCK).await;
+
+    let ix = instruction::execute_config_update(test.payer(), test.payer());
+    test.process(&[ix], &[]).await.unwrap();
+
+    assert_eq!(
+        test.account_len(&pda::collection_config().0).await,
+        CollectionConfiguration::space(INITIAL_ARTIST_CAPACITY + 1)
+    );
+    assert_eq!(
+        test.account_len(&pda::artist_ledger().0).await,
+        ArtistBalanceLedger::space(INITIAL_ARTIST_CAPACITY + 1)
+    );
+    assert_eq!(test.artist_balance(&newcomer).await, 0);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "grows accounts, run with cargo test-bpf"
+)]
+async fn transfer_artist_split_grows_full_config() {
+    let mut test = TestContext::initialized().await;
+    let artists = fill_artist_capacity(&mut test).await;
+
+    let newcomer = Pubkey::new_unique();
+    let ix = instruction::transfer_artist_split(test.payer(), artists[0].pubkey(), newcomer, 500);
+    test.process(&[ix], &[&artists[0]]).await.unwrap();
+
+    assert_eq!(
+        test.account_len(&pda::collection_config().0).await,
+        CollectionConfiguration::space(INITIAL_ARTIST_CAPACITY + 1)
+    );
+    assert_eq!(
+        test.account_len(&pda::artist_ledger().0).await,
+        ArtistBalanceLedger::space(INITIAL_ARTIST_CAPACITY + 1)
+    );
+    assert_eq!(
+        test.config().await.artist_splits.len(),
+        INITIAL_ARTIST_CAPACITY + 1
+    );
+    assert_eq!(test.artist_balance(&newcomer).await, 0);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "grows accounts, run with cargo test-bpf"
+)]
+async fn migrates_v0_accounts() {
+    let mut test = TestContext::new().await;
+    set_program_account(&mut test, &pda::collection_config().0, COLLECTION_CONFIG_V0);
+    set_program_account(&mut test, &pda::nft_ledger().0, NFT_BALANCE_LEDGER_V0);
+    set_program_account(&mut test, &pda::artist_ledger().0, ARTIST_BALANCE_LEDGER_V0);
+
+    let payer = test.payer();
+    let migrations = [
+        instruction::migrate_collection_config(payer),
+        instruction::migrate_nft_balance_ledger(payer),
+        instruction::migrate_artist_balance_ledger(payer),
+    ];
+    test.process(&migrations, &[]).await.unwrap();
+
+    let config = test.config().await;
+    assert_eq!(config.version, ACCOUNT_VERSION);
+    assert_eq!(config.artist_splits.len(), 1);
+    assert_eq!(config.artist_reserve_percentage, 0);
+    assert_eq!(
+        test.account_len(&pda::collection_config().0).await,
+        COLLECTION_CONFIG_V0.len() + 1
+    );
+
+    let nft_ledger: NftBalanceLedger = test.account(&pda::nft_ledger().0).await;
+    assert_eq!(nft_ledger.version, ACCOUNT_VERSION);
+    assert_eq!(nft_ledger.size, 3);
+    let artist_ledger: ArtistBalanceLedger = test.account(&pda::artist_ledger().0).await;
+    assert_eq!(artist_ledger.version, ACCOUNT_VERSION);
+    assert_eq!(
+        artist_ledger.artist_balances[0].royalties_balance,
+        5_000_000_000
+    );
+
+    assert_error(
+        test.process(&[instruction::migrate_collection_config(payer)], &[])
+            .await,
+        ErrorCode::AccountAlreadyMigrated,
+    );
+}
+
+#[tokio::test]
+async fn migration_rejects_current_and_unknown_accounts() {
+    let mut test = TestContext::initialized().await;
+    let payer = test.payer();
+
+    for ix in [
+        instruction::migrate_collection_config(payer),
+        instruction::migrate_nft_balance_ledger(payer),
+        instruction::migrate_artist_balance_ledger(payer),
+    ] {
+        assert_error(
+            test.process(&[ix], &[]).await,
+            ErrorCode::AccountAlreadyMigrated,
+        );
+    }
+
+    // An NFT ledger where the artist ledger should be
+    set_program_account(&mut test, &pda::artist_ledger().0, NFT_BALANCE_LEDGER_V0);
+    assert_error(
+        test.process(&[instruction::migrate_artist_balance_ledger(payer)], &[])
+            .await,
+        ErrorCode::UnknownAccountVersion,
+    );
+}
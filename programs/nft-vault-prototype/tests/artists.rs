@@ -0,0 +1,520 @@
+//! Config updates and changes to the artist splits
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction::{self, CollectionPercentages};
+use nft_vault_client::pda;
+use nft_vault_prototype::{ArtistBalanceLedger, ArtistDilution, ArtistPercentage, ErrorCode};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+fn split(artist_address: Pubkey, allocated_percentage: u64) -> ArtistPercentage {
+    ArtistPercentage {
+        artist_address,
+        allocated_percentage,
+    }
+}
+
+fn splits(config: &nft_vault_prototype::CollectionConfiguration) -> Vec<(Pubkey, u64)> {
+    config
+        .artist_splits
+        .iter()
+        .map(|split| (split.artist_address, split.allocated_percentage))
+        .collect()
+}
+
+// Collection with a second artist holding 4000 of the first artist's 10000
+async fn two_artists() -> (TestContext, Keypair) {
+    let mut test = TestContext::initialized().await;
+    let second = test.funded_keypair();
+
+    let artist = test.artist();
+    let ix =
+        instruction::transfer_artist_split(test.payer(), artist.pubkey(), second.pubkey(), 4000);
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    (test, second)
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn propose_config_update_checks_authority_and_splits() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::propose_config_update(
+        test.payer(),
+        fake_authority.pubkey(),
+        PERCENTAGES,
+        vec![split(artist, 10000)],
+    );
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::propose_config_update(
+        test.payer(),
+        test.payer(),
+        PERCENTAGES,
+        vec![split(artist, 5000), split(artist, 5000)],
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidCollectionConfig,
+    );
+
+    let ix = instruction::propose_config_update(
+        test.payer(),
+        test.payer(),
+        PERCENTAGES,
+        vec![split(artist, 9000)],
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidCollectionConfig,
+    );
+}
+
+#[tokio::test]
+async fn config_update_needs_approvals_and_timelock() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+
+    test.propose_config_update(
+        PERCENTAGES,
+        vec![split(artist.pubkey(), 5000), split(second.pubkey(), 5000)],
+        vec![artist.pubkey(), second.pubkey()],
+    )
+    .await;
+
+    let approve = [instruction::approve_config_update(artist.pubkey())];
+    test.process(&approve, &[&artist]).await.unwrap();
+    assert_error(
+        test.process(&approve, &[&artist]).await,
+        ErrorCode::ConfigUpdateAlreadyApproved,
+    );
+
+    let outsider = test.funded_keypair();
+    let ix = instruction::approve_config_update(outsider.pubkey());
+    assert_error(
+        test.process(&[ix], &[&outsider]).await,
+        ErrorCode::InvalidArtist,
+    );
+
+    let execute = [instruction::execute_config_update(
+        test.payer(),
+        test.payer(),
+    )];
+    assert_error(
+        test.process(&execute, &[]).await,
+        ErrorCode::ConfigUpdateTimelockActive,
+    );
+
+    test.advance_clock(CONFIG_UPDATE_TIMELOCK).await;
+    assert_error(
+        test.process(&execute, &[]).await,
+        ErrorCode::ConfigUpdateMissingApprovals,
+    );
+
+    let ix = instruction::approve_config_update(second.pubkey());
+    test.process(&[ix], &[&second]).await.unwrap();
+    test.process(&execute, &[]).await.unwrap();
+
+    assert_eq!(
+        splits(&test.config().await),
+        vec![(artist.pubkey(), 5000), (second.pubkey(), 5000)]
+    );
+    assert_eq!(test.balance(&pda::config_update().0).await, 0);
+}
+
+#[tokio::test]
+async fn config_update_adds_new_artists_to_ledger() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist();
+    let newcomer = test.funded_keypair().pubkey();
+    let percentages = CollectionPercentages {
+        artist_licensing_percentage: 7000,
+        label_licensing_percentage: 3000,
+        ..PERCENTAGES
+    };
+
+    test.propose_config_update(
+        percentages,
+        vec![split(artist.pubkey(), 6000), split(newcomer, 4000)],
+        vec![artist.pubkey()],
+    )
+    .await;
+    let ix = instruction::approve_config_update(artist.pubkey());
+    test.process(&[ix], &[&artist]).await.unwrap();
+    test.advance_clock(CONFIG_UPDATE_TIMELOCK).await;
+    let ix = instruction::execute_config_update(test.payer(), test.payer());
+    test.process(&[ix], &[]).await.unwrap();
+
+    let config = test.config().await;
+    assert_eq!(config.artist_licensing_percentage, 7000);
+    assert_eq!(config.label_licensing_percentage, 3000);
+    assert_eq!(test.artist_balance(&newcomer).await, 0);
+}
+
+#[tokio::test]
+async fn config_update_needs_approval_of_current_artists() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+
+    test.propose_config_update(
+        PERCENTAGES,
+        vec![split(artist.pubkey(), 5000), split(second.pubkey(), 5000)],
+        vec![artist.pubkey(), second.pubkey()],
+    )
+    .await;
+    for approver in [&artist, &second] {
+        let ix = instruction::approve_config_update(approver.pubkey());
+        test.process(&[ix], &[approver]).await.unwrap();
+    }
+
+    // The split sold during the timelock would be dropped without its new owner's consent
+    let third = test.funded_keypair();
+    let ix =
+        instruction::transfer_artist_split(test.payer(), second.pubkey(), third.pubkey(), 2000);
+    test.process(&[ix], &[&second]).await.unwrap();
+
+    test.advance_clock(CONFIG_UPDATE_TIMELOCK).await;
+    let ix = instruction::execute_config_update(test.payer(), test.payer());
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::ConfigUpdateMissingApprovals,
+    );
+}
+
+#[tokio::test]
+async fn config_update_rechecks_splits_against_reserve() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+    let percentages = CollectionPercentages {
+        artist_licensing_percentage: 7000,
+        label_licensing_percentage: 3000,
+        ..PERCENTAGES
+    };
+
+    test.propose_config_update(
+        percentages,
+        vec![split(artist.pubkey(), 6000), split(second.pubkey(), 4000)],
+        vec![artist.pubkey(), second.pubkey()],
+    )
+    .await;
+    for approver in [&artist, &second] {
+        let ix = instruction::approve_config_update(approver.pubkey());
+        test.process(&[ix], &[approver]).await.unwrap();
+    }
+
+    // The removed artist's split went to the reserve, the proposal's splits
+    // now allocate more than the artists have
+    let ix = instruction::remove_artist(
+        test.payer(),
+        second.pubkey(),
+        ArtistDilution::Reserve,
+        &[artist.pubkey()],
+    );
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    test.advance_clock(CONFIG_UPDATE_TIMELOCK).await;
+    let ix = instruction::execute_config_update(test.payer(), test.payer());
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidCollectionConfig,
+    );
+}
+
+#[tokio::test]
+async fn cancel_config_update_requires_authority() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+    test.propose_config_update(PERCENTAGES, vec![split(artist, 10000)], vec![])
+        .await;
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::cancel_config_update(fake_authority.pubkey(), test.payer());
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::cancel_config_update(test.payer(), test.payer());
+    test.process(&[ix], &[]).await.unwrap();
+    assert_eq!(test.balance(&pda::config_update().0).await, 0);
+}
+
+#[tokio::test]
+async fn transfer_artist_split_moves_percentage() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+
+    assert_eq!(
+        splits(&test.config().await),
+        vec![(artist.pubkey(), 6000), (second.pubkey(), 4000)]
+    );
+    assert_eq!(test.artist_balance(&second.pubkey()).await, 0);
+
+    // Selling the whole split drops the artist from the splits but not the ledger
+    let ix =
+        instruction::transfer_artist_split(test.payer(), second.pubkey(), artist.pubkey(), 4000);
+    test.process(&[ix], &[&second]).await.unwrap();
+
+    assert_eq!(splits(&test.config().await), vec![(artist.pubkey(), 10000)]);
+    assert_eq!(test.artist_balance(&second.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn transfer_artist_split_rejects_invalid_transfers() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist();
+    let other = test.funded_keypair();
+
+    for (new_artist, percentage) in [
+        (other.pubkey(), 0),
+        (artist.pubkey(), 1000),
+        (other.pubkey(), 10001),
+    ] {
+        let ix = instruction::transfer_artist_split(
+            test.payer(),
+            artist.pubkey(),
+            new_artist,
+            percentage,
+        );
+        assert_error(
+            test.process(&[ix], &[&artist]).await,
+            ErrorCode::InvalidArtistSplitTransfer,
+        );
+    }
+
+    let ix =
+        instruction::transfer_artist_split(test.payer(), other.pubkey(), artist.pubkey(), 1000);
+    assert_error(
+        test.process(&[ix], &[&other]).await,
+        ErrorCode::InvalidArtist,
+    );
+}
+
+#[tokio::test]
+async fn add_artist_pro_rata_needs_every_artist() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist();
+    let newcomer = test.funded_keypair().pubkey();
+
+    let ix = instruction::add_artist(
+        test.payer(),
+        test.payer(),
+        newcomer,
+        3000,
+        ArtistDilution::ProRata,
+        &[],
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::MissingArtistSignatures,
+    );
+
+    let ix = instruction::add_artist(
+        test.payer(),
+        test.payer(),
+        newcomer,
+        3000,
+        ArtistDilution::ProRata,
+        &[artist.pubkey()],
+    );
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    assert_eq!(
+        splits(&test.config().await),
+        vec![(artist.pubkey(), 7000), (newcomer, 3000)]
+    );
+    assert_eq!(test.artist_balance(&newcomer).await, 0);
+}
+
+#[tokio::test]
+async fn add_artist_rejects_invalid_dilution() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist();
+    let newcomer = test.funded_keypair().pubkey();
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::add_artist(
+        test.payer(),
+        fake_authority.pubkey(),
+        newcomer,
+        3000,
+        ArtistDilution::ProRata,
+        &[artist.pubkey()],
+    );
+    assert_error(
+        test.process(&[ix], &[&fake_authority, &artist]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let invalid = [
+        // Already an artist
+        (artist.pubkey(), 3000, ArtistDilution::ProRata),
+        // Nothing to give
+        (newcomer, 0, ArtistDilution::ProRata),
+        // Would take everything from the current artists
+        (newcomer, 10000, ArtistDilution::ProRata),
+        // Reserve is empty
+        (newcomer, 1000, ArtistDilution::Reserve),
+    ];
+    for (new_artist, percentage, source) in invalid {
+        let ix = instruction::add_artist(
+            test.payer(),
+            test.payer(),
+            new_artist,
+            percentage,
+            source,
+            &[artist.pubkey()],
+        );
+        assert_error(
+            test.process(&[ix], &[&artist]).await,
+            ErrorCode::InvalidArtistDilution,
+        );
+    }
+}
+
+#[tokio::test]
+async fn remove_artist_to_reserve_and_add_from_reserve() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+
+    let ix =
+        instruction::remove_artist(test.payer(), second.pubkey(), ArtistDilution::Reserve, &[]);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::MissingArtistSignatures,
+    );
+
+    let ix = instruction::remove_artist(
+        test.payer(),
+        second.pubkey(),
+        ArtistDilution::Reserve,
+        &[artist.pubkey()],
+    );
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    let config = test.config().await;
+    assert_eq!(splits(&config), vec![(artist.pubkey(), 6000)]);
+    assert_eq!(config.artist_reserve_percentage, 4000);
+    // Removed artists can still withdraw what they are owed
+    assert_eq!(test.artist_balance(&second.pubkey()).await, 0);
+
+    let newcomer = test.funded_keypair().pubkey();
+    let ix = instruction::add_artist(
+        test.payer(),
+        test.payer(),
+        newcomer,
+        5000,
+        ArtistDilution::Reserve,
+        &[],
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidArtistDilution,
+    );
+
+    let ix = instruction::add_artist(
+        test.payer(),
+        test.payer(),
+        newcomer,
+        3000,
+        ArtistDilution::Reserve,
+        &[],
+    );
+    test.process(&[ix], &[]).await.unwrap();
+
+    let config = test.config().await;
+    assert_eq!(
+        splits(&config),
+        vec![(artist.pubkey(), 6000), (newcomer, 3000)]
+    );
+    assert_eq!(config.artist_reserve_percentage, 1000);
+}
+
+#[tokio::test]
+async fn remove_artist_pro_rata_rescales_remaining() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+    let third = test.funded_keypair();
+    let ix =
+        instruction::transfer_artist_split(test.payer(), artist.pubkey(), third.pubkey(), 2000);
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    let ix = instruction::remove_artist(
+        test.payer(),
+        second.pubkey(),
+        ArtistDilution::ProRata,
+        &[artist.pubkey(), third.pubkey()],
+    );
+    test.process(&[ix], &[&artist, &third]).await.unwrap();
+
+    let config = test.config().await;
+    assert_eq!(
+        splits(&config),
+        vec![(artist.pubkey(), 6667), (third.pubkey(), 3333)]
+    );
+    assert_eq!(config.artist_reserve_percentage, 0);
+}
+
+#[tokio::test]
+async fn readded_artist_reuses_ledger_entry() {
+    let (mut test, second) = two_artists().await;
+    let artist = test.artist();
+
+    let ix = instruction::remove_artist(
+        test.payer(),
+        second.pubkey(),
+        ArtistDilution::Reserve,
+        &[artist.pubkey()],
+    );
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    let ledger_address = pda::artist_ledger().0;
+    let ledger_len = test.account_len(&ledger_address).await;
+    let ix = instruction::add_artist(
+        test.payer(),
+        test.payer(),
+        second.pubkey(),
+        4000,
+        ArtistDilution::Reserve,
+        &[],
+    );
+    test.process(&[ix], &[]).await.unwrap();
+
+    // Back in the splits with the entry they kept, the ledger doesn't grow
+    let ledger: ArtistBalanceLedger = test.account(&ledger_address).await;
+    assert_eq!(ledger.size, 2);
+    assert_eq!(ledger.artist_balances.len(), 2);
+    assert_eq!(test.account_len(&ledger_address).await, ledger_len);
+}
+
+#[tokio::test]
+async fn remove_artist_keeps_at_least_one_artist() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+
+    let ix = instruction::remove_artist(test.payer(), artist, ArtistDilution::Reserve, &[]);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidArtistDilution,
+    );
+
+    let outsider = test.funded_keypair().pubkey();
+    let artist_signer = test.artist();
+    let ix = instruction::remove_artist(test.payer(), outsider, ArtistDilution::Reserve, &[artist]);
+    assert_error(
+        test.process(&[ix], &[&artist_signer]).await,
+        ErrorCode::InvalidArtist,
+    );
+}
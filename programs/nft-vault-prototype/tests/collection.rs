@@ -0,0 +1,409 @@
+//! Collection setup, royalty streams and withdrawals
+//!
+//! InvalidRoyaltiesDistribution can't be reached through an instruction,
+//! every caller checks for an empty NFT ledger first and fails with
+//! NoNftsInCollection. InvalidWithdrawer is not raised anywhere.
+
+mod common;
+
+use anchor_lang::AccountSerialize;
+use common::*;
+use nft_vault_client::instruction::{self, CollectionPercentages};
+use nft_vault_client::pda;
+use nft_vault_prototype::{
+    ArtistBalanceLedger, ErrorCode, NftBalance, NftBalanceLedger, ACCOUNT_VERSION,
+};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+// Program owned account that looks like a ledger but is not the PDA
+fn fake_account<T: AccountSerialize>(test: &mut TestContext, account: &T) -> Pubkey {
+    let address = Pubkey::new_unique();
+    test.seed(&address, account, 1000);
+    address
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn initialize_collection() {
+    let mut test = TestContext::initialized().await;
+
+    let config = test.config().await;
+    assert_eq!(config.version, ACCOUNT_VERSION);
+    assert_eq!(config.collection_authority, test.payer());
+    assert_eq!(config.artist_mint_percentage, 8000);
+    assert_eq!(config.label_licensing_percentage, 4000);
+    assert_eq!(config.artist_splits.len(), 1);
+    assert_eq!(config.artist_splits[0].artist_address, test.artist.pubkey());
+    assert_eq!(config.artist_splits[0].allocated_percentage, 10000);
+
+    let artist_ledger: ArtistBalanceLedger = test.account(&pda::artist_ledger().0).await;
+    assert_eq!(artist_ledger.size, 1);
+    let nft_ledger: NftBalanceLedger = test.account(&pda::nft_ledger().0).await;
+    assert_eq!(nft_ledger.size, 0);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn initialize_collection_rejects_invalid_config() {
+    let mut test = TestContext::new().await;
+
+    let percentages = CollectionPercentages {
+        label_secondary_percentage: 4000,
+        ..PERCENTAGES
+    };
+    let ix = instruction::initialize_collection(test.payer(), percentages, test.artist.pubkey());
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidCollectionConfig,
+    );
+}
+
+#[tokio::test]
+async fn add_nft_splits_mint_revenue() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+
+    // First mint has no holders to share with, all of it goes to artists
+    let nft_1 = test.add_nft(LAMPORTS_PER_SOL).await;
+    assert_eq!(test.artist_balance(&artist).await, LAMPORTS_PER_SOL);
+    assert_eq!(test.nft_balance(&nft_1.mint).await, 0);
+
+    let nft_2 = test.add_nft(LAMPORTS_PER_SOL).await;
+    assert_eq!(
+        test.artist_balance(&artist).await,
+        LAMPORTS_PER_SOL + LAMPORTS_PER_SOL * 8 / 10
+    );
+    assert_eq!(
+        test.nft_balance(&nft_1.mint).await,
+        LAMPORTS_PER_SOL * 2 / 10
+    );
+    assert_eq!(test.nft_balance(&nft_2.mint).await, 0);
+}
+
+#[tokio::test]
+async fn add_nft_requires_collection_authority() {
+    let mut test = TestContext::initialized().await;
+    let mint = test.add_mint();
+    let fake_authority = Keypair::new();
+
+    let ix = instruction::add_nft(
+        test.payer(),
+        fake_authority.pubkey(),
+        mint,
+        LAMPORTS_PER_SOL,
+    );
+
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+}
+
+#[tokio::test]
+async fn add_nft_rejects_duplicate_nft() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(LAMPORTS_PER_SOL).await;
+
+    let ix = instruction::add_nft(test.payer(), test.payer(), nft.mint, LAMPORTS_PER_SOL);
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::NftAlreadyInCollection,
+    );
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_distributes_to_ledgers() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+    let nft_1 = test.add_nft(0).await;
+    let nft_2 = test.add_nft(0).await;
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    let license = test.payer_license().await;
+    let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&pda::vault().0).await,
+        vault_before + LAMPORTS_PER_SOL
+    );
+    assert_eq!(
+        test.artist_balance(&artist).await,
+        LAMPORTS_PER_SOL * 6 / 10
+    );
+    assert_eq!(
+        test.nft_balance(&nft_1.mint).await,
+        LAMPORTS_PER_SOL * 2 / 10
+    );
+    assert_eq!(
+        test.nft_balance(&nft_2.mint).await,
+        LAMPORTS_PER_SOL * 2 / 10
+    );
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_rejects_empty_collection() {
+    let mut test = TestContext::initialized().await;
+
+    let license = test.payer_license().await;
+    let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::NoNftsInCollection,
+    );
+}
+
+#[tokio::test]
+async fn distribute_secondary_pool_moves_pool_to_vault() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+    let nft = test.add_nft(0).await;
+    test.fund(&pda::secondary_pool().0, 2 * LAMPORTS_PER_SOL);
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    test.process(&[instruction::distribute_secondary_pool()], &[])
+        .await
+        .unwrap();
+
+    assert_eq!(test.balance(&pda::secondary_pool().0).await, 0);
+    assert_eq!(
+        test.balance(&pda::vault().0).await,
+        vault_before + 2 * LAMPORTS_PER_SOL
+    );
+    assert_eq!(test.artist_balance(&artist).await, LAMPORTS_PER_SOL);
+    assert_eq!(test.nft_balance(&nft.mint).await, LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn distribute_secondary_pool_rejects_empty_collection() {
+    let mut test = TestContext::initialized().await;
+    test.fund(&pda::secondary_pool().0, LAMPORTS_PER_SOL);
+
+    assert_error(
+        test.process(&[instruction::distribute_secondary_pool()], &[])
+            .await,
+        ErrorCode::NoNftsInCollection,
+    );
+}
+
+#[tokio::test]
+async fn distribute_secondary_pool_rejects_uninitialized_artist_ledger() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    test.fund(&pda::secondary_pool().0, LAMPORTS_PER_SOL);
+    let empty_ledger = fake_account(
+        &mut test,
+        &ArtistBalanceLedger {
+            version: ACCOUNT_VERSION,
+            artist_balances: vec![],
+            size: 0,
+        },
+    );
+
+    let mut ix = instruction::distribute_secondary_pool();
+    ix.accounts[3].pubkey = empty_ledger;
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::ArtistLedgerNotInitialized,
+    );
+}
+
+#[tokio::test]
+async fn distribute_secondary_pool_reports_math_errors() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    test.fund(&pda::secondary_pool().0, LAMPORTS_PER_SOL);
+    let config_address = pda::collection_config().0;
+    let space = test.account_len(&config_address).await;
+
+    let config = test.config().await;
+
+    // Neither config passes validation, they are written directly
+    let mut overflowing = config.clone();
+    overflowing.artist_secondary_percentage = u64::MAX;
+    test.seed(&config_address, &overflowing, space);
+    assert_error(
+        test.process(&[instruction::distribute_secondary_pool()], &[])
+            .await,
+        ErrorCode::RoyaltyOverflow,
+    );
+
+    let mut unallocated = config;
+    unallocated.artist_splits[0].allocated_percentage = 0;
+    unallocated.artist_reserve_percentage = 10000;
+    test.seed(&config_address, &unallocated, space);
+    assert_error(
+        test.process(&[instruction::distribute_secondary_pool()], &[])
+            .await,
+        ErrorCode::RoyaltyDivisionByZero,
+    );
+}
+
+#[tokio::test]
+async fn member_withdraw_pays_holder() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    test.add_nft(0).await;
+    let license = test.payer_license().await;
+    let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[]).await.unwrap();
+    let owner = nft.owner.pubkey();
+    let owner_before = test.balance(&owner).await;
+
+    let ix = instruction::member_withdraw(owner, nft.mint, nft.token_account);
+    test.process(&[ix], &[]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&owner).await,
+        owner_before + LAMPORTS_PER_SOL * 2 / 10
+    );
+    assert_eq!(test.nft_balance(&nft.mint).await, 0);
+}
+
+#[tokio::test]
+async fn member_withdraw_rejects_wrong_ledger() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let fake_ledger = fake_account(
+        &mut test,
+        &NftBalanceLedger {
+            version: ACCOUNT_VERSION,
+            nft_balances: vec![NftBalance {
+                nft_address: nft.mint,
+                royalties_balance: LAMPORTS_PER_SOL,
+            }],
+            size: 1,
+        },
+    );
+
+    let mut ix = instruction::member_withdraw(nft.owner.pubkey(), nft.mint, nft.token_account);
+    ix.accounts[4].pubkey = fake_ledger;
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidBalanceLedger,
+    );
+}
+
+#[tokio::test]
+async fn member_withdraw_checks_token_account() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let owner = nft.owner.pubkey();
+
+    // Token account for a different mint
+    let other_mint = test.add_mint();
+    let other_token_account = test.add_token_account(&other_mint, &owner, 1);
+    let ix = instruction::member_withdraw(owner, nft.mint, other_token_account);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidNftAssociatedAccount,
+    );
+
+    // Token account that no longer holds the NFT
+    let empty_token_account = test.add_token_account(&nft.mint, &owner, 0);
+    let ix = instruction::member_withdraw(owner, nft.mint, empty_token_account);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::AssociatedAccountBalanceZero,
+    );
+
+    // Withdrawing to someone other than the holder
+    let ix = instruction::member_withdraw(test.payer(), nft.mint, nft.token_account);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::NftNotOwnedByWithdrawer,
+    );
+}
+
+#[tokio::test]
+async fn member_withdraw_rejects_nft_outside_collection() {
+    let mut test = TestContext::initialized().await;
+    let mint = test.add_mint();
+    let owner = Keypair::new().pubkey();
+    let token_account = test.add_token_account(&mint, &owner, 1);
+
+    let ix = instruction::member_withdraw(owner, mint, token_account);
+
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidNft);
+}
+
+#[tokio::test]
+async fn artist_withdraw_pays_artist() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+    test.add_nft(LAMPORTS_PER_SOL).await;
+    test.fund(&pda::vault().0, 2 * LAMPORTS_PER_SOL);
+    let artist_before = test.balance(&artist).await;
+
+    test.process(&[instruction::artist_withdraw(artist)], &[])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        test.balance(&artist).await,
+        artist_before + LAMPORTS_PER_SOL
+    );
+    assert_eq!(test.artist_balance(&artist).await, 0);
+}
+
+#[tokio::test]
+async fn artist_withdraw_rejects_wrong_ledger_and_unknown_artist() {
+    let mut test = TestContext::initialized().await;
+    let artist = test.artist.pubkey();
+    let fake_ledger = fake_account(
+        &mut test,
+        &ArtistBalanceLedger {
+            version: ACCOUNT_VERSION,
+            artist_balances: vec![],
+            size: 0,
+        },
+    );
+
+    let mut ix = instruction::artist_withdraw(artist);
+    ix.accounts[2].pubkey = fake_ledger;
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidBalanceLedger,
+    );
+
+    let ix = instruction::artist_withdraw(test.payer());
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidArtist);
+}
+
+#[tokio::test]
+async fn transfer_collection_authority() {
+    let mut test = TestContext::initialized().await;
+    let new_authority = test.funded_keypair().pubkey();
+
+    // Needs one of the collection's artists to sign
+    let not_artist = Keypair::new();
+    let ix = instruction::transfer_collection_authority(
+        test.payer(),
+        not_artist.pubkey(),
+        new_authority,
+    );
+    assert_error(
+        test.process(&[ix], &[&not_artist]).await,
+        ErrorCode::InvalidArtist,
+    );
+
+    let artist = test.artist();
+    let ix =
+        instruction::transfer_collection_authority(test.payer(), artist.pubkey(), new_authority);
+    test.process(&[ix], &[&artist]).await.unwrap();
+
+    assert_eq!(test.config().await.collection_authority, new_authority);
+}
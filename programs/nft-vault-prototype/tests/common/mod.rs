@@ -0,0 +1,741 @@
+//! Shared setup for the solana-program-test suites
+//!
+//! The program runs natively through its `entry` function, or from the BPF
+//! build with `cargo test-bpf`. Native program-test can't resize accounts in
+//! a CPI, so every `init` fails there: natively the helpers below write the
+//! accounts those instructions would create, under `test-bpf` they send the
+//! real instructions. Mints and token accounts are always written directly.
+
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+
+use anchor_lang::prelude::{Clock, ProgramError, Pubkey};
+use anchor_lang::{AccountDeserialize, AccountSerialize};
+use nft_vault_client::instruction::{self, CollectionPercentages, LicenseTerms};
+use nft_vault_client::pda;
+use nft_vault_prototype::fee_schedule::{FeeCurrency, FeeSchedule, FeeTier};
+use nft_vault_prototype::governance::{GovernanceAction, HolderGovernance, HolderProposal};
+use nft_vault_prototype::licensing::{License, LicenseStatus, UsageType};
+use nft_vault_prototype::price_feed::{self, UsdPrice};
+use nft_vault_prototype::{
+    ArtistBalance, ArtistBalanceLedger, ArtistPercentage, CollectionConfiguration,
+    ConfigUpdateProposal, ErrorCode, NftBalanceLedger, ACCOUNT_VERSION, INITIAL_ARTIST_CAPACITY,
+    INITIAL_NFT_CAPACITY,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::transport::TransportError;
+use solana_sdk::{system_instruction, system_program};
+use spl_token::solana_program::program_option::COption;
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+pub const PERCENTAGES: CollectionPercentages = CollectionPercentages {
+    artist_mint_percentage: 8000,
+    label_mint_percentage: 2000,
+    artist_secondary_percentage: 5000,
+    label_secondary_percentage: 5000,
+    artist_licensing_percentage: 6000,
+    label_licensing_percentage: 4000,
+};
+
+// Mirrors the program's timelock on config updates
+pub const CONFIG_UPDATE_TIMELOCK: i64 = 60 * 60 * 24 * 2;
+
+// Owner of mock price accounts, the program only checks their address
+const PYTH_PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+pub type TestResult = Result<(), TransportError>;
+
+pub fn token_account_state(mint: &Pubkey, owner: &Pubkey, amount: u64) -> TokenAccount {
+    TokenAccount {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    }
+}
+
+/// Commercial license for the fee, valid from the first slot on
+pub fn license_terms(fee: u64) -> LicenseTerms {
+    LicenseTerms {
+        content_hash: [7; 32],
+        terms_uri: "https://example.com/licenses/sync.pdf".to_string(),
+        fee,
+        usage_type: UsageType::Commercial,
+        start_slot: 0,
+        end_slot: 1_000_000,
+        grace_slots: 0,
+        volume: 0,
+        licensed_nfts: vec![],
+        track_share_percentage: 0,
+        reporter: None,
+        per_use_rate: 0,
+    }
+}
+
+/// Fee schedule with a single price for every usage type
+pub fn flat_fee_tiers(price: u64) -> Vec<FeeTier> {
+    UsageType::ALL
+        .iter()
+        .map(|&usage_type| FeeTier {
+            usage_type,
+            min_volume: 0,
+            price,
+        })
+        .collect()
+}
+
+/// An NFT held by `owner` through `token_account`
+pub struct TestNft {
+    pub mint: Pubkey,
+    pub owner: Keypair,
+    pub token_account: Pubkey,
+}
+
+pub struct TestContext {
+    pub context: ProgramTestContext,
+    pub artist: Keypair,
+    sent: HashSet<Signature>,
+    min_unix_timestamp: i64,
+}
+
+impl TestContext {
+    pub async fn new() -> Self {
+        let mut program_test = ProgramTest::new(
+            "nft_vault_prototype",
+            nft_vault_prototype::id(),
+            processor!(nft_vault_prototype::entry),
+        );
+        // A native program can't invoke the bundled BPF token program
+        if cfg!(not(feature = "test-bpf")) {
+            program_test.add_program(
+                "spl_token",
+                spl_token::id(),
+                processor!(spl_token::processor::Processor::process),
+            );
+        }
+
+        let mut test = TestContext {
+            context: program_test.start_with_context().await,
+            artist: Keypair::new(),
+            sent: HashSet::new(),
+            min_unix_timestamp: 0,
+        };
+        test.fund(&test.artist.pubkey(), LAMPORTS_PER_SOL);
+        test
+    }
+
+    /**
+     * Collection initialized with the default percentages and a single
+     * artist, licensing fees are free
+     */
+    pub async fn initialized() -> Self {
+        let mut test = Self::new().await;
+        let artist = test.artist.pubkey();
+
+        if cfg!(feature = "test-bpf") {
+            let ix = instruction::initialize_collection(test.payer(), PERCENTAGES, artist);
+            let schedule =
+                instruction::initialize_fee_schedule(test.payer(), test.payer(), flat_fee_tiers(0));
+            test.process(&[ix, schedule], &[]).await.unwrap();
+            return test;
+        }
+
+        let config = CollectionConfiguration {
+            version: ACCOUNT_VERSION,
+            collection_authority: test.payer(),
+            artist_mint_percentage: PERCENTAGES.artist_mint_percentage,
+            label_mint_percentage: PERCENTAGES.label_mint_percentage,
+            artist_secondary_percentage: PERCENTAGES.artist_secondary_percentage,
+            label_secondary_percentage: PERCENTAGES.label_secondary_percentage,
+            artist_licensing_percentage: PERCENTAGES.artist_licensing_percentage,
+            label_licensing_percentage: PERCENTAGES.label_licensing_percentage,
+            artist_splits: vec![ArtistPercentage {
+                artist_address: artist,
+                allocated_percentage: 10000,
+            }],
+            artist_reserve_percentage: 0,
+        };
+        test.seed(
+            &pda::collection_config().0,
+            &config,
+            CollectionConfiguration::space(INITIAL_ARTIST_CAPACITY),
+        );
+
+        let nft_ledger = NftBalanceLedger {
+            version: ACCOUNT_VERSION,
+            nft_balances: vec![],
+            size: 0,
+        };
+        test.seed(
+            &pda::nft_ledger().0,
+            &nft_ledger,
+            NftBalanceLedger::space(INITIAL_NFT_CAPACITY),
+        );
+
+        let artist_ledger = ArtistBalanceLedger {
+            version: ACCOUNT_VERSION,
+            artist_balances: vec![ArtistBalance {
+                artist_address: artist,
+                royalties_balance: 0,
+            }],
+            size: 1,
+        };
+        test.seed(
+            &pda::artist_ledger().0,
+            &artist_ledger,
+            ArtistBalanceLedger::space(INITIAL_ARTIST_CAPACITY),
+        );
+
+        let fee_schedule = FeeSchedule {
+            version: ACCOUNT_VERSION,
+            currency: FeeCurrency::Lamports,
+            hold_slots: 0,
+            tiers: flat_fee_tiers(0),
+        };
+        test.seed(&pda::fee_schedule().0, &fee_schedule, FeeSchedule::SIZE);
+
+        test
+    }
+
+    /// Collection authority, also pays for every transaction
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /**
+     * Signs with the payer plus the given signers and processes the transaction
+     *
+     * Moves to a new blockhash when the exact same transaction was already
+     * sent, so retries reach the program instead of failing as duplicates.
+     */
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> TestResult {
+        loop {
+            let blockhash = self.context.banks_client.get_latest_blockhash().await?;
+            let transaction = self.sign(instructions, signers, blockhash);
+            if self.sent.insert(transaction.signatures[0]) {
+                return self
+                    .context
+                    .banks_client
+                    .process_transaction(transaction)
+                    .await;
+            }
+
+            // Warping lands one slot past a frozen bank, which can't be the current one
+            let slot = self.clock().await.slot;
+            self.warp_to_slot(slot + 2).await;
+        }
+    }
+
+    /**
+     * Runs the instructions without committing them, returns the first
+     * error and the logs
+     *
+     * Banks only returns simulation logs for failed transactions, so a
+     * transfer that always fails is appended: an error on that last
+     * instruction means the others succeeded.
+     */
+    pub async fn simulate(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> (TransactionError, Vec<String>) {
+        let mut instructions = instructions.to_vec();
+        instructions.push(system_instruction::transfer(
+            &self.payer(),
+            &Pubkey::new_unique(),
+            u64::MAX,
+        ));
+
+        let blockhash = self
+            .context
+            .banks_client
+            .get_latest_blockhash()
+            .await
+            .unwrap();
+        let transaction = self.sign(&instructions, signers, blockhash);
+        match self
+            .context
+            .banks_client
+            .process_transaction_with_preflight(transaction)
+            .await
+        {
+            Err(BanksClientError::SimulationError { err, logs, .. }) => (err, logs),
+            result => panic!("expected the simulation to fail, got {:?}", result),
+        }
+    }
+
+    fn sign(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+        blockhash: Hash,
+    ) -> Transaction {
+        let mut all_signers: Vec<&Keypair> = vec![&self.context.payer];
+        for signer in signers {
+            if signer.pubkey() != self.context.payer.pubkey() {
+                all_signers.push(signer);
+            }
+        }
+
+        Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        )
+    }
+
+    pub async fn clock(&mut self) -> Clock {
+        self.context
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap()
+    }
+
+    /// Moves the cluster's unix timestamp forward
+    pub async fn advance_clock(&mut self, seconds: i64) {
+        let mut clock = self.clock().await;
+        clock.unix_timestamp += seconds;
+        self.min_unix_timestamp = clock.unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub async fn warp_to_slot(&mut self, slot: u64) {
+        self.context.warp_to_slot(slot).unwrap();
+        self.keep_clock_advanced().await;
+    }
+
+    // Warping recomputes the clock, put back any time skipped forward
+    async fn keep_clock_advanced(&mut self) {
+        let mut clock = self.clock().await;
+        if clock.unix_timestamp < self.min_unix_timestamp {
+            clock.unix_timestamp = self.min_unix_timestamp;
+            self.context.set_sysvar(&clock);
+        }
+    }
+
+    pub fn set_account(&mut self, address: &Pubkey, owner: &Pubkey, lamports: u64, data: Vec<u8>) {
+        self.context.set_account(
+            address,
+            &AccountSharedData::from(Account {
+                lamports,
+                data,
+                owner: *owner,
+                executable: false,
+                rent_epoch: 0,
+            }),
+        );
+    }
+
+    /// Writes a rent exempt program account holding `account`, padded to `space`
+    pub fn seed<T: AccountSerialize>(&mut self, address: &Pubkey, account: &T, space: usize) {
+        let mut data = Vec::with_capacity(space);
+        account.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= space, "account larger than its space");
+        data.resize(space, 0);
+
+        let lamports = Rent::default().minimum_balance(space);
+        self.set_account(address, &nft_vault_prototype::id(), lamports, data);
+    }
+
+    /// Copy of the collection's first artist, to sign while `self` is borrowed
+    pub fn artist(&self) -> Keypair {
+        Keypair::from_bytes(&self.artist.to_bytes()).unwrap()
+    }
+
+    /// New keypair with a funded system account
+    pub fn funded_keypair(&mut self) -> Keypair {
+        let keypair = Keypair::new();
+        self.fund(&keypair.pubkey(), LAMPORTS_PER_SOL);
+        keypair
+    }
+
+    /// Gives a system account the lamports, creating it if needed
+    pub fn fund(&mut self, address: &Pubkey, lamports: u64) {
+        self.set_account(address, &system_program::id(), lamports, vec![]);
+    }
+
+    /// Writes an spl-token account, a mint or a token account
+    pub fn set_token_state<T: Pack>(&mut self, address: &Pubkey, state: T) {
+        let mut data = vec![0; T::LEN];
+        T::pack(state, &mut data).unwrap();
+
+        let lamports = Rent::default().minimum_balance(data.len());
+        self.set_account(address, &spl_token::id(), lamports, data);
+    }
+
+    pub fn add_mint(&mut self) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        self.set_token_state(
+            &mint,
+            Mint {
+                mint_authority: COption::None,
+                supply: 1,
+                decimals: 0,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+        );
+        mint
+    }
+
+    pub fn add_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let token_account = Pubkey::new_unique();
+        self.set_token_state(&token_account, token_account_state(mint, owner, amount));
+        token_account
+    }
+
+    pub async fn token_account(&mut self, address: &Pubkey) -> TokenAccount {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("token account {} not found", address));
+        TokenAccount::unpack(&account.data).unwrap()
+    }
+
+    /// Mints an NFT to a new holder, without adding it to the collection
+    pub fn mint_nft(&mut self) -> TestNft {
+        let mint = self.add_mint();
+        let owner = self.funded_keypair();
+        let token_account = self.add_token_account(&mint, &owner.pubkey(), 1);
+
+        TestNft {
+            mint,
+            owner,
+            token_account,
+        }
+    }
+
+    /// Mints an NFT to a new holder and adds it to the collection
+    pub async fn add_nft(&mut self, amount_paid: u64) -> TestNft {
+        let nft = self.mint_nft();
+
+        let ix = instruction::add_nft(self.payer(), self.payer(), nft.mint, amount_paid);
+        self.process(&[ix], &[]).await.unwrap();
+
+        nft
+    }
+
+    /**
+     * Proposes a config update from the collection authority
+     *
+     * `required_approvals` are the artists the update affects. The BPF run
+     * checks the program works out the same list.
+     */
+    pub async fn propose_config_update(
+        &mut self,
+        percentages: CollectionPercentages,
+        artist_splits: Vec<ArtistPercentage>,
+        required_approvals: Vec<Pubkey>,
+    ) {
+        let address = pda::config_update().0;
+
+        if cfg!(feature = "test-bpf") {
+            let ix = instruction::propose_config_update(
+                self.payer(),
+                self.payer(),
+                percentages,
+                artist_splits,
+            );
+            self.process(&[ix], &[]).await.unwrap();
+
+            let proposal: ConfigUpdateProposal = self.account(&address).await;
+            assert_eq!(proposal.required_approvals, required_approvals);
+            return;
+        }
+
+        let artist_count = self.config().await.artist_splits.len();
+        let space = ConfigUpdateProposal::space(artist_splits.len(), artist_count);
+        let proposal = ConfigUpdateProposal {
+            version: ACCOUNT_VERSION,
+            proposer: self.payer(),
+            artist_mint_percentage: percentages.artist_mint_percentage,
+            label_mint_percentage: percentages.label_mint_percentage,
+            artist_secondary_percentage: percentages.artist_secondary_percentage,
+            label_secondary_percentage: percentages.label_secondary_percentage,
+            artist_licensing_percentage: percentages.artist_licensing_percentage,
+            label_licensing_percentage: percentages.label_licensing_percentage,
+            artist_splits,
+            required_approvals,
+            approvals: vec![],
+            executable_at: self.clock().await.unix_timestamp + CONFIG_UPDATE_TIMELOCK,
+        };
+        self.seed(&address, &proposal, space);
+    }
+
+    pub async fn initialize_holder_governance(
+        &mut self,
+        quorum_percentage: u64,
+        voting_period: i64,
+    ) {
+        if cfg!(feature = "test-bpf") {
+            let ix = instruction::initialize_holder_governance(
+                self.payer(),
+                self.payer(),
+                quorum_percentage,
+                voting_period,
+            );
+            self.process(&[ix], &[]).await.unwrap();
+            return;
+        }
+
+        let governance = HolderGovernance {
+            version: ACCOUNT_VERSION,
+            quorum_percentage,
+            voting_period,
+            proposal_count: 0,
+        };
+        self.seed(
+            &pda::holder_governance().0,
+            &governance,
+            HolderGovernance::SIZE,
+        );
+    }
+
+    /// Creates a holder proposal from the NFT's owner, returns its id
+    pub async fn create_holder_proposal(&mut self, nft: &TestNft, action: GovernanceAction) -> u64 {
+        let governance_address = pda::holder_governance().0;
+        let mut governance: HolderGovernance = self.account(&governance_address).await;
+        let id = governance.proposal_count;
+
+        if cfg!(feature = "test-bpf") {
+            let ix = instruction::create_holder_proposal(
+                nft.owner.pubkey(),
+                id,
+                nft.mint,
+                nft.token_account,
+                action,
+            );
+            self.process(&[ix], &[&nft.owner]).await.unwrap();
+            return id;
+        }
+
+        let nft_ledger: NftBalanceLedger = self.account(&pda::nft_ledger().0).await;
+        let space = HolderProposal::space(&action, nft_ledger.size);
+        let proposal = HolderProposal {
+            version: ACCOUNT_VERSION,
+            id,
+            proposer: nft.owner.pubkey(),
+            action,
+            snapshot_size: nft_ledger.size,
+            quorum_percentage: governance.quorum_percentage,
+            voting_ends_at: self.clock().await.unix_timestamp + governance.voting_period,
+            yes_votes: 0,
+            no_votes: 0,
+            votes: vec![0; HolderProposal::votes_len(nft_ledger.size)],
+            executed: false,
+        };
+        self.seed(&pda::holder_proposal(id).0, &proposal, space);
+
+        governance.proposal_count += 1;
+        self.seed(&governance_address, &governance, HolderGovernance::SIZE);
+        id
+    }
+
+    /// Writes a mock Pyth price account holding only what the program reads
+    pub fn set_price_feed(&mut self, address: &Pubkey, price: UsdPrice) {
+        let mut data = vec![0; price_feed::PRICE_ACCOUNT_MIN_LEN];
+        let mut write = |offset: usize, bytes: &[u8]| {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        write(
+            price_feed::MAGIC_OFFSET,
+            &price_feed::PYTH_MAGIC.to_le_bytes(),
+        );
+        write(
+            price_feed::VERSION_OFFSET,
+            &price_feed::PYTH_VERSION.to_le_bytes(),
+        );
+        write(
+            price_feed::ACCOUNT_TYPE_OFFSET,
+            &price_feed::PYTH_PRICE_ACCOUNT_TYPE.to_le_bytes(),
+        );
+        write(price_feed::EXPO_OFFSET, &price.expo.to_le_bytes());
+        write(price_feed::AGG_PRICE_OFFSET, &price.price.to_le_bytes());
+        write(price_feed::AGG_CONF_OFFSET, &price.conf.to_le_bytes());
+        write(price_feed::AGG_STATUS_OFFSET, &price.status.to_le_bytes());
+        write(
+            price_feed::AGG_PUB_SLOT_OFFSET,
+            &price.pub_slot.to_le_bytes(),
+        );
+
+        let lamports = Rent::default().minimum_balance(data.len());
+        self.set_account(address, &PYTH_PROGRAM_ID, lamports, data);
+    }
+
+    /// Creates a license signed by the collection authority, returns its address
+    pub async fn create_license(&mut self, licensee: &Keypair, terms: LicenseTerms) -> Pubkey {
+        let address = pda::license(&licensee.pubkey(), &terms.content_hash).0;
+
+        if cfg!(feature = "test-bpf") {
+            let ix = instruction::create_license(self.payer(), licensee.pubkey(), terms);
+            self.process(&[ix], &[licensee]).await.unwrap();
+            return address;
+        }
+
+        // Token accounts create_license would initialize
+        let token_mint = pda::license_mint(&address).0;
+        let token_account = pda::license_token_account(&address).0;
+        let license_authority = pda::license_authority().0;
+        self.set_token_state(
+            &token_mint,
+            Mint {
+                mint_authority: COption::Some(license_authority),
+                supply: 0,
+                decimals: 0,
+                is_initialized: true,
+                freeze_authority: COption::Some(license_authority),
+            },
+        );
+        self.set_token_state(
+            &token_account,
+            token_account_state(&token_mint, &licensee.pubkey(), 0),
+        );
+
+        let license = License {
+            version: ACCOUNT_VERSION,
+            licensee: licensee.pubkey(),
+            collection: pda::collection_config().0,
+            terms_uri: terms.terms_uri,
+            content_hash: terms.content_hash,
+            fee: terms.fee,
+            usage_type: terms.usage_type,
+            start_slot: terms.start_slot,
+            end_slot: terms.end_slot,
+            term_slots: terms.end_slot - terms.start_slot,
+            grace_slots: terms.grace_slots,
+            status: LicenseStatus::Active,
+            volume: terms.volume,
+            fee_tier: None,
+            licensed_nfts: terms.licensed_nfts,
+            track_share_percentage: terms.track_share_percentage,
+            pending_payments: vec![],
+            reporter: terms.reporter.unwrap_or_else(|| licensee.pubkey()),
+            per_use_rate: terms.per_use_rate,
+            last_usage_period: None,
+            total_uses: 0,
+            outstanding_fee: 0,
+            paid_to_date: 0,
+            token_mint,
+            token_account,
+        };
+        self.seed(&address, &license, License::SIZE);
+        address
+    }
+
+    /// License with the default terms, the payer is the licensee
+    pub async fn payer_license(&mut self) -> Pubkey {
+        let payer = Keypair::from_bytes(&self.context.payer.to_bytes()).unwrap();
+        self.create_license(&payer, license_terms(LAMPORTS_PER_SOL))
+            .await
+    }
+
+    pub async fn try_account<T: AccountDeserialize>(&mut self, address: &Pubkey) -> Option<T> {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()?;
+        Some(T::try_deserialize(&mut &account.data[..]).unwrap())
+    }
+
+    pub async fn account<T: AccountDeserialize>(&mut self, address: &Pubkey) -> T {
+        self.try_account(address)
+            .await
+            .unwrap_or_else(|| panic!("account {} not found", address))
+    }
+
+    pub async fn account_len(&mut self, address: &Pubkey) -> usize {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .map(|account| account.data.len())
+            .unwrap_or_default()
+    }
+
+    pub async fn balance(&mut self, address: &Pubkey) -> u64 {
+        self.context
+            .banks_client
+            .get_balance(*address)
+            .await
+            .unwrap()
+    }
+
+    pub async fn config(&mut self) -> CollectionConfiguration {
+        self.account(&pda::collection_config().0).await
+    }
+
+    pub async fn nft_balance(&mut self, nft: &Pubkey) -> u64 {
+        let ledger: NftBalanceLedger = self.account(&pda::nft_ledger().0).await;
+        ledger
+            .nft_balances
+            .iter()
+            .find(|balance| balance.nft_address == *nft)
+            .map(|balance| balance.royalties_balance)
+            .unwrap_or_else(|| panic!("nft {} not in ledger", nft))
+    }
+
+    pub async fn artist_balance(&mut self, artist: &Pubkey) -> u64 {
+        let ledger: ArtistBalanceLedger = self.account(&pda::artist_ledger().0).await;
+        ledger
+            .artist_balances
+            .iter()
+            .find(|balance| balance.artist_address == *artist)
+            .map(|balance| balance.royalties_balance)
+            .unwrap_or_else(|| panic!("artist {} not in ledger", artist))
+    }
+}
+
+pub fn error_code(error: ErrorCode) -> u32 {
+    let error: nft_vault_prototype::Error = error.into();
+    match ProgramError::from(error) {
+        ProgramError::Custom(code) => code,
+        error => panic!("not a custom error: {:?}", error),
+    }
+}
+
+/// Asserts the transaction failed in the program with the given error
+pub fn assert_error(result: TestResult, expected: ErrorCode) {
+    let expected = error_code(expected);
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        ))) if code == expected => {}
+        result => panic!("expected custom error {}, got {:?}", expected, result),
+    }
+}
+
+/// Asserts the transaction failed, for failures raised outside the program's ErrorCode
+pub fn assert_instruction_error(result: TestResult, expected: InstructionError) {
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(_, error)))
+            if error == expected => {}
+        result => panic!("expected {:?}, got {:?}", expected, result),
+    }
+}
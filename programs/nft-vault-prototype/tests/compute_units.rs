@@ -0,0 +1,313 @@
+//! Compute units of the ledger instructions across collection sizes
+//!
+//! Measures each instruction with 10 to 10,000 NFTs (and one artist), then
+//! 10 to 10,000 artists (and 10 NFTs), and compares the results to
+//! compute_units.txt. Using more than TOLERANCE_PERCENTAGE above the
+//! baseline, or failing where the baseline passed, is a regression, and so
+//! is a measurement the baseline has no entry for.
+//! `UPDATE_COMPUTE_UNITS=1 cargo test-bpf --test compute_units` rewrites the
+//! baseline. Native program-test doesn't meter compute units, so this only
+//! runs against the BPF build.
+
+mod common;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use common::*;
+use nft_vault_client::instruction;
+use nft_vault_client::pda;
+use nft_vault_prototype::{
+    ArtistBalance, ArtistBalanceLedger, ArtistPercentage, CollectionConfiguration, NftBalance,
+    NftBalanceLedger, ACCOUNT_VERSION,
+};
+use solana_program_test::tokio;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::TransactionError;
+
+const SIZES: [usize; 4] = [10, 100, 1_000, 10_000];
+const TOLERANCE_PERCENTAGE: u64 = 2;
+
+// Owed to every NFT and artist in the seeded ledgers
+const BALANCE: u64 = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Outcome {
+    Units(u64),
+    Failed(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Units(units) => write!(f, "{}", units),
+            Outcome::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Outcome {
+    fn parse(value: &str) -> Self {
+        value
+            .parse()
+            .map(Outcome::Units)
+            .unwrap_or_else(|_| Outcome::Failed(value.to_string()))
+    }
+}
+
+// Instruction, ledger grown and its size
+type Key = (String, String, usize);
+
+struct Measurement {
+    key: Key,
+    outcome: Outcome,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (instruction, ledger, size) = &self.key;
+        write!(f, "{} {} {} {}", instruction, ledger, size, self.outcome)
+    }
+}
+
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/compute_units.txt")
+}
+
+// One `<instruction> <ledger> <size> <units or failure>` per line
+fn read_baseline() -> HashMap<Key, Outcome> {
+    let contents = std::fs::read_to_string(baseline_path()).unwrap_or_default();
+
+    contents
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.splitn(4, ' ').collect();
+            match fields[..] {
+                [instruction, ledger, size, outcome] => (
+                    (
+                        instruction.to_string(),
+                        ledger.to_string(),
+                        size.parse().expect("invalid size in baseline"),
+                    ),
+                    Outcome::parse(outcome),
+                ),
+                _ => panic!("invalid baseline line `{}`", line),
+            }
+        })
+        .collect()
+}
+
+fn write_baseline(measurements: &[Measurement]) {
+    let mut contents =
+        String::from("# Written by UPDATE_COMPUTE_UNITS=1 cargo test-bpf --test compute_units\n");
+    for measurement in measurements {
+        contents.push_str(&format!("{}\n", measurement));
+    }
+    std::fs::write(baseline_path(), contents).unwrap();
+}
+
+fn is_regression(baseline: &Outcome, outcome: &Outcome) -> bool {
+    match (baseline, outcome) {
+        (Outcome::Units(baseline), Outcome::Units(units)) => {
+            units * 100 > baseline * (100 + TOLERANCE_PERCENTAGE)
+        }
+        (Outcome::Units(_), Outcome::Failed(_)) => true,
+        (Outcome::Failed(_), _) => false,
+    }
+}
+
+/**
+ * Writes ledgers holding nft_count NFTs and artist_count artists, each owed
+ * BALANCE. The returned NFT and artist are the last entries, the worst case
+ * for the ledger lookups.
+ */
+async fn seeded(nft_count: usize, artist_count: usize) -> (TestContext, TestNft, Keypair) {
+    let mut test = TestContext::initialized().await;
+    let nft = test.mint_nft();
+    let artist = test.funded_keypair();
+
+    let mut artists: Vec<Pubkey> = (1..artist_count).map(|_| Pubkey::new_unique()).collect();
+    artists.push(artist.pubkey());
+    let mut nfts: Vec<Pubkey> = (1..nft_count).map(|_| Pubkey::new_unique()).collect();
+    nfts.push(nft.mint);
+
+    let config = CollectionConfiguration {
+        version: ACCOUNT_VERSION,
+        collection_authority: test.payer(),
+        artist_mint_percentage: PERCENTAGES.artist_mint_percentage,
+        label_mint_percentage: PERCENTAGES.label_mint_percentage,
+        artist_secondary_percentage: PERCENTAGES.artist_secondary_percentage,
+        label_secondary_percentage: PERCENTAGES.label_secondary_percentage,
+        artist_licensing_percentage: PERCENTAGES.artist_licensing_percentage,
+        label_licensing_percentage: PERCENTAGES.label_licensing_percentage,
+        // Every size divides 100%
+        artist_splits: artists
+            .iter()
+            .map(|artist| ArtistPercentage {
+                artist_address: *artist,
+                allocated_percentage: 10000 / artist_count as u64,
+            })
+            .collect(),
+        artist_reserve_percentage: 0,
+    };
+    test.seed(
+        &pda::collection_config().0,
+        &config,
+        CollectionConfiguration::space(artist_count),
+    );
+
+    let nft_ledger = NftBalanceLedger {
+        version: ACCOUNT_VERSION,
+        nft_balances: nfts
+            .iter()
+            .map(|nft| NftBalance {
+                nft_address: *nft,
+                royalties_balance: BALANCE,
+            })
+            .collect(),
+        size: nft_count as u64,
+    };
+    test.seed(
+        &pda::nft_ledger().0,
+        &nft_ledger,
+        NftBalanceLedger::space(nft_count),
+    );
+
+    let artist_ledger = ArtistBalanceLedger {
+        version: ACCOUNT_VERSION,
+        artist_balances: artists
+            .iter()
+            .map(|artist| ArtistBalance {
+                artist_address: *artist,
+                royalties_balance: BALANCE,
+            })
+            .collect(),
+        size: artist_count as u64,
+    };
+    test.seed(
+        &pda::artist_ledger().0,
+        &artist_ledger,
+        ArtistBalanceLedger::space(artist_count),
+    );
+
+    let owed = BALANCE * (nft_count + artist_count) as u64;
+    test.fund(&pda::vault().0, Rent::default().minimum_balance(0) + owed);
+    test.fund(&pda::secondary_pool().0, LAMPORTS_PER_SOL);
+
+    (test, nft, artist)
+}
+
+async fn measure(test: &mut TestContext, ix: Instruction) -> Outcome {
+    let (error, logs) = test.simulate(&[ix], &[]).await;
+
+    match error {
+        // Only the appended transfer failed
+        TransactionError::InstructionError(1, _) => {
+            let prefix = format!("Program {} consumed ", nft_vault_prototype::id());
+            let units = logs
+                .iter()
+                .find_map(|line| line.strip_prefix(&prefix)?.split(' ').next()?.parse().ok())
+                .expect("no compute units in the program logs");
+            Outcome::Units(units)
+        }
+        _ if logs.iter().any(|line| line.contains("out of memory")) => {
+            Outcome::Failed("out of memory".to_string())
+        }
+        _ if logs.iter().any(|line| line.contains("exceeded")) => {
+            Outcome::Failed("compute budget exceeded".to_string())
+        }
+        error => Outcome::Failed(format!("{:?}", error)),
+    }
+}
+
+async fn measure_instructions(
+    ledger: &str,
+    nft_count: usize,
+    artist_count: usize,
+) -> Vec<Measurement> {
+    let size = if ledger == "nfts" {
+        nft_count
+    } else {
+        artist_count
+    };
+    let (mut test, nft, artist) = seeded(nft_count, artist_count).await;
+    let payer = test.payer();
+    let new_nft = test.mint_nft();
+    let license = test.payer_license().await;
+
+    let instructions = [
+        (
+            "add_nft",
+            instruction::add_nft(payer, payer, new_nft.mint, LAMPORTS_PER_SOL),
+        ),
+        (
+            "pay_licensing_fee",
+            instruction::pay_licensing_fee(payer, license, LAMPORTS_PER_SOL),
+        ),
+        (
+            "distribute_secondary_pool",
+            instruction::distribute_secondary_pool(),
+        ),
+        (
+            "member_withdraw",
+            instruction::member_withdraw(nft.owner.pubkey(), nft.mint, nft.token_account),
+        ),
+        (
+            "artist_withdraw",
+            instruction::artist_withdraw(artist.pubkey()),
+        ),
+    ];
+
+    let mut measurements = Vec::new();
+    for (name, ix) in instructions {
+        measurements.push(Measurement {
+            key: (name.to_string(), ledger.to_string(), size),
+            outcome: measure(&mut test, ix).await,
+        });
+    }
+    measurements
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "measures BPF compute units, run with cargo test-bpf"
+)]
+async fn compute_units_within_baseline() {
+    let mut measurements = Vec::new();
+    for size in SIZES {
+        measurements.extend(measure_instructions("nfts", size, 1).await);
+    }
+    for size in SIZES {
+        measurements.extend(measure_instructions("artists", 10, size).await);
+    }
+
+    if std::env::var("UPDATE_COMPUTE_UNITS").is_ok() {
+        write_baseline(&measurements);
+        return;
+    }
+
+    let baseline = read_baseline();
+    let mut regressions = Vec::new();
+    for measurement in &measurements {
+        match baseline.get(&measurement.key) {
+            Some(expected) if is_regression(expected, &measurement.outcome) => {
+                regressions.push(format!("{} (baseline {})", measurement, expected));
+            }
+            Some(expected) => println!("{} (baseline {})", measurement, expected),
+            None => regressions.push(format!("{} (not in baseline)", measurement)),
+        }
+    }
+
+    assert!(
+        regressions.is_empty(),
+        "compute unit regressions, record new instructions or sizes with \
+         UPDATE_COMPUTE_UNITS=1:\n{}",
+        regressions.join("\n")
+    );
+}
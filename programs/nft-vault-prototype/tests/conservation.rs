@@ -0,0 +1,242 @@
+//! Property tests for the royalty math
+//!
+//! Random sequences of deposits and withdrawals run through the program,
+//! checking after each step that:
+//! - the vault holds at least what the ledgers owe
+//! - ledger balances only go down on withdraw
+//! - every holder gets the same share of a distribution, to the lamport
+//!
+//! Amounts are split by rounding down, the dust stays in the vault.
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction;
+use nft_vault_client::pda;
+use nft_vault_prototype::{ArtistBalanceLedger, ErrorCode, NftBalanceLedger};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::Signer;
+use solana_sdk::system_instruction;
+
+// Up to 10,000 SOL per deposit, the payer can cover a full sequence
+const MAX_AMOUNT: u64 = 10_000 * LAMPORTS_PER_SOL;
+
+#[derive(Clone, Debug)]
+enum Step {
+    // Mint proceeds are deposited in the vault as the NFT is added
+    AddNft(u64),
+    PayLicensingFee(u64),
+    DistributeSecondaryPool(u64),
+    // Indexes wrap around the NFTs or artists in the ledger
+    MemberWithdraw(usize),
+    ArtistWithdraw(usize),
+}
+
+fn amount() -> impl Strategy<Value = u64> {
+    // Small amounts hit the rounding, large ones the multiplication
+    prop_oneof![0..100_000u64, 0..=MAX_AMOUNT]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        amount().prop_map(Step::AddNft),
+        amount().prop_map(Step::PayLicensingFee),
+        amount().prop_map(Step::DistributeSecondaryPool),
+        any::<usize>().prop_map(Step::MemberWithdraw),
+        any::<usize>().prop_map(Step::ArtistWithdraw),
+    ]
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    vault: u64,
+    nfts: Vec<(Pubkey, u64)>,
+    artists: Vec<(Pubkey, u64)>,
+}
+
+impl Snapshot {
+    async fn take(test: &mut TestContext) -> Self {
+        let nft_ledger: NftBalanceLedger = test.account(&pda::nft_ledger().0).await;
+        let artist_ledger: ArtistBalanceLedger = test.account(&pda::artist_ledger().0).await;
+
+        Snapshot {
+            vault: test.balance(&pda::vault().0).await,
+            nfts: nft_ledger
+                .nft_balances
+                .iter()
+                .map(|nft| (nft.nft_address, nft.royalties_balance))
+                .collect(),
+            artists: artist_ledger
+                .artist_balances
+                .iter()
+                .map(|artist| (artist.artist_address, artist.royalties_balance))
+                .collect(),
+        }
+    }
+
+    fn liabilities(&self) -> u64 {
+        self.nfts
+            .iter()
+            .chain(self.artists.iter())
+            .map(|(_, balance)| balance)
+            .sum()
+    }
+
+    fn balances(&self) -> impl Iterator<Item = &(Pubkey, u64)> {
+        self.nfts.iter().chain(self.artists.iter())
+    }
+
+    fn balance(&self, address: &Pubkey) -> Option<u64> {
+        self.balances()
+            .find(|(entry, _)| entry == address)
+            .map(|(_, balance)| *balance)
+    }
+}
+
+// Nothing owed before the step went down, except the withdrawn balance
+fn check_no_decrease(before: &Snapshot, after: &Snapshot, withdrawn: Option<&Pubkey>) {
+    for (address, balance) in before.balances() {
+        let balance_after = after.balance(address).expect("ledger entry removed");
+        if Some(address) == withdrawn {
+            assert_eq!(balance_after, 0, "withdrawn balance not emptied");
+        } else {
+            assert!(
+                balance_after >= *balance,
+                "balance of {} decreased",
+                address
+            );
+        }
+    }
+}
+
+// NFTs in the ledger before a distribution all receive the same amount
+fn check_equal_shares(before: &Snapshot, after: &Snapshot) {
+    let shares: Vec<u64> = before
+        .nfts
+        .iter()
+        .map(|(address, balance)| after.balance(address).unwrap() - balance)
+        .collect();
+
+    if let (Some(min), Some(max)) = (shares.iter().min(), shares.iter().max()) {
+        assert!(max - min <= 1, "holder shares differ: {:?}", shares);
+    }
+}
+
+async fn run(second_artist_percentage: u64, steps: Vec<Step>) {
+    let mut test = TestContext::initialized().await;
+    let mut nfts = Vec::new();
+    let mut artists = vec![test.artist()];
+
+    // Rounding across artists needs more than one split
+    if second_artist_percentage > 0 {
+        let artist = test.artist();
+        let second = test.funded_keypair();
+        let ix = instruction::transfer_artist_split(
+            test.payer(),
+            artist.pubkey(),
+            second.pubkey(),
+            second_artist_percentage,
+        );
+        test.process(&[ix], &[&artist]).await.unwrap();
+        artists.push(second);
+    }
+
+    // Rent exemption for the vault itself, not available to pay out
+    let vault = pda::vault().0;
+    let vault_reserve = Rent::default().minimum_balance(0);
+    test.fund(&vault, vault_reserve);
+    let license = test.payer_license().await;
+
+    for step in steps {
+        let before = Snapshot::take(&mut test).await;
+        let mut withdrawn = None;
+
+        match step {
+            Step::AddNft(amount) => {
+                let nft = test.mint_nft();
+                let deposit = system_instruction::transfer(&test.payer(), &vault, amount);
+                let ix = instruction::add_nft(test.payer(), test.payer(), nft.mint, amount);
+                test.process(&[deposit, ix], &[]).await.unwrap();
+                nfts.push(nft);
+            }
+            Step::PayLicensingFee(amount) => {
+                let ix = instruction::pay_licensing_fee(test.payer(), license, amount);
+                let result = test.process(&[ix], &[]).await;
+                if nfts.is_empty() {
+                    assert_error(result, ErrorCode::NoNftsInCollection);
+                } else {
+                    result.unwrap();
+                }
+            }
+            Step::DistributeSecondaryPool(amount) => {
+                let pool = pda::secondary_pool().0;
+                let pool_balance = test.balance(&pool).await;
+                test.fund(&pool, pool_balance + amount);
+
+                let result = test
+                    .process(&[instruction::distribute_secondary_pool()], &[])
+                    .await;
+                if nfts.is_empty() {
+                    assert_error(result, ErrorCode::NoNftsInCollection);
+                } else {
+                    result.unwrap();
+                }
+            }
+            Step::MemberWithdraw(index) => {
+                if nfts.is_empty() {
+                    continue;
+                }
+                let nft = &nfts[index % nfts.len()];
+                let ix =
+                    instruction::member_withdraw(nft.owner.pubkey(), nft.mint, nft.token_account);
+                test.process(&[ix], &[]).await.unwrap();
+                withdrawn = Some(nft.mint);
+            }
+            Step::ArtistWithdraw(index) => {
+                let artist = artists[index % artists.len()].pubkey();
+                let ix = instruction::artist_withdraw(artist);
+                test.process(&[ix], &[]).await.unwrap();
+                withdrawn = Some(artist);
+            }
+        }
+
+        let after = Snapshot::take(&mut test).await;
+
+        assert!(
+            after.vault - vault_reserve >= after.liabilities(),
+            "vault holds {} but owes {}",
+            after.vault - vault_reserve,
+            after.liabilities()
+        );
+        check_no_decrease(&before, &after, withdrawn.as_ref());
+
+        match withdrawn {
+            // Withdrawals pay out exactly the ledger balance
+            Some(address) => assert_eq!(
+                before.vault - after.vault,
+                before.balance(&address).unwrap()
+            ),
+            None => check_equal_shares(&before, &after),
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn royalties_are_conserved(
+        second_artist_percentage in prop_oneof![Just(0u64), 1..10000u64],
+        steps in vec(step(), 1..24),
+    ) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(run(second_artist_percentage, steps));
+    }
+}
@@ -0,0 +1,152 @@
+//! Licensing fee schedule and the prices it enforces on payments
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction::{self, LicenseTerms};
+use nft_vault_client::pda;
+use nft_vault_prototype::fee_schedule::{FeeSchedule, FeeTier, MAX_FEE_TIERS};
+use nft_vault_prototype::licensing::{License, UsageType};
+use nft_vault_prototype::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+
+// Flat 1 SOL, games licenses cost more from 10,000 and 100,000 units
+fn games_tiers() -> Vec<FeeTier> {
+    let mut tiers = flat_fee_tiers(LAMPORTS_PER_SOL);
+    tiers.push(FeeTier {
+        usage_type: UsageType::Games,
+        min_volume: 10_000,
+        price: 3 * LAMPORTS_PER_SOL,
+    });
+    tiers.push(FeeTier {
+        usage_type: UsageType::Games,
+        min_volume: 100_000,
+        price: 5 * LAMPORTS_PER_SOL,
+    });
+    tiers
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn initialize_fee_schedule_records_tiers() {
+    let mut test = TestContext::new().await;
+    let ix = instruction::initialize_collection(test.payer(), PERCENTAGES, test.artist.pubkey());
+    test.process(&[ix], &[]).await.unwrap();
+
+    let fake_authority = test.funded_keypair();
+    let ix =
+        instruction::initialize_fee_schedule(test.payer(), fake_authority.pubkey(), games_tiers());
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::initialize_fee_schedule(test.payer(), test.payer(), games_tiers());
+    test.process(&[ix], &[]).await.unwrap();
+
+    let fee_schedule: FeeSchedule = test.account(&pda::fee_schedule().0).await;
+    assert_eq!(fee_schedule.tiers, games_tiers());
+}
+
+#[tokio::test]
+async fn update_fee_schedule_checks_tiers() {
+    let mut test = TestContext::initialized().await;
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::update_fee_schedule(fake_authority.pubkey(), games_tiers());
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let mut unpriced = flat_fee_tiers(LAMPORTS_PER_SOL);
+    unpriced.retain(|tier| tier.usage_type != UsageType::Personal);
+    let mut duplicate = games_tiers();
+    duplicate.push(FeeTier {
+        price: 4 * LAMPORTS_PER_SOL,
+        ..duplicate[4]
+    });
+    let mut too_many = flat_fee_tiers(LAMPORTS_PER_SOL);
+    too_many.extend((1..=MAX_FEE_TIERS as u64).map(|min_volume| FeeTier {
+        usage_type: UsageType::Broadcast,
+        min_volume,
+        price: LAMPORTS_PER_SOL,
+    }));
+    for tiers in [unpriced, duplicate, too_many] {
+        let ix = instruction::update_fee_schedule(test.payer(), tiers);
+        assert_error(
+            test.process(&[ix], &[]).await,
+            ErrorCode::InvalidFeeSchedule,
+        );
+    }
+
+    let ix = instruction::update_fee_schedule(test.payer(), games_tiers());
+    test.process(&[ix], &[]).await.unwrap();
+
+    let fee_schedule: FeeSchedule = test.account(&pda::fee_schedule().0).await;
+    assert_eq!(fee_schedule.tiers, games_tiers());
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_charges_volume_tier() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let ix = instruction::update_fee_schedule(test.payer(), games_tiers());
+    test.process(&[ix], &[]).await.unwrap();
+
+    let licensee = test.funded_keypair();
+    test.fund(&licensee.pubkey(), 10 * LAMPORTS_PER_SOL);
+    let terms = LicenseTerms {
+        usage_type: UsageType::Games,
+        volume: 50_000,
+        ..license_terms(3 * LAMPORTS_PER_SOL)
+    };
+    let address = test.create_license(&licensee, terms).await;
+
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), address, 2 * LAMPORTS_PER_SOL);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::LicensingFeeTooLow,
+    );
+
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), address, 3 * LAMPORTS_PER_SOL);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    let license: License = test.account(&address).await;
+    assert_eq!(license.fee_tier, Some(games_tiers()[4]));
+    assert_eq!(license.paid_to_date, 3 * LAMPORTS_PER_SOL);
+
+    // Other usage types keep their base price at any volume
+    let terms = LicenseTerms {
+        content_hash: [8; 32],
+        usage_type: UsageType::Personal,
+        volume: 50_000,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let address = test.create_license(&licensee, terms).await;
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), address, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    let license: License = test.account(&address).await;
+    assert_eq!(license.fee_tier, Some(games_tiers()[3]));
+}
+
+#[tokio::test]
+async fn renew_license_rejects_fee_below_schedule() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let ix = instruction::update_fee_schedule(test.payer(), flat_fee_tiers(2 * LAMPORTS_PER_SOL));
+    test.process(&[ix], &[]).await.unwrap();
+    let license = test.payer_license().await;
+
+    let ix = instruction::renew_license(test.payer(), license);
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::LicensingFeeTooLow,
+    );
+}
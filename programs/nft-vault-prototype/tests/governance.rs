@@ -0,0 +1,299 @@
+//! Holder governance over the label percentages and fee tiers
+
+mod common;
+
+use common::*;
+use nft_vault_client::{instruction, pda};
+use nft_vault_prototype::fee_schedule::{FeeSchedule, FeeTier};
+use nft_vault_prototype::governance::GovernanceAction;
+use nft_vault_prototype::licensing::UsageType;
+use nft_vault_prototype::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+
+const VOTING_PERIOD: i64 = 100;
+
+fn update_holder_percentages(percentage: u64) -> GovernanceAction {
+    GovernanceAction::UpdateHolderPercentages {
+        label_mint_percentage: percentage,
+        label_secondary_percentage: percentage,
+        label_licensing_percentage: percentage,
+    }
+}
+
+async fn vote(
+    test: &mut TestContext,
+    proposal_id: u64,
+    nft: &TestNft,
+    approve: bool,
+) -> TestResult {
+    let ix = instruction::cast_holder_vote(
+        nft.owner.pubkey(),
+        proposal_id,
+        nft.mint,
+        nft.token_account,
+        approve,
+    );
+    test.process(&[ix], &[&nft.owner]).await
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn initialize_holder_governance_checks_config() {
+    let mut test = TestContext::initialized().await;
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::initialize_holder_governance(
+        test.payer(),
+        fake_authority.pubkey(),
+        5000,
+        VOTING_PERIOD,
+    );
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    for (quorum_percentage, voting_period) in
+        [(0, VOTING_PERIOD), (10001, VOTING_PERIOD), (5000, 0)]
+    {
+        let ix = instruction::initialize_holder_governance(
+            test.payer(),
+            test.payer(),
+            quorum_percentage,
+            voting_period,
+        );
+        assert_error(
+            test.process(&[ix], &[]).await,
+            ErrorCode::InvalidGovernanceConfig,
+        );
+    }
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn create_holder_proposal_checks_holder_and_action() {
+    let mut test = TestContext::initialized().await;
+    test.initialize_holder_governance(5000, VOTING_PERIOD).await;
+    let nft = test.add_nft(0).await;
+
+    let ix = instruction::create_holder_proposal(
+        nft.owner.pubkey(),
+        0,
+        nft.mint,
+        nft.token_account,
+        update_holder_percentages(10001),
+    );
+    assert_error(
+        test.process(&[ix], &[&nft.owner]).await,
+        ErrorCode::InvalidCollectionConfig,
+    );
+
+    let not_holder = test.funded_keypair();
+    let ix = instruction::create_holder_proposal(
+        not_holder.pubkey(),
+        0,
+        nft.mint,
+        nft.token_account,
+        update_holder_percentages(3000),
+    );
+    assert_error(
+        test.process(&[ix], &[&not_holder]).await,
+        ErrorCode::NftNotOwnedByWithdrawer,
+    );
+
+    let outside_mint = test.add_mint();
+    let outside_token_account = test.add_token_account(&outside_mint, &nft.owner.pubkey(), 1);
+    let ix = instruction::create_holder_proposal(
+        nft.owner.pubkey(),
+        0,
+        outside_mint,
+        outside_token_account,
+        update_holder_percentages(3000),
+    );
+    assert_error(
+        test.process(&[ix], &[&nft.owner]).await,
+        ErrorCode::InvalidNft,
+    );
+}
+
+#[tokio::test]
+async fn holder_proposal_updates_percentages() {
+    let mut test = TestContext::initialized().await;
+    test.initialize_holder_governance(5000, VOTING_PERIOD).await;
+    let nft_1 = test.add_nft(0).await;
+    let nft_2 = test.add_nft(0).await;
+    test.add_nft(0).await;
+
+    let id = test
+        .create_holder_proposal(&nft_1, update_holder_percentages(3000))
+        .await;
+
+    vote(&mut test, id, &nft_1, true).await.unwrap();
+    vote(&mut test, id, &nft_2, true).await.unwrap();
+    assert_error(
+        vote(&mut test, id, &nft_2, false).await,
+        ErrorCode::NftAlreadyVoted,
+    );
+
+    let artist = test.artist();
+    let execute = [instruction::execute_holder_proposal(id, &[artist.pubkey()])];
+    assert_error(
+        test.process(&execute, &[&artist]).await,
+        ErrorCode::VotingPeriodActive,
+    );
+
+    test.advance_clock(VOTING_PERIOD).await;
+
+    // Lowering the label percentages raises the artist's, which needs their signature
+    let ix = instruction::execute_holder_proposal(id, &[]);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::MissingArtistSignatures,
+    );
+
+    test.process(&execute, &[&artist]).await.unwrap();
+
+    let config = test.config().await;
+    assert_eq!(config.label_mint_percentage, 3000);
+    assert_eq!(config.artist_mint_percentage, 7000);
+    assert_eq!(config.label_secondary_percentage, 3000);
+    assert_eq!(config.artist_licensing_percentage, 7000);
+
+    assert_error(
+        test.process(&execute, &[&artist]).await,
+        ErrorCode::ProposalAlreadyExecuted,
+    );
+}
+
+#[tokio::test]
+async fn holder_proposal_keeping_artist_percentages_needs_no_artist() {
+    let mut test = TestContext::initialized().await;
+    test.initialize_holder_governance(5000, VOTING_PERIOD).await;
+    let nft = test.add_nft(0).await;
+
+    // Label percentages as they are leave the artist's untouched
+    let action = GovernanceAction::UpdateHolderPercentages {
+        label_mint_percentage: PERCENTAGES.label_mint_percentage,
+        label_secondary_percentage: PERCENTAGES.label_secondary_percentage,
+        label_licensing_percentage: PERCENTAGES.label_licensing_percentage,
+    };
+    let id = test.create_holder_proposal(&nft, action).await;
+    vote(&mut test, id, &nft, true).await.unwrap();
+    test.advance_clock(VOTING_PERIOD).await;
+
+    let ix = instruction::execute_holder_proposal(id, &[]);
+    test.process(&[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn holder_proposal_sets_fee_tiers() {
+    let mut test = TestContext::initialized().await;
+    test.initialize_holder_governance(5000, VOTING_PERIOD).await;
+    let nft = test.add_nft(0).await;
+
+    let mut tiers = flat_fee_tiers(LAMPORTS_PER_SOL);
+    tiers.push(FeeTier {
+        usage_type: UsageType::Commercial,
+        min_volume: 1000,
+        price: 2 * LAMPORTS_PER_SOL,
+    });
+    let id = test
+        .create_holder_proposal(
+            &nft,
+            GovernanceAction::SetFeeTiers {
+                tiers: tiers.clone(),
+            },
+        )
+        .await;
+    vote(&mut test, id, &nft, true).await.unwrap();
+    test.advance_clock(VOTING_PERIOD).await;
+
+    // Fee tiers leave the artist percentages alone, no artist signs
+    let ix = instruction::execute_holder_proposal(id, &[]);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let fee_schedule: FeeSchedule = test.account(&pda::fee_schedule().0).await;
+    assert_eq!(fee_schedule.tiers, tiers);
+    assert_eq!(
+        test.config().await.label_mint_percentage,
+        PERCENTAGES.label_mint_percentage
+    );
+
+    // Tiers still have to price every usage type when executed
+    let id = test
+        .create_holder_proposal(
+            &nft,
+            GovernanceAction::SetFeeTiers {
+                tiers: tiers[1..].to_vec(),
+            },
+        )
+        .await;
+    vote(&mut test, id, &nft, true).await.unwrap();
+    test.advance_clock(VOTING_PERIOD).await;
+    let ix = instruction::execute_holder_proposal(id, &[]);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidFeeSchedule,
+    );
+}
+
+#[tokio::test]
+async fn holder_proposal_needs_quorum_and_majority() {
+    let mut test = TestContext::initialized().await;
+    test.initialize_holder_governance(5000, VOTING_PERIOD).await;
+    let nft_1 = test.add_nft(0).await;
+    let nft_2 = test.add_nft(0).await;
+
+    let unvoted = test
+        .create_holder_proposal(&nft_1, update_holder_percentages(3000))
+        .await;
+    let tied = test
+        .create_holder_proposal(&nft_1, update_holder_percentages(3000))
+        .await;
+    vote(&mut test, tied, &nft_1, true).await.unwrap();
+    vote(&mut test, tied, &nft_2, false).await.unwrap();
+
+    test.advance_clock(VOTING_PERIOD).await;
+    assert_error(
+        vote(&mut test, unvoted, &nft_1, true).await,
+        ErrorCode::VotingPeriodEnded,
+    );
+
+    let ix = instruction::execute_holder_proposal(unvoted, &[]);
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::QuorumNotReached);
+    let ix = instruction::execute_holder_proposal(tied, &[]);
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::ProposalRejected);
+}
+
+#[tokio::test]
+async fn cast_holder_vote_checks_snapshot_and_holder() {
+    let mut test = TestContext::initialized().await;
+    test.initialize_holder_governance(5000, VOTING_PERIOD).await;
+    let nft = test.add_nft(0).await;
+
+    let id = test
+        .create_holder_proposal(&nft, update_holder_percentages(3000))
+        .await;
+
+    // Minted after the proposal was created
+    let late_nft = test.add_nft(0).await;
+    assert_error(
+        vote(&mut test, id, &late_nft, true).await,
+        ErrorCode::NftNotInSnapshot,
+    );
+
+    let not_holder = test.funded_keypair();
+    let ix =
+        instruction::cast_holder_vote(not_holder.pubkey(), id, nft.mint, nft.token_account, true);
+    assert_error(
+        test.process(&[ix], &[&not_holder]).await,
+        ErrorCode::NftNotOwnedByWithdrawer,
+    );
+}
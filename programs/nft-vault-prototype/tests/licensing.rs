@@ -0,0 +1,433 @@
+//! License accounts and the licensing fees paid against them
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction::{self, LicenseTerms};
+use nft_vault_client::pda;
+use nft_vault_prototype::licensing::{
+    License, LicenseStatus, UsageType, MAX_LICENSED_NFTS, MAX_TERMS_URI_LEN,
+};
+use nft_vault_prototype::{ErrorCode, NftBalance, NftBalanceLedger, ACCOUNT_VERSION};
+use solana_program_test::tokio;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use spl_token::error::TokenError;
+use spl_token::state::AccountState;
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn create_license_records_terms() {
+    let mut test = TestContext::initialized().await;
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        usage_type: UsageType::Broadcast,
+        start_slot: 10,
+        end_slot: 500,
+        ..license_terms(3 * LAMPORTS_PER_SOL)
+    };
+
+    let address = test.create_license(&licensee, terms.clone()).await;
+
+    let license: License = test.account(&address).await;
+    assert_eq!(license.version, ACCOUNT_VERSION);
+    assert_eq!(license.licensee, licensee.pubkey());
+    assert_eq!(license.collection, pda::collection_config().0);
+    assert_eq!(license.terms_uri, terms.terms_uri);
+    assert_eq!(license.content_hash, terms.content_hash);
+    assert_eq!(license.fee, 3 * LAMPORTS_PER_SOL);
+    assert_eq!(license.usage_type, UsageType::Broadcast);
+    assert_eq!((license.start_slot, license.end_slot), (10, 500));
+    assert_eq!(license.paid_to_date, 0);
+    assert_eq!(license.token_mint, pda::license_mint(&address).0);
+
+    // Issued on the first payment
+    let token_account = test.token_account(&license.token_account).await;
+    assert_eq!(token_account.owner, licensee.pubkey());
+    assert_eq!(token_account.amount, 0);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn create_license_checks_terms() {
+    let mut test = TestContext::initialized().await;
+    let licensee = test.funded_keypair();
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::create_license(
+        fake_authority.pubkey(),
+        licensee.pubkey(),
+        license_terms(LAMPORTS_PER_SOL),
+    );
+    assert_error(
+        test.process(&[ix], &[&fake_authority, &licensee]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let invalid_terms = [
+        LicenseTerms {
+            terms_uri: String::new(),
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+        LicenseTerms {
+            terms_uri: "a".repeat(MAX_TERMS_URI_LEN + 1),
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+        LicenseTerms {
+            start_slot: 100,
+            end_slot: 100,
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+        // A track share without tracks, or over 100%
+        LicenseTerms {
+            track_share_percentage: 5000,
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+        LicenseTerms {
+            licensed_nfts: vec![Pubkey::new_unique()],
+            track_share_percentage: 10_001,
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+        LicenseTerms {
+            licensed_nfts: vec![Pubkey::new_unique(); 2],
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+        LicenseTerms {
+            licensed_nfts: (0..=MAX_LICENSED_NFTS)
+                .map(|_| Pubkey::new_unique())
+                .collect(),
+            ..license_terms(LAMPORTS_PER_SOL)
+        },
+    ];
+    for terms in invalid_terms {
+        let ix = instruction::create_license(test.payer(), licensee.pubkey(), terms);
+        assert_error(
+            test.process(&[ix], &[&licensee]).await,
+            ErrorCode::InvalidLicenseTerms,
+        );
+    }
+
+    // Every licensed track must be in the ledger
+    let nft = test.add_nft(0).await;
+    let terms = LicenseTerms {
+        licensed_nfts: vec![nft.mint, Pubkey::new_unique()],
+        track_share_percentage: 5000,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let ix = instruction::create_license(test.payer(), licensee.pubkey(), terms);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::InvalidNft,
+    );
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_updates_paid_to_date() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let license = test.payer_license().await;
+
+    for amount in [LAMPORTS_PER_SOL / 4, LAMPORTS_PER_SOL / 2] {
+        let ix = instruction::pay_licensing_fee(test.payer(), license, amount);
+        test.process(&[ix], &[]).await.unwrap();
+    }
+
+    let license: License = test.account(&license).await;
+    assert_eq!(license.paid_to_date, LAMPORTS_PER_SOL * 3 / 4);
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_credits_licensed_tracks() {
+    let mut test = TestContext::initialized().await;
+    let nfts = [
+        test.add_nft(0).await,
+        test.add_nft(0).await,
+        test.add_nft(0).await,
+    ];
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        licensed_nfts: vec![nfts[1].mint],
+        track_share_percentage: 5000,
+        ..license_terms(LAMPORTS_PER_SOL / 2)
+    };
+    let license = test.create_license(&licensee, terms).await;
+
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL / 2);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    // Holders get 200,000,000, half of it to the licensed track and the
+    // other half split between all three
+    let per_nft_share = 100_000_000 / 3;
+    assert_eq!(test.nft_balance(&nfts[0].mint).await, per_nft_share);
+    assert_eq!(
+        test.nft_balance(&nfts[1].mint).await,
+        100_000_000 + per_nft_share
+    );
+    assert_eq!(test.nft_balance(&nfts[2].mint).await, per_nft_share);
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_rejects_unknown_licensed_track() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        licensed_nfts: vec![Pubkey::new_unique()],
+        track_share_percentage: 5000,
+        ..license_terms(LAMPORTS_PER_SOL / 2)
+    };
+    let license = test.create_license(&licensee, terms).await;
+
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL / 2);
+
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::InvalidNft,
+    );
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_rejects_other_licensee() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let licensee = test.funded_keypair();
+    let license = test
+        .create_license(&licensee, license_terms(LAMPORTS_PER_SOL))
+        .await;
+
+    let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidLicense);
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_rejects_other_collection() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let address = test.payer_license().await;
+    let mut license: License = test.account(&address).await;
+    license.collection = Pubkey::new_unique();
+    test.seed(&address, &license, License::SIZE);
+
+    let ix = instruction::pay_licensing_fee(test.payer(), address, LAMPORTS_PER_SOL);
+
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidLicense);
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_rejects_wrong_vault_and_ledger() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let license = test.payer_license().await;
+    let seeds_violated =
+        InstructionError::Custom(anchor_lang::__private::ErrorCode::ConstraintSeeds as u32);
+
+    // Paying the licensee's own wallet must not buy a license
+    let own_wallet = test.funded_keypair().pubkey();
+    let mut ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+    ix.accounts[5].pubkey = own_wallet;
+    assert_instruction_error(test.process(&[ix], &[]).await, seeds_violated.clone());
+
+    // Nor crediting a ledger the vault doesn't back
+    let fake_ledger = Pubkey::new_unique();
+    let ledger = NftBalanceLedger {
+        version: ACCOUNT_VERSION,
+        nft_balances: vec![NftBalance {
+            nft_address: nft.mint,
+            royalties_balance: 0,
+        }],
+        size: 1,
+    };
+    test.seed(&fake_ledger, &ledger, 1000);
+    let mut ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+    ix.accounts[9].pubkey = fake_ledger;
+    assert_instruction_error(test.process(&[ix], &[]).await, seeds_violated);
+
+    let license: License = test.account(&license).await;
+    assert_eq!(license.paid_to_date, 0);
+    assert_eq!(test.nft_balance(&nft.mint).await, 0);
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_rejects_inactive_license() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let slot = test.clock().await.slot;
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        start_slot: slot + 10,
+        end_slot: slot + 20,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let license = test.create_license(&licensee, terms).await;
+    let pay = [instruction::pay_licensing_fee(
+        licensee.pubkey(),
+        license,
+        LAMPORTS_PER_SOL / 10,
+    )];
+
+    assert_error(
+        test.process(&pay, &[&licensee]).await,
+        ErrorCode::LicenseNotActive,
+    );
+
+    test.warp_to_slot(slot + 15).await;
+    test.process(&pay, &[&licensee]).await.unwrap();
+
+    test.warp_to_slot(slot + 30).await;
+    assert_error(
+        test.process(&pay, &[&licensee]).await,
+        ErrorCode::LicenseLapsed,
+    );
+}
+
+#[tokio::test]
+async fn first_payment_issues_frozen_license_token() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let licensee = test.funded_keypair();
+    let address = test
+        .create_license(&licensee, license_terms(LAMPORTS_PER_SOL))
+        .await;
+    let license: License = test.account(&address).await;
+    assert_eq!(license.token_mint, pda::license_mint(&address).0);
+    assert_eq!(
+        license.token_account,
+        pda::license_token_account(&address).0
+    );
+
+    for amount in [LAMPORTS_PER_SOL / 2, LAMPORTS_PER_SOL / 2] {
+        let ix = instruction::pay_licensing_fee(licensee.pubkey(), address, amount);
+        test.process(&[ix], &[&licensee]).await.unwrap();
+    }
+
+    let token_account = test.token_account(&license.token_account).await;
+    assert_eq!(token_account.owner, licensee.pubkey());
+    assert_eq!(token_account.amount, 1);
+    assert_eq!(token_account.state, AccountState::Frozen);
+
+    // Frozen, so the licensee can't pass it on
+    let other_account = test.add_token_account(&license.token_mint, &Pubkey::new_unique(), 0);
+    let ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &license.token_account,
+        &other_account,
+        &licensee.pubkey(),
+        &[],
+        1,
+    )
+    .unwrap();
+    assert_instruction_error(
+        test.process(&[ix], &[&licensee]).await,
+        InstructionError::Custom(TokenError::AccountFrozen as u32),
+    );
+}
+
+#[tokio::test]
+async fn verify_license_requires_paid_active_license() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let slot = test.clock().await.slot;
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        end_slot: slot + 20,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let license = test.create_license(&licensee, terms).await;
+    let verify = [instruction::verify_license(license, licensee.pubkey())];
+
+    assert_error(
+        test.process(&verify, &[]).await,
+        ErrorCode::LicenseTokenNotIssued,
+    );
+
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+    test.process(&verify, &[]).await.unwrap();
+
+    let ix = instruction::verify_license(license, Pubkey::new_unique());
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidLicense);
+
+    test.warp_to_slot(slot + 30).await;
+    assert_error(test.process(&verify, &[]).await, ErrorCode::LicenseLapsed);
+}
+
+#[tokio::test]
+async fn renew_license_pays_fee_for_next_term() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let slot = test.clock().await.slot;
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        start_slot: slot,
+        end_slot: slot + 20,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let license = test.create_license(&licensee, terms).await;
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    let ix = instruction::renew_license(licensee.pubkey(), license);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    let renewed: License = test.account(&license).await;
+    assert_eq!(renewed.end_slot, slot + 40);
+    assert_eq!(renewed.term_slots, 20);
+    assert_eq!(renewed.paid_to_date, LAMPORTS_PER_SOL);
+    assert_eq!(
+        test.balance(&pda::vault().0).await - vault_before,
+        LAMPORTS_PER_SOL
+    );
+
+    // Valid into the new term, the renewal issued the license token
+    test.warp_to_slot(slot + 30).await;
+    let verify = [instruction::verify_license(license, licensee.pubkey())];
+    test.process(&verify, &[]).await.unwrap();
+
+    let ix = instruction::renew_license(test.payer(), license);
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidLicense);
+}
+
+#[tokio::test]
+async fn license_lapses_after_grace_period() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let slot = test.clock().await.slot;
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        start_slot: slot,
+        end_slot: slot + 20,
+        grace_slots: 20,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let license = test.create_license(&licensee, terms).await;
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+    let verify = [instruction::verify_license(license, licensee.pubkey())];
+    let lapse = [instruction::lapse_license(license)];
+
+    // Past the term but in the grace period
+    test.warp_to_slot(slot + 30).await;
+    test.process(&verify, &[]).await.unwrap();
+    assert_error(test.process(&lapse, &[]).await, ErrorCode::LicenseNotLapsed);
+
+    test.warp_to_slot(slot + 60).await;
+    assert_error(test.process(&verify, &[]).await, ErrorCode::LicenseLapsed);
+    let ix = instruction::renew_license(licensee.pubkey(), license);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::LicenseLapsed,
+    );
+
+    test.process(&lapse, &[]).await.unwrap();
+    let lapsed: License = test.account(&license).await;
+    assert_eq!(lapsed.status, LicenseStatus::Lapsed);
+
+    assert_error(test.process(&lapse, &[]).await, ErrorCode::LicenseLapsed);
+}
@@ -0,0 +1,359 @@
+//! Licensing payments held for a refund window before reaching the ledgers
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction;
+use nft_vault_client::instruction::LicenseTerms;
+use nft_vault_client::pda;
+use nft_vault_prototype::fee_schedule::FeeSchedule;
+use nft_vault_prototype::licensing::{
+    License, PaymentPurpose, UsageType, MAX_LICENSED_NFTS, MAX_PENDING_PAYMENTS, MAX_TERMS_URI_LEN,
+};
+use nft_vault_prototype::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+const HOLD_SLOTS: u64 = 20;
+
+// Collection with one NFT, holding licensing payments for HOLD_SLOTS
+async fn held_collection() -> (TestContext, TestNft) {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let ix = instruction::set_payment_hold(test.payer(), HOLD_SLOTS);
+    test.process(&[ix], &[]).await.unwrap();
+
+    (test, nft)
+}
+
+#[tokio::test]
+async fn set_payment_hold_checks_authority() {
+    let mut test = TestContext::initialized().await;
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::set_payment_hold(fake_authority.pubkey(), HOLD_SLOTS);
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::set_payment_hold(test.payer(), HOLD_SLOTS);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let fee_schedule: FeeSchedule = test.account(&pda::fee_schedule().0).await;
+    assert_eq!(fee_schedule.hold_slots, HOLD_SLOTS);
+}
+
+#[tokio::test]
+async fn held_payments_reach_ledgers_after_hold() {
+    let (mut test, nft) = held_collection().await;
+    let license = test.payer_license().await;
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[]).await.unwrap();
+
+    // In the vault, but nobody can claim it yet
+    assert_eq!(
+        test.balance(&pda::vault().0).await - vault_before,
+        LAMPORTS_PER_SOL
+    );
+    assert_eq!(test.nft_balance(&nft.mint).await, 0);
+    assert_eq!(test.artist_balance(&test.artist.pubkey()).await, 0);
+
+    let held: License = test.account(&license).await;
+    assert_eq!(held.paid_to_date, LAMPORTS_PER_SOL);
+    assert_eq!(held.pending_payments.len(), 1);
+    assert_eq!(held.pending_payments[0].amount, LAMPORTS_PER_SOL);
+
+    let release = [instruction::release_licensing_payments(license)];
+    assert_error(
+        test.process(&release, &[]).await,
+        ErrorCode::NoPaymentsToRelease,
+    );
+
+    test.warp_to_slot(held.pending_payments[0].release_slot + 10)
+        .await;
+    test.process(&release, &[]).await.unwrap();
+
+    assert_eq!(test.nft_balance(&nft.mint).await, 4 * LAMPORTS_PER_SOL / 10);
+    assert_eq!(
+        test.artist_balance(&test.artist.pubkey()).await,
+        6 * LAMPORTS_PER_SOL / 10
+    );
+    let released: License = test.account(&license).await;
+    assert!(released.pending_payments.is_empty());
+}
+
+#[tokio::test]
+async fn authority_refunds_held_payment_during_hold() {
+    let (mut test, nft) = held_collection().await;
+    let licensee = test.funded_keypair();
+    test.fund(&licensee.pubkey(), 10 * LAMPORTS_PER_SOL);
+    let license = test
+        .create_license(&licensee, license_terms(LAMPORTS_PER_SOL))
+        .await;
+
+    let pay = [instruction::pay_licensing_fee(
+        licensee.pubkey(),
+        license,
+        LAMPORTS_PER_SOL,
+    )];
+    test.process(&pay, &[&licensee]).await.unwrap();
+    let licensee_before = test.balance(&licensee.pubkey()).await;
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::refund_licensing_payment(
+        fake_authority.pubkey(),
+        license,
+        licensee.pubkey(),
+        0,
+    );
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    // The licensee can't take back a payment during the hold
+    let ix =
+        instruction::refund_licensing_payment(licensee.pubkey(), license, licensee.pubkey(), 0);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::refund_licensing_payment(test.payer(), license, licensee.pubkey(), 1);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidPendingPayment,
+    );
+
+    let refund = [instruction::refund_licensing_payment(
+        test.payer(),
+        license,
+        licensee.pubkey(),
+        0,
+    )];
+    test.process(&refund, &[]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&licensee.pubkey()).await - licensee_before,
+        LAMPORTS_PER_SOL
+    );
+    let refunded: License = test.account(&license).await;
+    assert_eq!(refunded.paid_to_date, 0);
+    assert!(refunded.pending_payments.is_empty());
+
+    // Once the hold is over the payment can only be released
+    test.process(&pay, &[&licensee]).await.unwrap();
+    let held: License = test.account(&license).await;
+    test.warp_to_slot(held.pending_payments[0].release_slot + 10)
+        .await;
+    assert_error(test.process(&refund, &[]).await, ErrorCode::PaymentHoldOver);
+
+    let ix = instruction::release_licensing_payments(license);
+    test.process(&[ix], &[]).await.unwrap();
+    assert_eq!(test.nft_balance(&nft.mint).await, 4 * LAMPORTS_PER_SOL / 10);
+}
+
+#[tokio::test]
+async fn refunded_license_no_longer_verifies() {
+    let (mut test, _) = held_collection().await;
+    let licensee = test.funded_keypair();
+    test.fund(&licensee.pubkey(), 10 * LAMPORTS_PER_SOL);
+    let license = test
+        .create_license(&licensee, license_terms(LAMPORTS_PER_SOL))
+        .await;
+
+    let pay = [instruction::pay_licensing_fee(
+        licensee.pubkey(),
+        license,
+        LAMPORTS_PER_SOL,
+    )];
+    test.process(&pay, &[&licensee]).await.unwrap();
+    let verify = [instruction::verify_license(license, licensee.pubkey())];
+    test.process(&verify, &[]).await.unwrap();
+
+    let ix = instruction::refund_licensing_payment(test.payer(), license, licensee.pubkey(), 0);
+    test.process(&[ix], &[]).await.unwrap();
+
+    // The licensee still holds the frozen token
+    let token = test
+        .token_account(&pda::license_token_account(&license).0)
+        .await;
+    assert_eq!(token.amount, 1);
+    assert_error(test.process(&verify, &[]).await, ErrorCode::LicenseNotPaid);
+
+    // Paying again reuses the token
+    test.process(&pay, &[&licensee]).await.unwrap();
+    test.process(&verify, &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn pending_payments_are_bounded() {
+    let (mut test, _) = held_collection().await;
+    let license = test.payer_license().await;
+
+    for _ in 0..MAX_PENDING_PAYMENTS {
+        let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL / 10);
+        test.process(&[ix], &[]).await.unwrap();
+    }
+
+    let ix = instruction::pay_licensing_fee(test.payer(), license, LAMPORTS_PER_SOL / 10);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::PendingPaymentsFull,
+    );
+}
+
+#[tokio::test]
+async fn refunded_renewal_takes_back_its_term() {
+    let (mut test, _) = held_collection().await;
+    let license = test.payer_license().await;
+    let before: License = test.account(&license).await;
+
+    let ix = instruction::renew_license(test.payer(), license);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let renewed: License = test.account(&license).await;
+    assert_eq!(renewed.end_slot, before.end_slot + before.term_slots);
+    assert_eq!(
+        renewed.pending_payments[0].purpose,
+        PaymentPurpose::Renewal {
+            term_slots: before.term_slots
+        }
+    );
+
+    let ix = instruction::refund_licensing_payment(test.payer(), license, test.payer(), 0);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let refunded: License = test.account(&license).await;
+    assert_eq!(refunded.end_slot, before.end_slot);
+    assert_eq!(refunded.paid_to_date, 0);
+}
+
+#[tokio::test]
+async fn refunded_usage_fee_is_owed_again() {
+    let (mut test, _) = held_collection().await;
+    let license = test.payer_license().await;
+
+    // Usage already reported, report_usage itself needs cargo test-bpf
+    let reported = License {
+        per_use_rate: 1_000_000,
+        total_uses: 500,
+        outstanding_fee: LAMPORTS_PER_SOL / 2,
+        ..test.account(&license).await
+    };
+    test.seed(&license, &reported, License::SIZE);
+
+    let ix = instruction::pay_usage_fee(test.payer(), license);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let paid: License = test.account(&license).await;
+    assert_eq!(paid.outstanding_fee, 0);
+    assert_eq!(paid.pending_payments[0].purpose, PaymentPurpose::UsageFee);
+
+    let ix = instruction::refund_licensing_payment(test.payer(), license, test.payer(), 0);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let refunded: License = test.account(&license).await;
+    assert_eq!(refunded.outstanding_fee, LAMPORTS_PER_SOL / 2);
+    assert_eq!(refunded.paid_to_date, 0);
+}
+
+#[tokio::test]
+async fn licensee_refunds_payment_that_cannot_be_released() {
+    let (mut test, nft) = held_collection().await;
+    let licensee = test.funded_keypair();
+    test.fund(&licensee.pubkey(), 10 * LAMPORTS_PER_SOL);
+
+    // Agreed before create_license checked its tracks against the ledger
+    let terms = LicenseTerms {
+        licensed_nfts: vec![nft.mint, Pubkey::new_unique()],
+        track_share_percentage: 5000,
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let license = test.create_license(&licensee, terms).await;
+    let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    let held: License = test.account(&license).await;
+    test.warp_to_slot(held.pending_payments[0].release_slot + 10)
+        .await;
+    let ix = instruction::release_licensing_payments(license);
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidNft);
+
+    let licensee_before = test.balance(&licensee.pubkey()).await;
+    let ix =
+        instruction::refund_licensing_payment(licensee.pubkey(), license, licensee.pubkey(), 0);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&licensee.pubkey()).await - licensee_before,
+        LAMPORTS_PER_SOL
+    );
+    let refunded: License = test.account(&license).await;
+    assert!(refunded.pending_payments.is_empty());
+    assert_eq!(refunded.paid_to_date, 0);
+}
+
+#[tokio::test]
+async fn license_holds_most_payments_with_longest_terms_uri() {
+    let (mut test, _) = held_collection().await;
+    let licensee = test.funded_keypair();
+    test.fund(&licensee.pubkey(), 10 * LAMPORTS_PER_SOL);
+    let terms = LicenseTerms {
+        terms_uri: "a".repeat(MAX_TERMS_URI_LEN),
+        ..license_terms(LAMPORTS_PER_SOL / 10)
+    };
+    let license = test.create_license(&licensee, terms).await;
+
+    for _ in 0..MAX_PENDING_PAYMENTS {
+        let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL / 10);
+        test.process(&[ix], &[&licensee]).await.unwrap();
+    }
+
+    let held: License = test.account(&license).await;
+    assert_eq!(held.pending_payments.len(), MAX_PENDING_PAYMENTS);
+}
+
+#[tokio::test]
+async fn license_at_maximum_size_holds_payments() {
+    let (mut test, nft) = held_collection().await;
+    let mut licensed_nfts = vec![nft.mint];
+    for _ in 1..MAX_LICENSED_NFTS {
+        licensed_nfts.push(test.add_nft(0).await.mint);
+    }
+    let licensee = test.funded_keypair();
+    test.fund(&licensee.pubkey(), 10 * LAMPORTS_PER_SOL);
+    let terms = LicenseTerms {
+        terms_uri: "a".repeat(MAX_TERMS_URI_LEN),
+        usage_type: UsageType::Broadcast,
+        licensed_nfts,
+        track_share_percentage: 5000,
+        reporter: Some(Pubkey::new_unique()),
+        per_use_rate: 1_000,
+        ..license_terms(LAMPORTS_PER_SOL / 10)
+    };
+    let license = test.create_license(&licensee, terms).await;
+
+    // Every optional field set, as after a usage report
+    let reported = License {
+        last_usage_period: Some(u64::MAX),
+        total_uses: u64::MAX,
+        outstanding_fee: u64::MAX,
+        ..test.account(&license).await
+    };
+    test.seed(&license, &reported, License::SIZE);
+
+    for _ in 0..MAX_PENDING_PAYMENTS {
+        let ix = instruction::pay_licensing_fee(licensee.pubkey(), license, LAMPORTS_PER_SOL / 10);
+        test.process(&[ix], &[&licensee]).await.unwrap();
+    }
+
+    let held: License = test.account(&license).await;
+    assert_eq!(held.pending_payments.len(), MAX_PENDING_PAYMENTS);
+    assert!(held.fee_tier.is_some());
+    assert_eq!(test.account_len(&license).await, License::SIZE);
+}
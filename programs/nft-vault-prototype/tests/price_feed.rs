@@ -0,0 +1,303 @@
+//! USD fee schedules and licensing payments converted at a price feed
+
+mod common;
+
+use common::*;
+use nft_vault_client::instruction;
+use nft_vault_client::pda;
+use nft_vault_prototype::fee_schedule::{FeeCurrency, FeeSchedule};
+use nft_vault_prototype::licensing::License;
+use nft_vault_prototype::price_feed::{PriceFeedConfig, UsdPrice, PYTH_STATUS_TRADING};
+use nft_vault_prototype::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+// Every license costs $50.00
+const FEE_CENTS: u64 = 5_000;
+// $50.00 at $150.00 per SOL, rounded down
+const FEE_LAMPORTS: u64 = 333_333_333;
+
+const MAX_STALENESS_SLOTS: u64 = 25;
+const MAX_CONFIDENCE_BPS: u64 = 100;
+
+// $150.00 per SOL give or take 0.1%
+fn sol_price(pub_slot: u64) -> UsdPrice {
+    UsdPrice {
+        price: 15_000_000_000,
+        conf: 15_000_000,
+        expo: -8,
+        status: PYTH_STATUS_TRADING,
+        pub_slot,
+    }
+}
+
+struct UsdLicense {
+    test: TestContext,
+    license: Pubkey,
+    price_feed: Pubkey,
+}
+
+// Licenses priced in USD at a fresh price, paid by the payer
+async fn usd_license() -> UsdLicense {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+
+    let price_feed = Pubkey::new_unique();
+    let slot = test.clock().await.slot;
+    test.set_price_feed(&price_feed, sol_price(slot));
+
+    let currency = FeeCurrency::Usd(PriceFeedConfig {
+        price_feed,
+        max_staleness_slots: MAX_STALENESS_SLOTS,
+        max_confidence_bps: MAX_CONFIDENCE_BPS,
+    });
+    let ix = instruction::set_fee_currency(test.payer(), currency, flat_fee_tiers(FEE_CENTS));
+    test.process(&[ix], &[]).await.unwrap();
+    let license = test.payer_license().await;
+
+    UsdLicense {
+        test,
+        license,
+        price_feed,
+    }
+}
+
+#[tokio::test]
+async fn set_fee_currency_checks_authority_and_bounds() {
+    let mut test = TestContext::initialized().await;
+    let config = PriceFeedConfig {
+        price_feed: Pubkey::new_unique(),
+        max_staleness_slots: MAX_STALENESS_SLOTS,
+        max_confidence_bps: MAX_CONFIDENCE_BPS,
+    };
+
+    let fake_authority = test.funded_keypair();
+    let ix = instruction::set_fee_currency(
+        fake_authority.pubkey(),
+        FeeCurrency::Usd(config),
+        flat_fee_tiers(FEE_CENTS),
+    );
+    assert_error(
+        test.process(&[ix], &[&fake_authority]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let unbounded = PriceFeedConfig {
+        max_confidence_bps: 10_001,
+        ..config
+    };
+    let ix = instruction::set_fee_currency(
+        test.payer(),
+        FeeCurrency::Usd(unbounded),
+        flat_fee_tiers(FEE_CENTS),
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidFeeSchedule,
+    );
+
+    // The tiers are checked like any schedule's
+    let ix = instruction::set_fee_currency(test.payer(), FeeCurrency::Usd(config), vec![]);
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::InvalidFeeSchedule,
+    );
+
+    let ix = instruction::set_fee_currency(
+        test.payer(),
+        FeeCurrency::Usd(config),
+        flat_fee_tiers(FEE_CENTS),
+    );
+    test.process(&[ix], &[]).await.unwrap();
+
+    // Lamport prices are replaced, not read as cents
+    let fee_schedule: FeeSchedule = test.account(&pda::fee_schedule().0).await;
+    assert_eq!(fee_schedule.currency, FeeCurrency::Usd(config));
+    assert_eq!(fee_schedule.tiers, flat_fee_tiers(FEE_CENTS));
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_usd_converts_at_feed_price() {
+    let UsdLicense {
+        mut test,
+        license,
+        price_feed,
+    } = usd_license().await;
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    let ix = instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        price_feed,
+        FEE_CENTS,
+        FEE_LAMPORTS,
+        0,
+    );
+    test.process(&[ix], &[]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&pda::vault().0).await - vault_before,
+        FEE_LAMPORTS
+    );
+    let license: License = test.account(&license).await;
+    assert_eq!(license.paid_to_date, FEE_LAMPORTS);
+    assert_eq!(license.fee_tier.unwrap().price, FEE_CENTS);
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_usd_enforces_slippage() {
+    let UsdLicense {
+        mut test,
+        license,
+        price_feed,
+    } = usd_license().await;
+    let quoted_lamports = 300_000_000;
+
+    // 1% over the quote is 303,000,000 lamports
+    let ix = instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        price_feed,
+        FEE_CENTS,
+        quoted_lamports,
+        100,
+    );
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::SlippageExceeded);
+
+    let ix = instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        price_feed,
+        FEE_CENTS,
+        quoted_lamports,
+        1_200,
+    );
+    test.process(&[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn usd_payments_reject_unreliable_prices() {
+    let UsdLicense {
+        mut test,
+        license,
+        price_feed,
+    } = usd_license().await;
+    let slot = test.clock().await.slot;
+    let pay = [instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        price_feed,
+        FEE_CENTS,
+        FEE_LAMPORTS,
+        0,
+    )];
+
+    test.set_price_feed(
+        &price_feed,
+        UsdPrice {
+            status: 0,
+            ..sol_price(slot)
+        },
+    );
+    assert_error(test.process(&pay, &[]).await, ErrorCode::StalePrice);
+
+    // 2% confidence interval
+    test.set_price_feed(
+        &price_feed,
+        UsdPrice {
+            conf: 300_000_000,
+            ..sol_price(slot)
+        },
+    );
+    assert_error(
+        test.process(&pay, &[]).await,
+        ErrorCode::PriceConfidenceTooWide,
+    );
+
+    test.set_price_feed(&price_feed, sol_price(slot));
+    test.warp_to_slot(slot + MAX_STALENESS_SLOTS + 10).await;
+    assert_error(test.process(&pay, &[]).await, ErrorCode::StalePrice);
+
+    // Only the feed the schedule names
+    let slot = test.clock().await.slot;
+    let other_feed = Pubkey::new_unique();
+    test.set_price_feed(&other_feed, sol_price(slot));
+    let ix = instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        other_feed,
+        FEE_CENTS,
+        FEE_LAMPORTS,
+        0,
+    );
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidPriceFeed);
+
+    test.set_price_feed(&price_feed, sol_price(slot));
+    test.process(&pay, &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn usd_schedule_prices_lamport_payments() {
+    let UsdLicense {
+        mut test,
+        license,
+        price_feed,
+    } = usd_license().await;
+
+    let ix = instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        price_feed,
+        FEE_CENTS - 1,
+        FEE_LAMPORTS,
+        0,
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::LicensingFeeTooLow,
+    );
+
+    // Lamport payments need the feed to check the scheduled price
+    let ix = instruction::pay_licensing_fee(test.payer(), license, FEE_LAMPORTS);
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidPriceFeed);
+
+    let ix = instruction::with_price_feed(
+        instruction::pay_licensing_fee(test.payer(), license, FEE_LAMPORTS - 1),
+        price_feed,
+    );
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::LicensingFeeTooLow,
+    );
+
+    let ix = instruction::with_price_feed(
+        instruction::pay_licensing_fee(test.payer(), license, FEE_LAMPORTS),
+        price_feed,
+    );
+    test.process(&[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn pay_licensing_fee_usd_requires_usd_schedule() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let license = test.payer_license().await;
+    let price_feed = Pubkey::new_unique();
+    let slot = test.clock().await.slot;
+    test.set_price_feed(&price_feed, sol_price(slot));
+
+    let ix = instruction::pay_licensing_fee_usd(
+        test.payer(),
+        license,
+        price_feed,
+        FEE_CENTS,
+        FEE_LAMPORTS,
+        0,
+    );
+
+    assert_error(
+        test.process(&[ix], &[]).await,
+        ErrorCode::FeeScheduleNotInUsd,
+    );
+}
@@ -0,0 +1,368 @@
+//! Secondary sales recorded by marketplaces and the royalties they deposit
+
+mod common;
+
+use anchor_lang::prelude::ProgramError;
+use anchor_lang::AccountSerialize;
+use common::*;
+use nft_vault_client::instruction;
+use nft_vault_client::pda;
+use nft_vault_prototype::secondary_sales::{NftSales, SaleReporter, SecondarySale};
+use nft_vault_prototype::{ErrorCode, ACCOUNT_VERSION};
+use solana_program_test::tokio;
+use solana_sdk::account_info::IntoAccountInfo;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_sdk::system_program;
+
+const PRICE: u64 = 2 * LAMPORTS_PER_SOL;
+const ROYALTY: u64 = LAMPORTS_PER_SOL / 10;
+
+// record_secondary_sale and approve_sale_reporter create accounts, which
+// native runs can't, so the native tests check the approval and the sale
+// totals on seeded accounts
+
+fn seed_approval(test: &mut TestContext, reporter: &Pubkey) {
+    let approval = SaleReporter {
+        version: ACCOUNT_VERSION,
+        reporter: *reporter,
+        approved_slot: 0,
+    };
+    test.seed(
+        &pda::sale_reporter(reporter).0,
+        &approval,
+        SaleReporter::SIZE,
+    );
+}
+
+// What record_secondary_sale makes of the reporter's approval account
+async fn verify_approval(test: &mut TestContext, reporter: &Pubkey) -> Result<(), ProgramError> {
+    let address = pda::sale_reporter(reporter).0;
+    let mut account = test
+        .context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    let sale_reporter = (&address, &mut account).into_account_info();
+
+    SaleReporter::verify_approval(&sale_reporter, &nft_vault_prototype::id())
+        .map_err(ProgramError::from)
+}
+
+fn seed_nft_sales(test: &mut TestContext, nft: &TestNft, sale_count: u64, volume: u64) {
+    let nft_sales = NftSales {
+        version: ACCOUNT_VERSION,
+        nft: nft.mint,
+        sale_count,
+        volume,
+        royalties: sale_count * ROYALTY,
+    };
+    test.seed(&pda::nft_sales(&nft.mint).0, &nft_sales, NftSales::SIZE);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn record_secondary_sale_deposits_and_records() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let marketplace = test.funded_keypair();
+    let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let instructions = [
+        instruction::initialize_nft_sales(test.payer(), nft.mint),
+        instruction::approve_sale_reporter(test.payer(), test.payer(), marketplace.pubkey()),
+    ];
+    test.process(&instructions, &[]).await.unwrap();
+
+    for sale_index in 0..2 {
+        let ix = instruction::record_secondary_sale(
+            marketplace.pubkey(),
+            nft.mint,
+            sale_index,
+            PRICE,
+            buyer,
+            seller,
+            ROYALTY,
+        );
+        test.process(&[ix], &[&marketplace]).await.unwrap();
+    }
+
+    assert_eq!(test.balance(&pda::secondary_pool().0).await, 2 * ROYALTY);
+
+    let sale: SecondarySale = test.account(&pda::secondary_sale(&nft.mint, 1).0).await;
+    assert_eq!(sale.nft, nft.mint);
+    assert_eq!(sale.sale_index, 1);
+    assert_eq!(sale.price, PRICE);
+    assert_eq!((sale.buyer, sale.seller), (buyer, seller));
+    assert_eq!(sale.royalty_amount, ROYALTY);
+    assert_eq!(sale.reporter, marketplace.pubkey());
+
+    let nft_sales: NftSales = test.account(&pda::nft_sales(&nft.mint).0).await;
+    assert_eq!(nft_sales.sale_count, 2);
+    assert_eq!(nft_sales.volume, 2 * PRICE);
+    assert_eq!(nft_sales.royalties, 2 * ROYALTY);
+
+    // The recorded royalties are distributed like any pool deposit
+    test.process(&[instruction::distribute_secondary_pool()], &[])
+        .await
+        .unwrap();
+    assert_eq!(test.balance(&pda::secondary_pool().0).await, 0);
+    assert_eq!(test.nft_balance(&nft.mint).await, ROYALTY);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn record_secondary_sale_checks_sale() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    // Only NFTs in the ledger have sale totals
+    let stranger = test.mint_nft();
+    let ix = instruction::initialize_nft_sales(test.payer(), stranger.mint);
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidNft);
+
+    let instructions = [
+        instruction::initialize_nft_sales(test.payer(), nft.mint),
+        instruction::approve_sale_reporter(test.payer(), test.payer(), test.payer()),
+    ];
+    test.process(&instructions, &[]).await.unwrap();
+
+    // Every sale pays a royalty, at most the price
+    for (price, buyer, seller, royalty) in [
+        (0, buyer, seller, 0),
+        (PRICE, buyer, seller, 0),
+        (PRICE, buyer, seller, PRICE + 1),
+        (PRICE, buyer, buyer, ROYALTY),
+    ] {
+        let ix = instruction::record_secondary_sale(
+            test.payer(),
+            nft.mint,
+            0,
+            price,
+            buyer,
+            seller,
+            royalty,
+        );
+        assert_error(
+            test.process(&[ix], &[]).await,
+            ErrorCode::InvalidSecondarySale,
+        );
+    }
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn record_secondary_sale_requires_approved_reporter() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let marketplace = test.funded_keypair();
+    let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let ix = instruction::initialize_nft_sales(test.payer(), nft.mint);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let record = [instruction::record_secondary_sale(
+        marketplace.pubkey(),
+        nft.mint,
+        0,
+        PRICE,
+        buyer,
+        seller,
+        ROYALTY,
+    )];
+    assert_error(
+        test.process(&record, &[&marketplace]).await,
+        ErrorCode::UnauthorizedSaleReporter,
+    );
+
+    // Only the collection authority approves reporters
+    let ix = instruction::approve_sale_reporter(
+        marketplace.pubkey(),
+        marketplace.pubkey(),
+        marketplace.pubkey(),
+    );
+    assert_error(
+        test.process(&[ix], &[&marketplace]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::approve_sale_reporter(test.payer(), test.payer(), marketplace.pubkey());
+    test.process(&[ix], &[]).await.unwrap();
+    test.process(&record, &[&marketplace]).await.unwrap();
+
+    let ix = instruction::revoke_sale_reporter(marketplace.pubkey(), marketplace.pubkey());
+    assert_error(
+        test.process(&[ix], &[&marketplace]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+
+    let ix = instruction::revoke_sale_reporter(test.payer(), marketplace.pubkey());
+    test.process(&[ix], &[]).await.unwrap();
+
+    let ix = instruction::record_secondary_sale(
+        marketplace.pubkey(),
+        nft.mint,
+        1,
+        PRICE,
+        buyer,
+        seller,
+        ROYALTY,
+    );
+    assert_error(
+        test.process(&[ix], &[&marketplace]).await,
+        ErrorCode::UnauthorizedSaleReporter,
+    );
+
+    let nft_sales: NftSales = test.account(&pda::nft_sales(&nft.mint).0).await;
+    assert_eq!(nft_sales.sale_count, 1);
+    assert_eq!(nft_sales.volume, PRICE);
+}
+
+#[tokio::test]
+async fn unapproved_reporter_cannot_record_sales() {
+    let mut test = TestContext::initialized().await;
+    let marketplace = test.funded_keypair();
+    let unauthorized = Err(ProgramError::from(ErrorCode::UnauthorizedSaleReporter));
+
+    assert_eq!(
+        verify_approval(&mut test, &marketplace.pubkey()).await,
+        unauthorized
+    );
+
+    // Only an approval account of the program counts
+    let address = pda::sale_reporter(&marketplace.pubkey()).0;
+    let mut not_an_approval = vec![];
+    NftSales {
+        version: ACCOUNT_VERSION,
+        nft: Pubkey::new_unique(),
+        sale_count: 0,
+        volume: 0,
+        royalties: 0,
+    }
+    .try_serialize(&mut not_an_approval)
+    .unwrap();
+    let mut approval = vec![];
+    SaleReporter {
+        version: ACCOUNT_VERSION,
+        reporter: marketplace.pubkey(),
+        approved_slot: 0,
+    }
+    .try_serialize(&mut approval)
+    .unwrap();
+    for (owner, data) in [
+        (nft_vault_prototype::id(), not_an_approval),
+        (system_program::id(), approval),
+    ] {
+        test.set_account(&address, &owner, LAMPORTS_PER_SOL, data);
+        assert_eq!(
+            verify_approval(&mut test, &marketplace.pubkey()).await,
+            unauthorized
+        );
+    }
+
+    seed_approval(&mut test, &marketplace.pubkey());
+    assert_eq!(
+        verify_approval(&mut test, &marketplace.pubkey()).await,
+        Ok(())
+    );
+}
+
+#[tokio::test]
+async fn revoked_reporter_cannot_record_sales() {
+    let mut test = TestContext::initialized().await;
+    let marketplace = test.funded_keypair();
+    seed_approval(&mut test, &marketplace.pubkey());
+    let address = pda::sale_reporter(&marketplace.pubkey()).0;
+
+    // Only the collection authority revokes reporters
+    let ix = instruction::revoke_sale_reporter(marketplace.pubkey(), marketplace.pubkey());
+    assert_error(
+        test.process(&[ix], &[&marketplace]).await,
+        ErrorCode::MissingCollectionAuthoritySignature,
+    );
+    assert_eq!(
+        verify_approval(&mut test, &marketplace.pubkey()).await,
+        Ok(())
+    );
+
+    let payer = test.payer();
+    let authority_before = test.balance(&payer).await;
+    let ix = instruction::revoke_sale_reporter(test.payer(), marketplace.pubkey());
+    test.process(&[ix], &[]).await.unwrap();
+
+    assert_eq!(test.account_len(&address).await, 0);
+    // The approval's rent went back to the authority
+    assert!(test.balance(&payer).await > authority_before);
+    assert_eq!(
+        verify_approval(&mut test, &marketplace.pubkey()).await,
+        Err(ProgramError::from(ErrorCode::UnauthorizedSaleReporter))
+    );
+}
+
+#[tokio::test]
+async fn nft_sales_record_totals() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+    seed_nft_sales(&mut test, &nft, 3, 3 * PRICE);
+    let mut nft_sales: NftSales = test.account(&pda::nft_sales(&nft.mint).0).await;
+
+    assert_eq!(
+        nft_sales
+            .record_sale(PRICE, buyer, seller, ROYALTY)
+            .unwrap(),
+        3
+    );
+    // The buyer reselling, at the whole price as royalty
+    assert_eq!(
+        nft_sales
+            .record_sale(2 * PRICE, seller, buyer, 2 * PRICE)
+            .unwrap(),
+        4
+    );
+
+    assert_eq!(nft_sales.sale_count, 5);
+    assert_eq!(nft_sales.volume, 6 * PRICE);
+    assert_eq!(nft_sales.royalties, 4 * ROYALTY + 2 * PRICE);
+}
+
+#[tokio::test]
+async fn nft_sales_record_checks_sale() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+    seed_nft_sales(&mut test, &nft, 1, u64::MAX - PRICE);
+    let mut nft_sales: NftSales = test.account(&pda::nft_sales(&nft.mint).0).await;
+
+    for (price, buyer, seller, royalty) in [
+        (0, buyer, seller, 0),
+        (PRICE, buyer, seller, 0),
+        (PRICE, buyer, seller, PRICE + 1),
+        (PRICE, buyer, buyer, ROYALTY),
+        // The volume would overflow
+        (PRICE + 1, buyer, seller, ROYALTY),
+    ] {
+        assert_eq!(
+            nft_sales
+                .record_sale(price, buyer, seller, royalty)
+                .map_err(ProgramError::from),
+            Err(ProgramError::from(ErrorCode::InvalidSecondarySale))
+        );
+    }
+
+    // Rejected sales leave the totals as they were
+    assert_eq!(nft_sales.sale_count, 1);
+    assert_eq!(nft_sales.volume, u64::MAX - PRICE);
+    assert_eq!(nft_sales.royalties, ROYALTY);
+}
@@ -0,0 +1,343 @@
+//! Usage reports and per-use licensing fees
+
+mod common;
+
+use anchor_lang::prelude::ProgramError;
+use common::*;
+use nft_vault_client::instruction::{self, LicenseTerms};
+use nft_vault_client::pda;
+use nft_vault_prototype::fee_schedule::FeeTier;
+use nft_vault_prototype::licensing::{
+    License, TrackUsage, UsageReport, UsageType, MAX_USAGE_TRACKS,
+};
+use nft_vault_prototype::{ErrorCode, NftBalanceLedger};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+// Lamports per play
+const PER_USE_RATE: u64 = 1_000;
+
+fn streaming_terms(reporter: Pubkey) -> LicenseTerms {
+    LicenseTerms {
+        usage_type: UsageType::Broadcast,
+        reporter: Some(reporter),
+        per_use_rate: PER_USE_RATE,
+        ..license_terms(0)
+    }
+}
+
+fn report_error<T>(result: Result<T, nft_vault_prototype::Error>) -> ProgramError {
+    match result {
+        Ok(_) => panic!("report should be rejected"),
+        Err(err) => err.into(),
+    }
+}
+
+fn usage(nft: &TestNft, uses: u64) -> TrackUsage {
+    TrackUsage {
+        nft: nft.mint,
+        uses,
+    }
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn report_usage_records_fee_owed() {
+    let mut test = TestContext::initialized().await;
+    let nfts = [test.add_nft(0).await, test.add_nft(0).await];
+    let licensee = test.funded_keypair();
+    let reporter = test.funded_keypair();
+    let license = test
+        .create_license(&licensee, streaming_terms(reporter.pubkey()))
+        .await;
+
+    let tracks = vec![usage(&nfts[0], 300), usage(&nfts[1], 200)];
+    let ix = instruction::report_usage(licensee.pubkey(), license, 1, tracks.clone());
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::InvalidReporter,
+    );
+
+    let ix = instruction::report_usage(reporter.pubkey(), license, 1, tracks.clone());
+    test.process(&[ix], &[&reporter]).await.unwrap();
+
+    let report: UsageReport = test.account(&pda::usage_report(&license, 1).0).await;
+    assert_eq!(report.license, license);
+    assert_eq!(report.tracks, tracks);
+    assert_eq!(report.total_uses, 500);
+    assert_eq!(report.fee, 500 * PER_USE_RATE);
+
+    let ix = instruction::report_usage(reporter.pubkey(), license, 2, vec![usage(&nfts[1], 100)]);
+    test.process(&[ix], &[&reporter]).await.unwrap();
+
+    let license: License = test.account(&license).await;
+    assert_eq!(license.last_usage_period, Some(2));
+    assert_eq!(license.total_uses, 600);
+    assert_eq!(license.outstanding_fee, 600 * PER_USE_RATE);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-bpf"),
+    ignore = "creates accounts, run with cargo test-bpf"
+)]
+async fn report_usage_checks_report() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    let licensee = test.funded_keypair();
+    let license = test
+        .create_license(&licensee, streaming_terms(licensee.pubkey()))
+        .await;
+
+    let ix = instruction::report_usage(licensee.pubkey(), license, 5, vec![usage(&nft, 10)]);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    // Periods only move forward
+    let ix = instruction::report_usage(licensee.pubkey(), license, 4, vec![usage(&nft, 10)]);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::InvalidUsagePeriod,
+    );
+
+    for tracks in [vec![], vec![usage(&nft, 10), usage(&nft, 20)]] {
+        let ix = instruction::report_usage(licensee.pubkey(), license, 6, tracks);
+        assert_error(
+            test.process(&[ix], &[&licensee]).await,
+            ErrorCode::InvalidUsageReport,
+        );
+    }
+
+    let unknown = TrackUsage {
+        nft: Pubkey::new_unique(),
+        uses: 10,
+    };
+    let ix = instruction::report_usage(licensee.pubkey(), license, 6, vec![unknown]);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::InvalidNft,
+    );
+
+    // Licenses paid per term take no reports
+    let terms = LicenseTerms {
+        content_hash: [8; 32],
+        ..license_terms(LAMPORTS_PER_SOL)
+    };
+    let unmetered = test.create_license(&licensee, terms).await;
+    let ix = instruction::report_usage(licensee.pubkey(), unmetered, 1, vec![usage(&nft, 10)]);
+    assert_error(
+        test.process(&[ix], &[&licensee]).await,
+        ErrorCode::UsageNotMetered,
+    );
+}
+
+// report_usage creates the report account, which native runs can't, so
+// these check its accounting on the seeded license
+#[tokio::test]
+async fn record_usage_adds_fee_owed() {
+    let mut test = TestContext::initialized().await;
+    let nfts = [test.add_nft(0).await, test.add_nft(0).await];
+    let licensee = test.funded_keypair();
+    let reporter = test.funded_keypair();
+    let address = test
+        .create_license(&licensee, streaming_terms(reporter.pubkey()))
+        .await;
+    let ledger: NftBalanceLedger = test.account(&pda::nft_ledger().0).await;
+    let mut license: License = test.account(&address).await;
+
+    let tracks = [usage(&nfts[0], 6_000), usage(&nfts[1], 4_000)];
+    assert_eq!(
+        report_error(license.record_usage(licensee.pubkey(), 1, &tracks, &ledger)),
+        ProgramError::from(ErrorCode::InvalidReporter)
+    );
+    assert_eq!(
+        license
+            .record_usage(reporter.pubkey(), 1, &tracks, &ledger)
+            .unwrap(),
+        (10_000, 10_000 * PER_USE_RATE)
+    );
+    assert_eq!(
+        license
+            .record_usage(reporter.pubkey(), 2, &[usage(&nfts[1], 2_000)], &ledger)
+            .unwrap(),
+        (2_000, 2_000 * PER_USE_RATE)
+    );
+    assert_eq!(license.last_usage_period, Some(2));
+    assert_eq!(license.total_uses, 12_000);
+    assert_eq!(license.outstanding_fee, 12_000 * PER_USE_RATE);
+
+    // The reported fee is what pay_usage_fee charges
+    let ix = instruction::update_fee_schedule(test.payer(), flat_fee_tiers(LAMPORTS_PER_SOL));
+    test.process(&[ix], &[]).await.unwrap();
+    test.seed(&address, &license, License::SIZE);
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    let ix = instruction::pay_usage_fee(licensee.pubkey(), address);
+    test.process(&[ix], &[&licensee]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&pda::vault().0).await - vault_before,
+        12_000 * PER_USE_RATE
+    );
+}
+
+#[tokio::test]
+async fn record_usage_checks_report() {
+    let mut test = TestContext::initialized().await;
+    let nfts = [test.add_nft(0).await, test.add_nft(0).await];
+    let licensee = test.funded_keypair();
+    let terms = LicenseTerms {
+        licensed_nfts: vec![nfts[0].mint],
+        ..streaming_terms(licensee.pubkey())
+    };
+    let address = test.create_license(&licensee, terms).await;
+    let ledger: NftBalanceLedger = test.account(&pda::nft_ledger().0).await;
+    let mut license: License = test.account(&address).await;
+    let reporter = licensee.pubkey();
+
+    license
+        .record_usage(reporter, 5, &[usage(&nfts[0], 10)], &ledger)
+        .unwrap();
+
+    // Periods only move forward
+    for period in [4, 5] {
+        assert_eq!(
+            report_error(license.record_usage(reporter, period, &[usage(&nfts[0], 10)], &ledger)),
+            ProgramError::from(ErrorCode::InvalidUsagePeriod)
+        );
+    }
+
+    let too_many = vec![usage(&nfts[0], 1); MAX_USAGE_TRACKS + 1];
+    let duplicated = vec![usage(&nfts[0], 10), usage(&nfts[0], 20)];
+    let overflowing = vec![usage(&nfts[0], u64::MAX / PER_USE_RATE + 1)];
+    for tracks in [vec![], too_many, duplicated, overflowing] {
+        assert_eq!(
+            report_error(license.record_usage(reporter, 6, &tracks, &ledger)),
+            ProgramError::from(ErrorCode::InvalidUsageReport)
+        );
+    }
+
+    let unknown = TrackUsage {
+        nft: Pubkey::new_unique(),
+        uses: 10,
+    };
+    // Not a track of the collection, or not one the license covers
+    for track in [unknown, usage(&nfts[1], 10)] {
+        assert_eq!(
+            report_error(license.record_usage(reporter, 6, &[track], &ledger)),
+            ProgramError::from(ErrorCode::InvalidNft)
+        );
+    }
+
+    // Rejected reports leave the license as it was
+    assert_eq!(license.last_usage_period, Some(5));
+    assert_eq!(license.total_uses, 10);
+    assert_eq!(license.outstanding_fee, 10 * PER_USE_RATE);
+
+    let mut unmetered = License {
+        per_use_rate: 0,
+        ..license
+    };
+    assert_eq!(
+        report_error(unmetered.record_usage(reporter, 6, &[usage(&nfts[0], 10)], &ledger)),
+        ProgramError::from(ErrorCode::UsageNotMetered)
+    );
+}
+
+#[tokio::test]
+async fn pay_usage_fee_settles_outstanding_fee() {
+    let mut test = TestContext::initialized().await;
+    let nft = test.add_nft(0).await;
+    // The per-use rate applies, not the schedule's price
+    let ix = instruction::update_fee_schedule(test.payer(), flat_fee_tiers(LAMPORTS_PER_SOL));
+    test.process(&[ix], &[]).await.unwrap();
+
+    let licensee = test.funded_keypair();
+    let address = test
+        .create_license(&licensee, streaming_terms(licensee.pubkey()))
+        .await;
+    let pay = [instruction::pay_usage_fee(licensee.pubkey(), address)];
+    assert_error(
+        test.process(&pay, &[&licensee]).await,
+        ErrorCode::NoUsageFeeOwed,
+    );
+
+    // As if 10,000 plays were reported
+    let mut license: License = test.account(&address).await;
+    license.total_uses = 10_000;
+    license.outstanding_fee = 10_000 * PER_USE_RATE;
+    test.seed(&address, &license, License::SIZE);
+    let vault_before = test.balance(&pda::vault().0).await;
+
+    test.process(&pay, &[&licensee]).await.unwrap();
+
+    assert_eq!(
+        test.balance(&pda::vault().0).await - vault_before,
+        10_000 * PER_USE_RATE
+    );
+    assert_eq!(test.nft_balance(&nft.mint).await, 4_000 * PER_USE_RATE);
+    let license: License = test.account(&address).await;
+    assert_eq!(license.outstanding_fee, 0);
+    assert_eq!(license.paid_to_date, 10_000 * PER_USE_RATE);
+}
+
+#[tokio::test]
+async fn pay_usage_fee_rejects_other_licensee() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let licensee = test.funded_keypair();
+    let address = test
+        .create_license(&licensee, streaming_terms(licensee.pubkey()))
+        .await;
+    let mut license: License = test.account(&address).await;
+    license.outstanding_fee = PER_USE_RATE;
+    test.seed(&address, &license, License::SIZE);
+
+    let ix = instruction::pay_usage_fee(test.payer(), address);
+
+    assert_error(test.process(&[ix], &[]).await, ErrorCode::InvalidLicense);
+}
+
+#[tokio::test]
+async fn pay_usage_fee_records_volume_tier() {
+    let mut test = TestContext::initialized().await;
+    test.add_nft(0).await;
+    let base_tier = FeeTier {
+        usage_type: UsageType::Broadcast,
+        min_volume: 0,
+        price: LAMPORTS_PER_SOL,
+    };
+    let volume_tier = FeeTier {
+        min_volume: 10_000,
+        price: 3 * LAMPORTS_PER_SOL,
+        ..base_tier
+    };
+    let mut tiers = flat_fee_tiers(LAMPORTS_PER_SOL);
+    tiers.push(volume_tier);
+    let ix = instruction::update_fee_schedule(test.payer(), tiers);
+    test.process(&[ix], &[]).await.unwrap();
+
+    let licensee = test.funded_keypair();
+    for (volume, tier) in [(9_999, base_tier), (50_000, volume_tier)] {
+        let terms = LicenseTerms {
+            content_hash: [volume as u8; 32],
+            volume,
+            ..streaming_terms(licensee.pubkey())
+        };
+        let address = test.create_license(&licensee, terms).await;
+        let mut license: License = test.account(&address).await;
+        license.outstanding_fee = 10_000 * PER_USE_RATE;
+        test.seed(&address, &license, License::SIZE);
+
+        let ix = instruction::pay_usage_fee(licensee.pubkey(), address);
+        test.process(&[ix], &[&licensee]).await.unwrap();
+
+        // The tier for the license's volume is recorded, the usage fee is still what's paid
+        let license: License = test.account(&address).await;
+        assert_eq!(license.fee_tier, Some(tier));
+        assert_eq!(license.paid_to_date, 10_000 * PER_USE_RATE);
+    }
+}
@@ -2,6 +2,7 @@ import * as anchor from '@project-serum/anchor';
 import { Program } from '@project-serum/anchor';
 import { NftVaultPrototype } from '../target/types/nft_vault_prototype';
 import { assert } from 'chai'
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import { mintNft } from "./mint-nft-helper";
 import { printBalance, convertBasisPointsToPercentage } from './utils';
 
@@ -255,6 +256,9 @@ describe('nft-vault-prototype', () => {
      */
     let collectionConfig = await program.account.collectionConfiguration.fetch(pdaCollectionConfigAddress);
 
+    // Accounts are created on the current layout version
+    assert.equal(collectionConfig.version, 1);
+
     // Collection authority
     let collectionAuthorityResult = collectionConfig.collectionAuthority.toBase58();
     assert.equal(collectionAuthorityResult, collectionAuthority.publicKey.toBase58());
@@ -711,6 +715,65 @@ describe('nft-vault-prototype', () => {
       "confirmed"
     );
 
+    // License the deal is paid under, User 1 pays for the account
+    const contentHash = Buffer.alloc(32, 7);
+    const [licensePda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("license"), user1.publicKey.toBuffer(), contentHash], program.programId);
+    const [licenseMintPda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("license-mint"), licensePda.toBuffer()], program.programId);
+    const [licenseTokenPda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("license-token"), licensePda.toBuffer()], program.programId);
+    const [licenseAuthorityPda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("license-authority")], program.programId);
+    const [feeSchedulePda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("fee-schedule")], program.programId);
+
+    // Every usage type costs the airdrop, commercial licenses of 1000 units and up twice that
+    const usageTypes = [{ commercial: {} }, { broadcast: {} }, { games: {} }, { personal: {} }];
+    const feeTiers = usageTypes.map((usageType) => ({ usageType, minVolume: new anchor.BN(0), price: new anchor.BN(airdropAmount) }));
+    feeTiers.push({ usageType: { commercial: {} }, minVolume: new anchor.BN(1000), price: new anchor.BN(2 * airdropAmount) });
+    await provider.connection.confirmTransaction(
+      await program.rpc.initializeFeeSchedule(
+        feeTiers,
+        {
+          accounts: {
+            feeSchedule: feeSchedulePda,
+            collectionConfig: pdaCollectionConfigAddress,
+            collectionAuthority: collectionAuthority.publicKey,
+            payer: collectionAuthority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [collectionAuthority]
+        }
+      ));
+    const slot = await provider.connection.getSlot();
+    await provider.connection.confirmTransaction(
+      await program.rpc.createLicense(
+        [...contentHash],
+        "https://example.com/licenses/sync.pdf",
+        new anchor.BN(airdropAmount), // fee
+        { commercial: {} },
+        new anchor.BN(slot),
+        new anchor.BN(slot + 100000),
+        new anchor.BN(1000), // grace slots
+        new anchor.BN(500), // volume, priced at the base commercial tier
+        [], // licensed NFTs, the whole collection shares the holder split
+        new anchor.BN(0), // track share
+        null, // reporter, the licensee
+        new anchor.BN(0), // per-use rate, paid per term
+        {
+          accounts: {
+            license: licensePda,
+            licenseMint: licenseMintPda,
+            licenseTokenAccount: licenseTokenPda,
+            licenseAuthority: licenseAuthorityPda,
+            collectionConfig: pdaCollectionConfigAddress,
+            nftBalanceLedger: pdaNftLedgerAddress,
+            collectionAuthority: collectionAuthority.publicKey,
+            licensee: user1.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY
+          },
+          signers: [collectionAuthority, user1]
+        },
+      ));
+
     const user1_Before = await provider.connection.getBalance(user1.publicKey);
     printBalance(provider, [user1.publicKey], ['before - user1']);
 
@@ -720,6 +783,12 @@ describe('nft-vault-prototype', () => {
     const artistLedger_Before = await (await program.account.artistBalanceLedger.fetch(artistLedgerPda)).artistBalances as any[]; // [0].royaltiesBalance.toNumber();
     const nftLedger_Before = await (await program.account.nftBalanceLedger.fetch(pdaNftLedgerAddress)).nftBalances as any[];
 
+    // Capture the distribution event emitted by the payment
+    let distributionListener;
+    const distributionEvent = new Promise<any>((resolve) => {
+      distributionListener = program.addEventListener("RoyaltiesDistributed", (event) => resolve(event));
+    });
+
     // Distribute secondary pool
     await provider.connection.confirmTransaction(
       await program.rpc.payLicensingFee(
@@ -727,11 +796,17 @@ describe('nft-vault-prototype', () => {
         {
           accounts: {
             from: user1.publicKey,
+            license: licensePda,
+            licenseMint: licenseMintPda,
+            licenseTokenAccount: licenseTokenPda,
+            licenseAuthority: licenseAuthorityPda,
             pdaVault: pdaVaultAddress,
             collectionConfig: pdaCollectionConfigAddress,
+            feeSchedule: feeSchedulePda,
             artistBalanceLedger: artistLedgerPda,
             nftBalanceLedger: pdaNftLedgerAddress,
-            systemProgram: anchor.web3.SystemProgram.programId
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID
           },
           signers: [user1]
         },
@@ -770,6 +845,527 @@ describe('nft-vault-prototype', () => {
       const difference = nftLedger_After[i].royaltiesBalance.toNumber() - nftLedger_Before[i].royaltiesBalance.toNumber();
       assert.equal(difference, amountDistributedToEachMember);
     }
+
+    /**
+     * Verify distribution event
+     */
+    const event = await distributionEvent;
+    await program.removeEventListener(distributionListener);
+
+    assert.deepEqual(event.stream, { licensing: {} });
+    assert.equal(event.amount.toNumber(), airdropAmount);
+    assert.equal(event.artistTotal.toNumber(), amountDistributedToArtists);
+    assert.equal(event.holderTotal.toNumber(), Math.floor(airdropAmount * convertBasisPointsToPercentage(label_licensing_percentage)));
+    assert.equal(event.perNftShare.toNumber(), amountDistributedToEachMember);
+    assert.equal(event.nftCount.toNumber(), nftLedger_After.length);
+
+    /**
+     * Verify the payment was recorded on the license
+     */
+    const license = await program.account.license.fetch(licensePda);
+    assert.equal(license.licensee.toBase58(), user1.publicKey.toBase58());
+    assert.equal(license.paidToDate.toNumber(), airdropAmount);
+    assert.equal(license.termSlots.toNumber(), 100000);
+    assert.equal(license.graceSlots.toNumber(), 1000);
+    assert.deepEqual(license.status, { active: {} });
+    assert.equal(license.feeTier.price.toNumber(), airdropAmount);
+
+    /**
+     * Verify the license token was issued and the license verifies
+     */
+    const licenseToken = await provider.connection.getTokenAccountBalance(licenseTokenPda);
+    assert.equal(licenseToken.value.amount, "1");
+    await program.rpc.verifyLicense({
+      accounts: {
+        license: licensePda,
+        licenseTokenAccount: licenseTokenPda,
+        licensee: user1.publicKey
+      }
+    });
+  })
+
+  it('Holder governance proposal and voting!', async () => {
+    const [holderGovernancePda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("holder-governance")], program.programId);
+    const [holderProposalPda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("holder-proposal"), new anchor.BN(0).toArrayLike(Buffer, "le", 8)], program.programId);
+
+    const quorumPercentage = 5000;
+    const votingPeriod = 60 * 60;
+
+    await provider.connection.confirmTransaction(
+      await program.rpc.initializeHolderGovernance(
+        new anchor.BN(quorumPercentage),
+        new anchor.BN(votingPeriod),
+        {
+          accounts: {
+            holderGovernance: holderGovernancePda,
+            collectionConfig: pdaCollectionConfigAddress,
+            collectionAuthority: collectionAuthority.publicKey,
+            payer: collectionAuthority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [collectionAuthority]
+        }
+      ));
+
+    const nft1AssociatedAccount = (await provider.connection.getTokenLargestAccounts(nft_1.mintAddress)).value[0].address;
+    const nft2AssociatedAccount = (await provider.connection.getTokenLargestAccounts(nft_2.mintAddress)).value[0].address;
+
+    /**
+     * Scenario 1: Holder of nft_1 proposes to raise holder licensing share to 50%
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.createHolderProposal(
+        {
+          updateHolderPercentages: {
+            labelMintPercentage: new anchor.BN(label_mint_percentage),
+            labelSecondaryPercentage: new anchor.BN(label_secondary_percentage),
+            labelLicensingPercentage: new anchor.BN(5000),
+          }
+        },
+        {
+          accounts: {
+            holderGovernance: holderGovernancePda,
+            holderProposal: holderProposalPda,
+            nftBalanceLedger: pdaNftLedgerAddress,
+            nft: nft_1.mintAddress,
+            nftAssociatedAccount: nft1AssociatedAccount,
+            proposer: nft_1.ownerKeypair.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [nft_1.ownerKeypair]
+        }
+      ));
+
+    let proposal = await program.account.holderProposal.fetch(holderProposalPda);
+    assert.equal(proposal.snapshotSize.toNumber(), 3);
+    assert.equal(proposal.quorumPercentage.toNumber(), quorumPercentage);
+
+    /**
+     * Scenario 2: Holders vote, one vote per NFT
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.castHolderVote(
+        true,
+        {
+          accounts: {
+            holderProposal: holderProposalPda,
+            nftBalanceLedger: pdaNftLedgerAddress,
+            nft: nft_1.mintAddress,
+            nftAssociatedAccount: nft1AssociatedAccount,
+            holder: nft_1.ownerKeypair.publicKey
+          },
+          signers: [nft_1.ownerKeypair]
+        }
+      ));
+
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.castHolderVote(
+          true,
+          {
+            accounts: {
+              holderProposal: holderProposalPda,
+              nftBalanceLedger: pdaNftLedgerAddress,
+              nft: nft_1.mintAddress,
+              nftAssociatedAccount: nft1AssociatedAccount,
+              holder: nft_1.ownerKeypair.publicKey
+            },
+            signers: [nft_1.ownerKeypair]
+          }
+        ));
+      assert.fail("NFT should not vote twice");
+    } catch (err) {
+      assert.equal("Error: NFT already voted on this proposal", err.toString());
+    }
+
+    // Only the token holder can vote with an NFT
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.castHolderVote(
+          true,
+          {
+            accounts: {
+              holderProposal: holderProposalPda,
+              nftBalanceLedger: pdaNftLedgerAddress,
+              nft: nft_2.mintAddress,
+              nftAssociatedAccount: nft2AssociatedAccount,
+              holder: nft_1.ownerKeypair.publicKey
+            },
+            signers: [nft_1.ownerKeypair]
+          }
+        ));
+      assert.fail("non holder should not vote");
+    } catch (err) {
+      assert.equal("Error: NFT is not owned by withdrawer", err.toString());
+    }
+
+    await provider.connection.confirmTransaction(
+      await program.rpc.castHolderVote(
+        false,
+        {
+          accounts: {
+            holderProposal: holderProposalPda,
+            nftBalanceLedger: pdaNftLedgerAddress,
+            nft: nft_2.mintAddress,
+            nftAssociatedAccount: nft2AssociatedAccount,
+            holder: nft_2.ownerKeypair.publicKey
+          },
+          signers: [nft_2.ownerKeypair]
+        }
+      ));
+
+    proposal = await program.account.holderProposal.fetch(holderProposalPda);
+    assert.equal(proposal.yesVotes.toNumber(), 1);
+    assert.equal(proposal.noVotes.toNumber(), 1);
+
+    /**
+     * Scenario 3: Can't execute while voting is open
+     */
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.executeHolderProposal(
+          {
+            accounts: {
+              holderProposal: holderProposalPda,
+              collectionConfig: pdaCollectionConfigAddress,
+              feeSchedule: (await anchor.web3.PublicKey.findProgramAddress([Buffer.from("fee-schedule")], program.programId))[0]
+            }
+          }
+        ));
+      assert.fail("execute during voting period should fail");
+    } catch (err) {
+      assert.equal("Error: Voting period for this proposal is still active", err.toString());
+    }
+  })
+
+  it('Propose, approve and cancel config update!', async () => {
+    const [configUpdatePda] = await anchor.web3.PublicKey.findProgramAddress([Buffer.from("config-update")], program.programId);
+    const secondArtist = anchor.web3.Keypair.generate();
+
+    /**
+     * Scenario 1: Try to propose splits that don't add up to 100%
+     */
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.proposeConfigUpdate(
+          new anchor.BN(artist_mint_percentage),
+          new anchor.BN(label_mint_percentage),
+          new anchor.BN(artist_secondary_percentage),
+          new anchor.BN(label_secondary_percentage),
+          new anchor.BN(artist_licensing_percentage),
+          new anchor.BN(label_licensing_percentage),
+          [
+            { artistAddress: artistAddress, allocatedPercentage: new anchor.BN(7000) },
+            { artistAddress: secondArtist.publicKey, allocatedPercentage: new anchor.BN(2000) },
+          ],
+          {
+            accounts: {
+              configUpdateProposal: configUpdatePda,
+              collectionConfig: pdaCollectionConfigAddress,
+              collectionAuthority: collectionAuthority.publicKey,
+              payer: collectionAuthority.publicKey,
+              systemProgram: anchor.web3.SystemProgram.programId
+            },
+            signers: [collectionAuthority]
+          }
+        ));
+      assert.fail("proposal with invalid splits should fail");
+    } catch (err) {
+      assert.equal("Error: Invalid Collection Config parameters", err.toString());
+    }
+
+    /**
+     * Scenario 2: Propose moving 30% of the artist split to a second artist
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.proposeConfigUpdate(
+        new anchor.BN(artist_mint_percentage),
+        new anchor.BN(label_mint_percentage),
+        new anchor.BN(artist_secondary_percentage),
+        new anchor.BN(label_secondary_percentage),
+        new anchor.BN(artist_licensing_percentage),
+        new anchor.BN(label_licensing_percentage),
+        [
+          { artistAddress: artistAddress, allocatedPercentage: new anchor.BN(7000) },
+          { artistAddress: secondArtist.publicKey, allocatedPercentage: new anchor.BN(3000) },
+        ],
+        {
+          accounts: {
+            configUpdateProposal: configUpdatePda,
+            collectionConfig: pdaCollectionConfigAddress,
+            collectionAuthority: collectionAuthority.publicKey,
+            payer: collectionAuthority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [collectionAuthority]
+        }
+      ));
+
+    let proposal = await program.account.configUpdateProposal.fetch(configUpdatePda);
+    assert.equal(proposal.requiredApprovals.length, 1);
+    assert.equal(proposal.requiredApprovals[0].toBase58(), artistAddress.toBase58());
+
+    /**
+     * Scenario 3: Executing before the timelock passes fails
+     */
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.executeConfigUpdate(
+          {
+            accounts: {
+              configUpdateProposal: configUpdatePda,
+              collectionConfig: pdaCollectionConfigAddress,
+              artistBalanceLedger: artistLedgerPda,
+              proposer: collectionAuthority.publicKey,
+              payer: collectionAuthority.publicKey,
+              systemProgram: anchor.web3.SystemProgram.programId
+            },
+            signers: [collectionAuthority]
+          }
+        ));
+      assert.fail("execute before timelock should fail");
+    } catch (err) {
+      assert.equal("Error: Config update timelock has not passed", err.toString());
+    }
+
+    /**
+     * Scenario 4: Only affected artists can approve
+     */
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.approveConfigUpdate(
+          {
+            accounts: {
+              configUpdateProposal: configUpdatePda,
+              artist: secondArtist.publicKey
+            },
+            signers: [secondArtist]
+          }
+        ));
+      assert.fail("unaffected artist should not approve");
+    } catch (err) {
+      assert.equal("Error: Artist address not found in ledger", err.toString());
+    }
+
+    await provider.connection.confirmTransaction(
+      await program.rpc.approveConfigUpdate(
+        {
+          accounts: {
+            configUpdateProposal: configUpdatePda,
+            artist: artistAddress
+          },
+          signers: [artistKeypair]
+        }
+      ));
+
+    proposal = await program.account.configUpdateProposal.fetch(configUpdatePda);
+    assert.equal(proposal.approvals.length, 1);
+
+    /**
+     * Scenario 5: Cancel the proposal, config stays unchanged
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.cancelConfigUpdate(
+        {
+          accounts: {
+            configUpdateProposal: configUpdatePda,
+            collectionConfig: pdaCollectionConfigAddress,
+            collectionAuthority: collectionAuthority.publicKey,
+            proposer: collectionAuthority.publicKey
+          },
+          signers: [collectionAuthority]
+        }
+      ));
+
+    assert.equal(await provider.connection.getAccountInfo(configUpdatePda), null);
+
+    const collectionConfig = await program.account.collectionConfiguration.fetch(pdaCollectionConfigAddress);
+    assert.equal((collectionConfig.artistSplits as any[]).length, 1);
+  })
+
+  it('Artist transfers part of their split!', async () => {
+    const newArtist = anchor.web3.Keypair.generate();
+
+    const artistLedger_Before = await (await program.account.artistBalanceLedger.fetch(artistLedgerPda)).artistBalances as any[];
+
+    /**
+     * Scenario 1: Can't transfer more than the allocated percentage
+     */
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.transferArtistSplit(
+          new anchor.BN(10001),
+          {
+            accounts: {
+              collectionConfig: pdaCollectionConfigAddress,
+              artistBalanceLedger: artistLedgerPda,
+              artist: artistAddress,
+              newArtist: newArtist.publicKey,
+              payer: collectionAuthority.publicKey,
+              systemProgram: anchor.web3.SystemProgram.programId
+            },
+            signers: [artistKeypair, collectionAuthority]
+          }
+        ));
+      assert.fail("transfer above allocated percentage should fail");
+    } catch (err) {
+      assert.equal("Error: Invalid artist split transfer", err.toString());
+    }
+
+    /**
+     * Scenario 2: Transfer 30% to a new address
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.transferArtistSplit(
+        new anchor.BN(3000),
+        {
+          accounts: {
+            collectionConfig: pdaCollectionConfigAddress,
+            artistBalanceLedger: artistLedgerPda,
+            artist: artistAddress,
+            newArtist: newArtist.publicKey,
+            payer: collectionAuthority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [artistKeypair, collectionAuthority]
+        }
+      ));
+
+    const collectionConfig = await program.account.collectionConfiguration.fetch(pdaCollectionConfigAddress);
+    const artistSplits = collectionConfig.artistSplits as any[];
+    assert.equal(artistSplits.length, 2);
+    assert.equal(artistSplits[0].allocatedPercentage.toNumber(), 7000);
+    assert.equal(artistSplits[1].artistAddress.toBase58(), newArtist.publicKey.toBase58());
+    assert.equal(artistSplits[1].allocatedPercentage.toNumber(), 3000);
+
+    /**
+     * Accrued balances are untouched, new artist starts at 0
+     */
+    const artistLedger_After = await (await program.account.artistBalanceLedger.fetch(artistLedgerPda)).artistBalances as any[];
+    assert.equal(artistLedger_After.length, 2);
+    assert.equal(artistLedger_After[0].royaltiesBalance.toNumber(), artistLedger_Before[0].royaltiesBalance.toNumber());
+    assert.equal(artistLedger_After[1].royaltiesBalance.toNumber(), 0);
+  })
+
+  it('Remove and add artists!', async () => {
+    const lateArtist = anchor.web3.Keypair.generate();
+
+    let collectionConfig = await program.account.collectionConfiguration.fetch(pdaCollectionConfigAddress);
+    const secondArtistAddress = (collectionConfig.artistSplits as any[])[1].artistAddress;
+
+    /**
+     * Scenario 1: Pro-rata dilution needs every current artist to sign
+     */
+    try {
+      await provider.connection.confirmTransaction(
+        await program.rpc.addArtist(
+          new anchor.BN(1000),
+          { proRata: {} },
+          {
+            accounts: {
+              collectionConfig: pdaCollectionConfigAddress,
+              artistBalanceLedger: artistLedgerPda,
+              collectionAuthority: collectionAuthority.publicKey,
+              newArtist: lateArtist.publicKey,
+              payer: collectionAuthority.publicKey,
+              systemProgram: anchor.web3.SystemProgram.programId
+            },
+            signers: [collectionAuthority]
+          }
+        ));
+      assert.fail("add artist without consensus should fail");
+    } catch (err) {
+      assert.equal("Error: Missing artist signatures", err.toString());
+    }
+
+    /**
+     * Scenario 2: Remove second artist, their 30% goes to the reserve
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.removeArtist(
+        { reserve: {} },
+        {
+          accounts: {
+            collectionConfig: pdaCollectionConfigAddress,
+            collectionAuthority: collectionAuthority.publicKey,
+            removedArtist: secondArtistAddress
+          },
+          remainingAccounts: [{ pubkey: artistAddress, isSigner: true, isWritable: false }],
+          signers: [collectionAuthority, artistKeypair]
+        }
+      ));
+
+    collectionConfig = await program.account.collectionConfiguration.fetch(pdaCollectionConfigAddress);
+    assert.equal((collectionConfig.artistSplits as any[]).length, 1);
+    assert.equal(collectionConfig.artistReservePercentage.toNumber(), 3000);
+
+    /**
+     * Scenario 3: Add a late artist from the reserve
+     */
+    await provider.connection.confirmTransaction(
+      await program.rpc.addArtist(
+        new anchor.BN(3000),
+        { reserve: {} },
+        {
+          accounts: {
+            collectionConfig: pdaCollectionConfigAddress,
+            artistBalanceLedger: artistLedgerPda,
+            collectionAuthority: collectionAuthority.publicKey,
+            newArtist: lateArtist.publicKey,
+            payer: collectionAuthority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [collectionAuthority]
+        }
+      ));
+
+    collectionConfig = await program.account.collectionConfiguration.fetch(pdaCollectionConfigAddress);
+    const artistSplits = collectionConfig.artistSplits as any[];
+    assert.equal(artistSplits.length, 2);
+    assert.equal(artistSplits[1].artistAddress.toBase58(), lateArtist.publicKey.toBase58());
+    assert.equal(artistSplits[1].allocatedPercentage.toNumber(), 3000);
+    assert.equal(collectionConfig.artistReservePercentage.toNumber(), 0);
+
+    // Removed artist keeps their ledger entry
+    const artistBalanceLedger = await program.account.artistBalanceLedger.fetch(artistLedgerPda);
+    assert.equal((artistBalanceLedger.artistBalances as any[]).length, 3);
+    assert.equal(artistBalanceLedger.size.toNumber(), 3);
+  })
+
+  it('Expand ledgers!', async () => {
+    const artistCapacity = 20;
+    const nftCapacity = 400;
+
+    await provider.connection.confirmTransaction(
+      await program.rpc.expandLedger(
+        new anchor.BN(artistCapacity),
+        new anchor.BN(nftCapacity),
+        {
+          accounts: {
+            collectionConfig: pdaCollectionConfigAddress,
+            artistBalanceLedger: artistLedgerPda,
+            nftBalanceLedger: pdaNftLedgerAddress,
+            payer: collectionAuthority.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId
+          },
+          signers: [collectionAuthority]
+        }
+      ));
+
+    // discriminator + version + fixed fields + 40 bytes per entry
+    const configInfo = await provider.connection.getAccountInfo(pdaCollectionConfigAddress);
+    const artistLedgerInfo = await provider.connection.getAccountInfo(artistLedgerPda);
+    const nftLedgerInfo = await provider.connection.getAccountInfo(pdaNftLedgerAddress);
+    assert.equal(configInfo.data.length, 101 + 40 * artistCapacity);
+    assert.equal(artistLedgerInfo.data.length, 21 + 40 * artistCapacity);
+    assert.equal(nftLedgerInfo.data.length, 21 + 40 * nftCapacity);
+
+    // Accounts stay rent exempt after growing
+    assert.isAtLeast(nftLedgerInfo.lamports, await provider.connection.getMinimumBalanceForRentExemption(nftLedgerInfo.data.length));
+
+    // Existing balances survive the realloc
+    const nftBalanceLedger = await program.account.nftBalanceLedger.fetch(pdaNftLedgerAddress);
+    assert.equal((nftBalanceLedger.nftBalances as any[]).length, 3);
   })
 
   it('Transfer Collection Authority!', async () => {